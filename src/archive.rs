@@ -0,0 +1,208 @@
+use std::fs;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::records::serialize_record;
+use crate::staged_files::StagedFiles;
+use crate::{
+    check_size_budget, check_unique_output_paths, create_output_directory, output_mode, read_resource,
+    stage_names_manifest, stage_rust_module, CollationError, CollationPlan, CollationSummary, ReportingTrait,
+    ResolvedResource, ResourceName, RunOptions, DEFAULT_RECORD_FILE_NAME,
+};
+
+/// A file to write into an archive, at its path within the archive
+pub(crate) struct ArchiveEntry {
+    pub(crate) path: String,
+    pub(crate) content: Vec<u8>,
+    pub(crate) mode: Option<u32>,
+}
+
+/// Collate the plan's resources into an archive at the given path, each at its output path, with
+/// the record of resolved resources (when written) at the top level. The archive's content is
+/// given by the write function, e.g. as a zip.
+///
+/// The archive is only written when its content has changed, in which case every resource is
+/// reported as copied, otherwise as already up to date. Any Rust module or names manifest is
+/// staged, to be moved into place with the other files collation writes.
+pub(crate) fn collate_to_archive(
+    plan: &CollationPlan,
+    archive_path: &Utf8Path,
+    run_options: &RunOptions,
+    reporter: &dyn ReportingTrait,
+    staged: &mut StagedFiles,
+    write: fn(&[ArchiveEntry]) -> Result<Vec<u8>, CollationError>,
+) -> Result<CollationSummary, CollationError> {
+    if plan.resources.is_empty() {
+        reporter.report_no_resources_found();
+    }
+
+    // An archive can hold several entries with the same path (of which only one could be
+    // extracted), so they are rejected, whatever the resources' groups
+    check_unique_output_paths(plan.resources.iter().map(|planned_resource| {
+        let res_dec = &planned_resource.specification;
+        (res_dec.output_path.to_owned(), &res_dec.resource_name)
+    }))?;
+
+    let mut entries = vec!();
+    let mut resolved_resources = vec!();
+    for planned_resource in &plan.resources {
+        let res_dec = &planned_resource.specification;
+        let content = read_resource(planned_resource, &plan.consumer)?;
+        let mode = output_mode(&res_dec.full_crate_path, res_dec, &plan.consumer)?;
+        resolved_resources.push(ResolvedResource {
+            specification: res_dec.clone(),
+            output_sha: planned_resource.expected_sha.to_owned(),
+            source_sha: planned_resource.source_sha.to_owned(),
+            hash_algorithm: plan.consumer.hash_algorithm,
+            size: content.len() as u64,
+            mode,
+            modified: None,
+            source_modified: None,
+        });
+        entries.push(ArchiveEntry { path: entry_path(&res_dec.output_path), content, mode });
+    }
+    let sizes: Vec<(ResourceName, u64)> = resolved_resources.iter()
+        .map(|resource| (resource.specification.resource_name.to_owned(), resource.size))
+        .collect();
+    check_size_budget(&sizes, &plan.consumer)?;
+
+    if run_options.write_record {
+        let record = serialize_record(&resolved_resources, run_options.record_format)?;
+        entries.push(ArchiveEntry {
+            path: Utf8Path::new(DEFAULT_RECORD_FILE_NAME).with_extension(run_options.record_format.extension()).to_string(),
+            content: record.into_bytes(),
+            mode: None,
+        });
+    }
+
+    let archive = write(&entries)?;
+    let up_to_date = fs::read(archive_path).is_ok_and(|existing| existing == archive);
+    for resolved_resource in &resolved_resources {
+        let entry = archive_path.join(&resolved_resource.specification.output_path);
+        match run_options.dry_run {
+            true => reporter.report_dry_run_collection(
+                &resolved_resource.specification, up_to_date, &entry, &resolved_resource.output_sha,
+            ),
+            false => reporter.report_resource_collection(
+                &resolved_resource.specification, up_to_date, &entry, &resolved_resource.output_sha,
+            ),
+        }
+    }
+
+    if !run_options.dry_run {
+        if !up_to_date {
+            write_archive(archive_path, &archive)?;
+        }
+        if let Some(rust_module) = &run_options.rust_module {
+            stage_rust_module(plan, &plan.crate_root.join(rust_module), staged)?;
+        }
+        if let Some(names_manifest) = &run_options.names_manifest {
+            stage_names_manifest(plan, &plan.crate_root.join(names_manifest), staged)?;
+        }
+    }
+
+    let mut summary = CollationSummary {
+        resource_root: archive_path.to_owned(),
+        copied: vec!(),
+        skipped: vec!(),
+        missing_optional: plan.missing_optional.to_owned(),
+        bytes_copied: 0,
+        total_bytes: resolved_resources.iter().map(|resource| resource.size).sum(),
+    };
+    match up_to_date {
+        true => summary.skipped = resolved_resources,
+        false => {
+            summary.bytes_copied = resolved_resources.iter().map(|resource| resource.size).sum();
+            summary.copied = resolved_resources;
+        }
+    }
+    Ok(summary)
+}
+
+/// The path of an archive entry for an output path, always separated by forward slashes
+fn entry_path(output_path: &Utf8Path) -> String {
+    output_path.components().map(|component| component.as_str()).collect::<Vec<&str>>().join("/")
+}
+
+/// Write the archive by way of a temporary file, renamed into place once complete, so it is never
+/// seen part written
+fn write_archive(archive_path: &Utf8Path, archive: &[u8]) -> Result<(), CollationError> {
+    if let Some(archive_directory) = archive_path.parent() {
+        create_output_directory(archive_directory, None)?;
+    }
+    let file_name = archive_path.file_name().expect("No archive file name!");
+    let temporary: Utf8PathBuf = archive_path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+    let result = fs::write(&temporary, archive)
+        .map_err(|e| CollationError::io(format!("Unable to write archive {}", archive_path), e))
+        .and_then(|_| fs::rename(&temporary, archive_path)
+            .map_err(|e| CollationError::io(format!("Unable to move {} into place at {}", &temporary, archive_path), e)));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Write the entries as a zip archive, deflated and with a fixed modified time (the earliest a zip
+/// can hold) so the same resources always give the same archive
+#[cfg(feature = "zip")]
+pub(crate) fn write_zip_archive(entries: &[ArchiveEntry]) -> Result<Vec<u8>, CollationError> {
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, DateTime, ZipWriter};
+
+    let unwritable = |e: zip::result::ZipError| CollationError::serialize("Unable to write the zip archive", e);
+    let mut zip = ZipWriter::new(Cursor::new(vec!()));
+    for entry in entries {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(DateTime::default())
+            .unix_permissions(entry.mode.unwrap_or(0o644));
+        zip.start_file(entry.path.as_str(), options).map_err(unwritable)?;
+        zip.write_all(&entry.content).map_err(|e| unwritable(e.into()))?;
+    }
+    Ok(zip.finish().map_err(unwritable)?.into_inner())
+}
+
+/// The modified time given to every tar entry, 1980-01-01 (as for zip entries), so the same
+/// resources always give the same archive
+#[cfg(feature = "tar")]
+const TAR_ENTRY_MTIME: u64 = 315_532_800;
+
+/// Whether a tar archive's path asks for it to be gzipped, i.e. ends in .tar.gz or .tgz
+#[cfg(feature = "tar")]
+pub(crate) fn is_gzipped(archive_path: &Utf8Path) -> bool {
+    let file_name = archive_path.file_name().unwrap_or_default().to_ascii_lowercase();
+    file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz")
+}
+
+/// Write the entries as a tar archive, each a regular file owned by root with its mode (or 0o644)
+/// and a fixed modified time, so the same resources always give the same archive
+#[cfg(feature = "tar")]
+pub(crate) fn write_tar_archive(entries: &[ArchiveEntry]) -> Result<Vec<u8>, CollationError> {
+    let unwritable = |e: std::io::Error| CollationError::io("Unable to write the tar archive", e);
+    let mut tar = tar::Builder::new(vec!());
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(entry.content.len() as u64);
+        header.set_mode(entry.mode.unwrap_or(0o644));
+        header.set_mtime(TAR_ENTRY_MTIME);
+        header.set_uid(0);
+        header.set_gid(0);
+        tar.append_data(&mut header, &entry.path, entry.content.as_slice()).map_err(unwritable)?;
+    }
+    tar.into_inner().map_err(unwritable)
+}
+
+/// Write the entries as a gzipped tar archive, the gzip header having no modified time or file
+/// name (so it too is the same for the same resources)
+#[cfg(feature = "tar")]
+pub(crate) fn write_tar_gz_archive(entries: &[ArchiveEntry]) -> Result<Vec<u8>, CollationError> {
+    use std::io::Write;
+
+    let unwritable = |e: std::io::Error| CollationError::io("Unable to write the gzipped tar archive", e);
+    let tar = write_tar_archive(entries)?;
+    let mut gz = flate2::write::GzEncoder::new(vec!(), flate2::Compression::default());
+    gz.write_all(&tar).map_err(unwritable)?;
+    gz.finish().map_err(unwritable)
+}
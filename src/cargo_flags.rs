@@ -0,0 +1,31 @@
+/// The flags passed to cargo when reading the dependency graph (with cargo metadata), so it can be
+/// kept off the network and from updating Cargo.lock, e.g. in a sandboxed CI build
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CargoFlags {
+    /// Run without accessing the network (--offline)
+    pub offline: bool,
+
+    /// Require Cargo.lock and the dependency cache to be up to date (--frozen, which is --offline
+    /// and --locked together)
+    pub frozen: bool,
+
+    /// Require Cargo.lock to be up to date, failing rather than updating it (--locked)
+    pub locked: bool,
+}
+
+impl CargoFlags {
+    /// The command line options for the flags that are set
+    pub(crate) fn other_options(&self) -> Vec<String> {
+        [(self.offline, "--offline"), (self.frozen, "--frozen"), (self.locked, "--locked")]
+            .into_iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, option)| option.to_string())
+            .collect()
+    }
+
+    /// Whether lock files mustn't be updated (--locked or --frozen), so the resources must also
+    /// match the cargo-resources.lock
+    pub(crate) fn is_locked(&self) -> bool {
+        self.locked || self.frozen
+    }
+}
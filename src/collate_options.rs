@@ -0,0 +1,455 @@
+use cargo_metadata::camino::Utf8PathBuf;
+
+use crate::{
+    CargoFlags, CopyMode, DefaultReporter, DependencyScope, DuplicateStrategy, FeatureSelection, HashAlgorithm, LineEndings,
+    OutputSink, OutputTarget, RecordFormat, ReportingTrait, SriAlgorithm, DEFAULT_HASH_BUFFER_SIZE,
+};
+
+/// The options for a collation, as used by [crate::collate_with_options].
+///
+/// The defaults collate as [crate::collate_resources] does, and each option can be set in turn:
+/// ```
+/// use cargo_resources::{BuildRsReporter, CollateOptions, HashAlgorithm};
+///
+/// let options = CollateOptions::default()
+///     .reporter(Box::new(BuildRsReporter))
+///     .hash_algorithm(HashAlgorithm::Sha512)
+///     .dry_run(true);
+/// ```
+pub struct CollateOptions {
+    /// The resource root to use instead of the consuming crate's (or the default)
+    pub resource_root_override: Option<Utf8PathBuf>,
+
+    /// The target triple to substitute for {triple} in the resource root, instead of the TARGET
+    /// environment variable's
+    pub target: Option<String>,
+
+    /// Where to report progress and problems, the console by default
+    pub reporter: Box<dyn ReportingTrait>,
+
+    /// When true, report what collation would do without touching the disk
+    pub dry_run: bool,
+
+    /// When true, remove the previously collated resources that are no longer required
+    pub prune: bool,
+
+    /// How resources are placed in the resource root, copied by default
+    pub copy_mode: CopyMode,
+
+    /// When true, sources and existing resources are always hashed, rather than trusting the size
+    /// and modified time recorded for them
+    pub always_hash: bool,
+
+    /// When true, each copied resource is hashed again, and collation fails if it doesn't have its
+    /// source's sha
+    pub post_verify: bool,
+
+    /// When true, a checksum file (e.g. logo.png.sha256) is written beside each collated resource
+    pub emit_checksums: bool,
+
+    /// The hash algorithm to use instead of the consuming crate's (or the default, SHA 256)
+    pub hash_algorithm: Option<HashAlgorithm>,
+
+    /// The line endings to normalize text resources to, instead of the consuming crate's (which
+    /// leaves them unchanged when not set)
+    pub line_endings: Option<LineEndings>,
+
+    /// Whether file names include the start of the resource's sha, instead of as the consuming
+    /// crate asks
+    pub hashed_filenames: Option<bool>,
+
+    /// Whether declared resources missing from the consuming crate's requires list fail collation,
+    /// instead of as the consuming crate asks (each being reported otherwise)
+    pub deny_unused: Option<bool>,
+
+    /// When false, resources not matching the consuming crate's required shas are collated (with a
+    /// warning) rather than failing collation, for development only
+    pub verify_shas: bool,
+
+    /// The size, in bytes, of the blocks read from a file to hash it, [DEFAULT_HASH_BUFFER_SIZE] by
+    /// default
+    pub hash_buffer_size: usize,
+
+    /// The cargo features enabled when resolving dependencies, all features by default
+    pub features: FeatureSelection,
+
+    /// The flags (--offline, --frozen or --locked) passed to cargo when resolving dependencies,
+    /// none by default
+    pub cargo_flags: CargoFlags,
+
+    /// Which declaration is used when a resource name is declared more than once, the last scanned
+    /// by default
+    pub duplicate_strategy: DuplicateStrategy,
+
+    /// Which dependencies are searched for resources, all of them by default
+    pub dependency_scope: DependencyScope,
+
+    /// The crates whose resources are never collated, each a crate name optionally qualified by a
+    /// version, e.g. "examples" or "examples@1.2.0"
+    pub exclude_crates: Vec<String>,
+
+    /// Where the record of resolved resources is written (relative to the crate root), instead of
+    /// the resource root
+    pub record_path: Option<Utf8PathBuf>,
+
+    /// How the record of resolved resources is written, compact JSON by default
+    pub record_format: RecordFormat,
+
+    /// When false, no record of resolved resources is written (nor read, to prune)
+    pub write_record: bool,
+
+    /// Where to generate a Rust module mapping resource names to output paths (relative to the
+    /// crate root), if anywhere
+    pub generate_rust_module: Option<Utf8PathBuf>,
+
+    /// Where to write an integrity manifest mapping output paths to SRI strings (relative to the
+    /// crate root), if anywhere
+    pub integrity_manifest: Option<Utf8PathBuf>,
+
+    /// The hash used for the integrity manifest's SRI strings, SHA-384 by default
+    pub integrity_algorithm: SriAlgorithm,
+
+    /// Where to write a names manifest mapping resource names to output paths (relative to the
+    /// crate root), if anywhere
+    pub names_manifest: Option<Utf8PathBuf>,
+
+    /// Where the resources are written, the resource root by default
+    pub output_target: OutputTarget,
+
+    /// The sink the resources are written to instead of the output target, if any
+    pub sink: Option<Box<dyn OutputSink>>,
+}
+
+impl Default for CollateOptions {
+    fn default() -> Self {
+        CollateOptions {
+            resource_root_override: None,
+            target: None,
+            reporter: Box::new(DefaultReporter),
+            dry_run: false,
+            prune: false,
+            copy_mode: CopyMode::default(),
+            always_hash: false,
+            post_verify: false,
+            emit_checksums: false,
+            hash_algorithm: None,
+            line_endings: None,
+            hashed_filenames: None,
+            deny_unused: None,
+            verify_shas: true,
+            hash_buffer_size: DEFAULT_HASH_BUFFER_SIZE,
+            features: FeatureSelection::default(),
+            cargo_flags: CargoFlags::default(),
+            duplicate_strategy: DuplicateStrategy::default(),
+            dependency_scope: DependencyScope::default(),
+            exclude_crates: vec!(),
+            record_path: None,
+            record_format: RecordFormat::default(),
+            write_record: true,
+            generate_rust_module: None,
+            integrity_manifest: None,
+            names_manifest: None,
+            integrity_algorithm: SriAlgorithm::default(),
+            output_target: OutputTarget::default(),
+            sink: None,
+        }
+    }
+}
+
+impl CollateOptions {
+    /// Use the given resource root, instead of the consuming crate's
+    pub fn resource_root_override(mut self, resource_root: Utf8PathBuf) -> Self {
+        self.resource_root_override = Some(resource_root);
+        self
+    }
+
+    /// Substitute the given target triple for {triple} in the resource root (e.g. when collating
+    /// for cross-compilation outside a build script), instead of the TARGET environment variable
+    pub fn target(mut self, target: String) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Report progress and problems to the given reporter
+    pub fn reporter(mut self, reporter: Box<dyn ReportingTrait>) -> Self {
+        self.reporter = reporter;
+        self
+    }
+
+    /// Whether to only report what collation would do, without touching the disk
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Whether to remove the resources recorded by the previous collation that are no longer
+    /// required, once the required ones are in place (ignored for a dry run)
+    pub fn prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    /// Place resources in the resource root in the given way, e.g. linking rather than copying large
+    /// files (falling back to a copy, with a warning, when a link can't be made)
+    pub fn copy_mode(mut self, copy_mode: CopyMode) -> Self {
+        self.copy_mode = copy_mode;
+        self
+    }
+
+    /// Whether to always hash sources and existing resources, rather than taking a source with the
+    /// size and modified time recorded when it was collated to have its recorded sha (without
+    /// reading it), and a resource with those recorded for it to be up to date
+    pub fn always_hash(mut self, always_hash: bool) -> Self {
+        self.always_hash = always_hash;
+        self
+    }
+
+    /// Whether to hash each resource again once copied, failing if it doesn't have its source's sha
+    /// (e.g. on unreliable storage), and reporting that it was verified
+    pub fn post_verify(mut self, post_verify: bool) -> Self {
+        self.post_verify = post_verify;
+        self
+    }
+
+    /// Whether to write a checksum file beside each collated resource, named for the hash algorithm
+    /// (e.g. logo.png.sha256) and holding its hash and file name as sha256sum does
+    pub fn emit_checksums(mut self, emit_checksums: bool) -> Self {
+        self.emit_checksums = emit_checksums;
+        self
+    }
+
+    /// Use the given hash algorithm, instead of the consuming crate's
+    pub fn hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = Some(hash_algorithm);
+        self
+    }
+
+    /// Normalize the line endings of text (Txt encoded) resources as they are copied, instead of
+    /// as the consuming crate asks. Binary resources, including those detected as binary when no
+    /// encoding is declared, are never changed.
+    pub fn line_endings(mut self, line_endings: LineEndings) -> Self {
+        self.line_endings = Some(line_endings);
+        self
+    }
+
+    /// Whether to put the start of each resource's sha in its file name (e.g. css/app.3f2a1b9c.css),
+    /// instead of as the consuming crate asks, so deployed resources can be cached indefinitely.
+    ///
+    /// The resource root's hashed_filenames.json then maps each unhashed output path to the hashed
+    /// one, for looking them up at run time.
+    pub fn hashed_filenames(mut self, hashed_filenames: bool) -> Self {
+        self.hashed_filenames = Some(hashed_filenames);
+        self
+    }
+
+    /// Whether to fail, before copying, when a dependency declares a resource the consuming
+    /// crate's requires list doesn't include (instead of as the consuming crate asks), e.g. in CI to
+    /// catch a misconfigured list.
+    ///
+    /// Without it each such resource is only reported; a crate without a requires list collates
+    /// every resource, so none are unused.
+    pub fn deny_unused(mut self, deny_unused: bool) -> Self {
+        self.deny_unused = Some(deny_unused);
+        self
+    }
+
+    /// Whether to check resources against the consuming crate's required shas, as by default.
+    ///
+    /// Turning it off is an escape hatch for development, e.g. while changing an upstream resource
+    /// through a path override: a resource not matching is collated (and recorded with its actual
+    /// sha) with a warning, rather than failing collation. It is unsafe for CI, where the required
+    /// shas are what stops an unexpected change to a resource.
+    pub fn verify_shas(mut self, verify_shas: bool) -> Self {
+        self.verify_shas = verify_shas;
+        self
+    }
+
+    /// Hash files in blocks of the given size, in bytes, e.g. larger blocks for large media files
+    /// (which reduce the number of reads)
+    ///
+    /// The block size doesn't change the hashes.
+    pub fn hash_buffer_size(mut self, hash_buffer_size: usize) -> Self {
+        self.hash_buffer_size = hash_buffer_size;
+        self
+    }
+
+    /// Choose which declaration is used when a resource name is declared more than once, e.g. the
+    /// one by the highest crate version:
+    /// ```
+    /// use cargo_resources::{CollateOptions, DuplicateStrategy};
+    ///
+    /// let options = CollateOptions::default().duplicate_strategy(DuplicateStrategy::HighestVersion);
+    /// ```
+    pub fn duplicate_strategy(mut self, duplicate_strategy: DuplicateStrategy) -> Self {
+        self.duplicate_strategy = duplicate_strategy;
+        self
+    }
+
+    /// Search only the given dependencies for resources, e.g. just the direct ones (overriding
+    /// the consuming crate's max_depth)
+    pub fn dependency_scope(mut self, dependency_scope: DependencyScope) -> Self {
+        self.dependency_scope = dependency_scope;
+        self
+    }
+
+    /// Never collate the resources declared by the given crates, each a crate name optionally
+    /// qualified by an exact version, e.g. "examples" or "examples@1.2.0"
+    pub fn exclude_crates(mut self, exclude_crates: Vec<String>) -> Self {
+        self.exclude_crates = exclude_crates;
+        self
+    }
+
+    /// Enable the given cargo features when resolving dependencies
+    pub fn features(mut self, features: FeatureSelection) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Resolve dependencies without accessing the network, as cargo's --offline
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.cargo_flags.offline = offline;
+        self
+    }
+
+    /// Resolve dependencies only with an up to date Cargo.lock and dependency cache, as cargo's
+    /// --frozen, and (as for [CollateOptions::locked]) only collate the resources in the
+    /// cargo-resources.lock
+    pub fn frozen(mut self, frozen: bool) -> Self {
+        self.cargo_flags.frozen = frozen;
+        self
+    }
+
+    /// Resolve dependencies only with an up to date Cargo.lock, failing rather than updating it, as
+    /// cargo's --locked.
+    ///
+    /// The crate's cargo-resources.lock ([crate::LOCK_FILE_NAME]) isn't updated either: collation
+    /// fails, before copying anything, when the resources don't match it.
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.cargo_flags.locked = locked;
+        self
+    }
+
+    /// Write the record of resolved resources to the given path (relative to the crate root),
+    /// instead of the resource root
+    pub fn record_path(mut self, record_path: Utf8PathBuf) -> Self {
+        self.record_path = Some(record_path);
+        self
+    }
+
+    /// Write the record of resolved resources in the given format
+    pub fn record_format(mut self, record_format: RecordFormat) -> Self {
+        self.record_format = record_format;
+        self
+    }
+
+    /// Whether to write the record of resolved resources; collation still returns its summary
+    /// when it doesn't
+    pub fn write_record(mut self, write_record: bool) -> Self {
+        self.write_record = write_record;
+        self
+    }
+
+    /// Generate a Rust module at the given path (relative to the crate root, or absolute, e.g. in
+    /// OUT_DIR), mapping each resource name to its output path relative to the resource root
+    ///
+    /// The module defines a `RESOURCES` table of (name, output path) pairs in name order, and a
+    /// `resource_path(name)` function, so a build script can include it:
+    /// ```no_run
+    /// use cargo_metadata::camino::Utf8PathBuf;
+    /// use cargo_resources::{collate_with_options, CollateOptions};
+    ///
+    /// // In build.rs, for the crate to include!(concat!(env!("OUT_DIR"), "/resources.rs"))
+    /// let out_dir = Utf8PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    /// let options = CollateOptions::default().generate_rust_module(out_dir.join("resources.rs"));
+    /// collate_with_options(&Utf8PathBuf::from("Cargo.toml"), options).unwrap();
+    /// ```
+    pub fn generate_rust_module(mut self, module_path: Utf8PathBuf) -> Self {
+        self.generate_rust_module = Some(module_path);
+        self
+    }
+
+    /// Write an integrity manifest at the given path (relative to the crate root, or absolute),
+    /// mapping each output path (relative to the resource root) to the resource's Subresource
+    /// Integrity string, ready for an HTML integrity attribute.
+    pub fn integrity_manifest(mut self, manifest_path: Utf8PathBuf) -> Self {
+        self.integrity_manifest = Some(manifest_path);
+        self
+    }
+
+    /// Write a names manifest at the given path (relative to the crate root, or absolute), mapping
+    /// each resource name to its output path (relative to the resource root), for finding
+    /// resources at run time.
+    ///
+    /// The manifest is JSON unless the path ends .toml or .yaml (with the toml and yaml features),
+    /// lists the resources in name order, and is only rewritten when they change:
+    /// ```json
+    /// {
+    ///   "app.js": "js/app.js",
+    ///   "framework.css": "css/framework.css"
+    /// }
+    /// ```
+    pub fn names_manifest(mut self, manifest_path: Utf8PathBuf) -> Self {
+        self.names_manifest = Some(manifest_path);
+        self
+    }
+
+    /// Use the given hash for the integrity manifest's SRI strings, instead of SHA-384
+    pub fn integrity_algorithm(mut self, integrity_algorithm: SriAlgorithm) -> Self {
+        self.integrity_algorithm = integrity_algorithm;
+        self
+    }
+
+    /// Write the resources to the given target, e.g. a zip archive (with the zip feature) rather
+    /// than the resource root.
+    ///
+    /// An archive holds each resource at its output path, and the record of resolved resources at
+    /// the top level. Its entries have a fixed modified time, so the same resources always give the
+    /// same archive (which is only rewritten when they change):
+    /// ```no_run
+    /// # #[cfg(feature = "zip")]
+    /// # {
+    /// use cargo_metadata::camino::Utf8PathBuf;
+    /// use cargo_resources::{collate_with_options, CollateOptions, OutputTarget};
+    ///
+    /// let options = CollateOptions::default().output_target(OutputTarget::ZipArchive("resources.zip".into()));
+    /// collate_with_options(&Utf8PathBuf::from("Cargo.toml"), options).unwrap();
+    /// # }
+    /// ```
+    pub fn output_target(mut self, output_target: OutputTarget) -> Self {
+        self.output_target = output_target;
+        self
+    }
+
+    /// Write the resources to the given sink, rather than the output target, e.g. to upload them
+    /// or to capture them in a test.
+    ///
+    /// The sink is given each resource's output path and content, and the record of resolved
+    /// resources (unless not written) as resolved_resources.json. Resources it already holds
+    /// aren't written again. The options for the resource root itself (copy_mode, prune,
+    /// post_verify, emit_checksums and the generated module and integrity manifest) don't apply.
+    /// ```no_run
+    /// use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+    /// use cargo_resources::{collate_with_options, CollateOptions, CollationError, HashAlgorithm, OutputSink};
+    ///
+    /// /// A sink printing the paths it is given
+    /// struct Printing;
+    ///
+    /// impl OutputSink for Printing {
+    ///     fn exists_with_sha(&self, _: &Utf8Path, _: &String, _: HashAlgorithm) -> Result<bool, CollationError> {
+    ///         Ok(false)
+    ///     }
+    ///
+    ///     fn write_resource(&self, output_path: &Utf8Path, content: &[u8], _: Option<u32>) -> Result<(), CollationError> {
+    ///         println!("{} is {} bytes", output_path, content.len());
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let options = CollateOptions::default().sink(Box::new(Printing));
+    /// collate_with_options(&Utf8PathBuf::from("Cargo.toml"), options).unwrap();
+    /// ```
+    pub fn sink(mut self, sink: Box<dyn OutputSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+}
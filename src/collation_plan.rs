@@ -0,0 +1,41 @@
+use cargo_metadata::camino::Utf8PathBuf;
+use crate::{ResourceName, ResourceSha, ResourceSpecification};
+use crate::specifications::ResourceConsumerSpecification;
+
+/// A fully resolved collation, which can be executed without access to cargo metadata.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CollationPlan {
+    /// The root directory of the consuming crate
+    pub crate_root: Utf8PathBuf,
+
+    /// The consuming crate's resolved specification (resource root and collation options)
+    pub consumer: ResourceConsumerSpecification,
+
+    /// The resources to collate, in order
+    pub resources: Vec<PlannedResource>,
+
+    /// The optional resources required but not declared by any crate, which are skipped
+    #[serde(default)]
+    pub missing_optional: Vec<ResourceName>,
+}
+
+/// A resource within a collation plan.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PlannedResource {
+    /// The resolved resource specification (with the absolute source path)
+    pub specification: ResourceSpecification,
+
+    /// The hex-encoded hash of the resource as it is collated (i.e. after normalizing any line
+    /// endings) when the plan was made, in the consumer's algorithm
+    pub expected_sha: ResourceSha,
+
+    /// The hex-encoded hash of the source file when the plan was made, in the consumer's algorithm
+    /// (the expected sha, unless its line endings are normalized)
+    #[serde(default)]
+    pub source_sha: ResourceSha,
+
+    /// When the source file was last modified, in nanoseconds since the unix epoch, as read before
+    /// hashing it (or taking its recorded sha) for the plan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_modified: Option<u64>,
+}
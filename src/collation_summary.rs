@@ -0,0 +1,26 @@
+use cargo_metadata::camino::Utf8PathBuf;
+
+use crate::{ResolvedResource, ResourceName};
+
+/// What a collation did, as returned when collating or executing a plan.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CollationSummary {
+    /// The resource root the resources were collated into
+    pub resource_root: Utf8PathBuf,
+
+    /// The resources copied into the resource root (as recorded in the record file)
+    pub copied: Vec<ResolvedResource>,
+
+    /// The resources skipped as they were already up to date (as recorded in the record file)
+    pub skipped: Vec<ResolvedResource>,
+
+    /// The optional resources skipped as no crate declares them
+    pub missing_optional: Vec<ResourceName>,
+
+    /// The total size of the copied resources, in bytes
+    pub bytes_copied: u64,
+
+    /// The total size of all the collated resources (copied or already up to date), in bytes
+    #[serde(default)]
+    pub total_bytes: u64,
+}
@@ -0,0 +1,12 @@
+/// How resources are placed in the resource root
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyMode {
+    /// Copy each resource's source file
+    #[default]
+    Copy,
+    /// Symbolically link to each resource's source file
+    Symlink,
+    /// Hard link to each resource's source file (which must be on the same file system)
+    Hardlink,
+}
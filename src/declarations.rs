@@ -1,11 +1,20 @@
+use std::collections::BTreeMap;
+
 use cargo_metadata::camino::Utf8PathBuf;
+use cargo_metadata::semver::VersionReq;
+use crate::hash_algorithm::HashAlgorithm;
+use crate::line_endings::LineEndings;
+use crate::path_style::PathStyle;
 use crate::resource_encoding::ResourceEncoding;
-use crate::ResourceName;
+use crate::{RequiredSha, ResourceName};
 
 /// The structure matching the resource declaration (provides) in the package metadata.
-#[derive(serde::Deserialize, Debug)]
+///
+/// A declaration whose output path is absolute or contains `..`, or whose resource name contains a
+/// path separator, fails collation with an error naming the declaring crate.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct ResourceDataDeclaration {
-    /// Whether resource's file encoding is text or binary
+    /// Whether resource's file encoding is text or binary, detected from the file when not set
     pub encoding: Option<ResourceEncoding>,
 
     /// The path of the resource within the crate
@@ -15,25 +24,110 @@ pub struct ResourceDataDeclaration {
     pub output_path: Option<Utf8PathBuf>,
 
     /// The unique name for the resource
-    pub resource_name: Option<ResourceName>
+    pub resource_name: Option<ResourceName>,
+
+    /// Whether the resource is only collated by consumers in the same workspace
+    pub workspace_only: Option<bool>,
+
+    /// Whether the collated resource is made executable (on unix), e.g. for a script
+    pub executable: Option<bool>,
+
+    /// The named group of the resource (e.g. "static" or "data"), which consumers may collate into
+    /// a root of its own
+    pub group: Option<String>,
 }
 
 /// The structure matching the resource usage declaration in the consuming package metadata.
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
 pub struct ResourceConsumerDeclaration {
     /// The relative path of the resource root from the crate root
     pub resource_root: Option<Utf8PathBuf>,
 
+    /// The root (relative to the crate root) of each named group of resources, instead of the
+    /// resource root
+    pub roots: Option<BTreeMap<String, Utf8PathBuf>>,
+
     /// The list of required resources
-    pub requires: Option<Vec<ResourceRequirementDeclaration>>
+    pub requires: Option<Vec<ResourceRequirementDeclaration>>,
+
+    /// Whether copied resources keep the modified time of their source
+    pub preserve_mtime: Option<bool>,
+
+    /// Whether copied resources keep the permissions (mode) of their source, on unix
+    pub preserve_mode: Option<bool>,
+
+    /// The names of resources whose resolution should be traced
+    pub trace: Option<Vec<ResourceName>>,
+
+    /// The maximum dependency depth scanned for resources (1 being direct dependencies only)
+    pub max_depth: Option<usize>,
+
+    /// How output paths are expressed in the record of resolved resources
+    pub path_style: Option<PathStyle>,
+
+    /// Whether resources whose source can't be read (permission denied) are skipped with a warning
+    pub skip_unreadable: Option<bool>,
+
+    /// The optional unix mode (e.g. 0o755) for directories created in the resource root
+    pub directory_mode: Option<u32>,
+
+    /// The optional unix mode (e.g. 0o644) for files copied into the resource root
+    pub file_mode: Option<u32>,
+
+    /// Whether collation fails, before copying, when any resource name is declared more than once
+    pub unique_names: Option<bool>,
+
+    /// Whether collation fails, before copying, when any declared resource isn't in the requires
+    /// list (rather than each being reported)
+    pub deny_unused: Option<bool>,
+
+    /// The algorithm used to hash resources (and to check any required sha), SHA 256 when not set
+    pub hash_algorithm: Option<HashAlgorithm>,
+
+    /// The line endings (lf or crlf) text resources are normalized to when copied, unchanged when
+    /// not set
+    pub line_endings: Option<LineEndings>,
+
+    /// Whether each resource's file name includes the start of its sha, for cache busting
+    pub hashed_filenames: Option<bool>,
+
+    /// The optional limit, in bytes, on the combined size of the collated resources
+    pub max_total_bytes: Option<u64>,
+
+    /// The optional limit, in bytes, on the size of any one collated resource
+    pub max_file_bytes: Option<u64>,
 }
 
 /// The structure matching the resource requirement in the consuming package.
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct ResourceRequirementDeclaration {
     /// The unique name of the required resource
     pub resource_name: String,
 
-    /// The optional hex-encoded SHA256 value of the required resource
-    pub required_sha: Option<String>
+    /// The optional hex-encoded hash of the required resource (compared against the source file),
+    /// optionally prefixed by the algorithm to hash it with, e.g. sha512:..., or a list of hashes
+    /// any of which is accepted
+    pub required_sha: Option<RequiredSha>,
+
+    /// The optional encoding to use for this consumer, overriding the providing crate's declaration
+    pub encoding: Option<ResourceEncoding>,
+
+    /// The optional name of the crate the resource must be declared by, when more than one declares it
+    pub from_crate: Option<String>,
+
+    /// The optional versions of the declaring crate the resource must come from, e.g. ">=2.0"
+    pub version_req: Option<VersionReq>,
+
+    /// Whether the resource is skipped, rather than failing collation, when no crate declares it
+    pub optional: Option<bool>,
+
+    /// The optional output path to use instead of the declaring crate's (relative, without ..)
+    pub output_path_override: Option<Utf8PathBuf>,
+
+    /// The optional file name (without a path separator) to write the resource as, keeping its
+    /// output directory
+    pub rename_to: Option<String>,
+
+    /// The optional group to collate the resource with, instead of the declaring crate's
+    pub group: Option<String>,
 }
@@ -0,0 +1,10 @@
+/// Which dependencies of the consuming crate are searched for resources
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyScope {
+    /// Every dependency, however indirect (limited by any max_depth the consuming crate declares)
+    #[default]
+    All,
+    /// Only the consuming crate's direct dependencies (and the crate itself)
+    DirectOnly,
+}
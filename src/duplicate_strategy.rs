@@ -0,0 +1,18 @@
+/// Which declaration is used when a resource name is declared more than once
+///
+/// Packages are scanned in order of name, then version, so the chosen declaration doesn't
+/// depend on the order cargo lists them in.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateStrategy {
+    /// The last declaration scanned replaces the earlier ones (with a warning)
+    #[default]
+    LastWins,
+    /// The first declaration scanned is kept (with a warning)
+    FirstWins,
+    /// Fail before copying, listing every name declared more than once
+    Error,
+    /// The declaration by the highest crate version is kept (with a warning), the last scanned
+    /// winning a tie
+    HighestVersion,
+}
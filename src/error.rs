@@ -0,0 +1,482 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+
+use cargo_metadata::camino::Utf8PathBuf;
+use cargo_metadata::semver;
+
+use crate::{RequiredSha, ResourceDrift, ResourceName, ResourceNameConflict, ResourceSha, ResourceSpecification, LOCK_FILE_NAME};
+
+/// The ways collating resources can fail.
+///
+/// The Display form of each error is the message describing the failure.
+#[derive(Debug)]
+pub enum CollationError {
+    /// The cargo manifest of the crate doesn't exist
+    ManifestNotFound(Utf8PathBuf),
+
+    /// cargo metadata failed to read the crate's dependency graph (e.g. for a malformed manifest,
+    /// unresolvable dependencies, or an out of date Cargo.lock when it mustn't be updated).
+    Metadata(cargo_metadata::Error),
+
+    /// A crate's resource declaration couldn't be read
+    MalformedDeclaration {
+        crate_name: String,
+        source: serde_json::Error,
+    },
+
+    /// More than one resource declaration couldn't be read or is invalid, each given by its error
+    /// (e.g. a MalformedDeclaration or AbsoluteResourcePath)
+    MultipleDeclarationErrors(Vec<CollationError>),
+
+    /// A crate's [package.metadata] has a cargo_resources key of the wrong type, e.g. provides
+    /// that isn't a list
+    MalformedMetadata {
+        crate_name: String,
+        key: String,
+    },
+
+    /// A crate declares a resource with an absolute crate_path (which must be relative to the
+    /// crate, though environment variables may expand to an absolute path)
+    AbsoluteResourcePath {
+        crate_name: String,
+        crate_path: Utf8PathBuf,
+    },
+
+    /// A crate declares a resource with an absolute output_path (which must be relative to the
+    /// resource root)
+    AbsoluteOutputPath {
+        crate_name: String,
+        output_path: Utf8PathBuf,
+    },
+
+    /// A crate declares a resource name containing a path separator
+    PathSeparatorInName {
+        crate_name: String,
+        resource_name: ResourceName,
+    },
+
+    /// A crate declares a resource directory without any files in it
+    EmptyResourceDirectory {
+        crate_name: String,
+        crate_path: Utf8PathBuf,
+    },
+
+    /// A crate declares a resource glob that isn't a valid pattern
+    InvalidGlob {
+        crate_name: String,
+        crate_path: Utf8PathBuf,
+        source: glob::PatternError,
+    },
+
+    /// A crate declares a resource glob matching no files
+    UnmatchedGlob {
+        crate_name: String,
+        crate_path: Utf8PathBuf,
+    },
+
+    /// A crate declares a path with a ${ that isn't closed
+    UnclosedVariable {
+        crate_name: String,
+        path: Utf8PathBuf,
+    },
+
+    /// A crate declares a path using an environment variable that isn't set
+    UnsetVariable {
+        crate_name: String,
+        path: Utf8PathBuf,
+        variable: String,
+    },
+
+    /// A version (e.g. a min_tool_version or in a crate exclusion) couldn't be parsed, with a
+    /// description of where it was given
+    InvalidVersion {
+        context: String,
+        source: semver::Error,
+    },
+
+    /// The manifest isn't that of a crate, e.g. being of a virtual workspace
+    NoRootPackage(Utf8PathBuf),
+
+    /// cargo metadata gave no dependency graph
+    MissingDependencyGraph,
+
+    /// A required resource isn't declared by any crate
+    MissingResource(ResourceName),
+
+    /// A resource asked for by name isn't one the crate requires
+    NotRequired(ResourceName),
+
+    /// No crate available to the consuming crate (with the given manifest) declares the resource
+    NotDeclared {
+        manifest: Utf8PathBuf,
+        resource: ResourceName,
+    },
+
+    /// A requirement overrides a resource's output path with one that is absolute or contains ..
+    InvalidOutputPathOverride {
+        resource: ResourceName,
+        output_path: Utf8PathBuf,
+    },
+
+    /// A requirement renames a resource to something other than a file name
+    InvalidRename {
+        resource: ResourceName,
+        rename_to: String,
+    },
+
+    /// A requirement's required_sha can't be checked, e.g. naming an unknown hash algorithm, with
+    /// the reason
+    InvalidRequiredSha {
+        resource: ResourceName,
+        required_sha: String,
+        reason: String,
+    },
+
+    /// The resource root has a {triple} placeholder, but no target triple is given
+    MissingTargetTriple(Utf8PathBuf),
+
+    /// Something was asked for that needs cargo-resources built with a feature that isn't enabled
+    FeatureNotEnabled {
+        feature: String,
+        needed_for: String,
+    },
+
+    /// An argument of the cargo tool isn't valid, with what it should be
+    InvalidArgument {
+        argument: String,
+        value: String,
+        expected: String,
+    },
+
+    /// No declaration of a required resource matches the requirement's crate or version, each
+    /// candidate declaration being given
+    UnmatchedRequirement {
+        resource: ResourceName,
+        requirement: String,
+        candidates: Vec<ResourceSpecification>,
+    },
+
+    /// More than one declaration of a required resource matches the requirement's crate or
+    /// version, each being given
+    AmbiguousRequirement {
+        resource: ResourceName,
+        requirement: String,
+        candidates: Vec<ResourceSpecification>,
+    },
+
+    /// Resource names are declared more than once, when the consumer requires unique names
+    NameConflicts(Vec<ResourceNameConflict>),
+
+    /// Declared resources aren't in the consuming crate's requires list, when it denies unused
+    /// resources
+    UnusedResources(Vec<ResourceSpecification>),
+
+    /// A resource's source doesn't match the sha (or any of the shas) required by the consuming
+    /// crate
+    ShaMismatch {
+        resource: ResourceName,
+        expected: RequiredSha,
+        actual: ResourceSha,
+    },
+
+    /// A resource's source has changed since the collation was planned
+    SourceChanged {
+        resource: ResourceName,
+        expected: ResourceSha,
+        actual: ResourceSha,
+    },
+
+    /// A copied resource doesn't have the sha of its source, when verifying copies
+    CopyCorrupted {
+        resource: ResourceName,
+        output_path: Utf8PathBuf,
+        expected: ResourceSha,
+        actual: ResourceSha,
+    },
+
+    /// The resource root doesn't match what collation would put in it, when verifying
+    ResourcesOutOfDate(Vec<ResourceDrift>),
+
+    /// The resources don't match the crate's cargo-resources.lock, when collating with the lock
+    /// mustn't be updated (e.g. with --locked)
+    LockOutOfDate(Vec<ResourceDrift>),
+
+    /// More than one resource has the same output path (within the same root, when collating into
+    /// a directory), so one would replace the other
+    DuplicateOutputPath {
+        output_path: Utf8PathBuf,
+        resources: Vec<ResourceName>,
+    },
+
+    /// A collated resource is larger than the consumer's max_file_bytes
+    ResourceTooLarge {
+        resource: ResourceName,
+        size: u64,
+        max_file_bytes: u64,
+    },
+
+    /// The collated resources are larger, combined, than the consumer's max_total_bytes, the
+    /// largest contributors being given (largest first) with their sizes.
+    SizeBudgetExceeded {
+        total_bytes: u64,
+        max_total_bytes: u64,
+        largest: Vec<(ResourceName, u64)>,
+    },
+
+    /// A resource's output path is outside the resource root. A path rejected from its declaration
+    /// (having .. components) is given relative to the root, as the declaring crate has no root;
+    /// one rejected when written is given with the root, resolved.
+    ResourceOutsideRoot {
+        path: Utf8PathBuf,
+        root: Option<Utf8PathBuf>,
+        crate_name: Option<String>,
+    },
+
+    /// A path (e.g. of a recorded resource) isn't within the root it must be relative to
+    NotWithinRoot {
+        path: Utf8PathBuf,
+        root: Utf8PathBuf,
+    },
+
+    /// A path isn't valid UTF8, so can't be handled
+    NonUtf8Path(PathBuf),
+
+    /// A file system operation failed, with a description of what was being done
+    Io {
+        context: String,
+        source: io::Error,
+    },
+
+    /// A file (e.g. a record or lock file) or metadata couldn't be parsed, with a description of
+    /// what was being read
+    Parse {
+        context: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// Something (e.g. a record or archive) couldn't be serialized, with a description of what was
+    /// being written
+    Serialize {
+        context: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+impl CollationError {
+    /// Wrap an IO error with a description of what was being done
+    pub(crate) fn io(context: impl Into<String>, source: io::Error) -> CollationError {
+        CollationError::Io { context: context.into(), source }
+    }
+
+    /// Wrap a parsing error with a description of what was being read
+    pub(crate) fn parse(
+        context: impl Into<String>,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> CollationError {
+        CollationError::Parse { context: context.into(), source: source.into() }
+    }
+
+    /// Wrap a serialization error with a description of what was being written
+    pub(crate) fn serialize(
+        context: impl Into<String>,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> CollationError {
+        CollationError::Serialize { context: context.into(), source: source.into() }
+    }
+}
+
+impl Display for CollationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CollationError::ManifestNotFound(manifest) =>
+                write!(f, "Source manifest does not exist: {}", manifest),
+            CollationError::Metadata(source) =>
+                write!(f, "Unable to read the cargo metadata: {}", source),
+            CollationError::MalformedDeclaration { crate_name, source } =>
+                write!(f, "Malformed resource declaration in {}: {}", crate_name, source),
+            CollationError::MultipleDeclarationErrors(errors) => {
+                let descriptions: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "{} malformed resource declarations: {}", errors.len(), descriptions.join("; "))
+            }
+            CollationError::MalformedMetadata { crate_name, key } =>
+                write!(f, "Crate {} has a malformed [package.metadata.{}]", crate_name, key),
+            CollationError::AbsoluteResourcePath { crate_name, crate_path } =>
+                write!(f, "Crate {} declares an absolute resource path {}", crate_name, crate_path),
+            CollationError::AbsoluteOutputPath { crate_name, output_path } =>
+                write!(f, "Crate {} declares an absolute output path {}", crate_name, output_path),
+            CollationError::PathSeparatorInName { crate_name, resource_name } =>
+                write!(f, "Crate {} declares a resource name {} containing a path separator", crate_name, resource_name),
+            CollationError::EmptyResourceDirectory { crate_name, crate_path } =>
+                write!(f, "Crate {} declares resource directory {} which contains no files", crate_name, crate_path),
+            CollationError::InvalidGlob { crate_name, crate_path, source } =>
+                write!(f, "Crate {} declares an invalid resource glob {}: {}", crate_name, crate_path, source),
+            CollationError::UnmatchedGlob { crate_name, crate_path } =>
+                write!(f, "Crate {} declares resource glob {} which matches no files", crate_name, crate_path),
+            CollationError::UnclosedVariable { crate_name, path } =>
+                write!(f, "Crate {} declares path {} with an unclosed ${{", crate_name, path),
+            CollationError::UnsetVariable { crate_name, path, variable } =>
+                write!(
+                    f,
+                    "Crate {} declares path {} using environment variable {}, which is not set",
+                    crate_name,
+                    path,
+                    variable
+                ),
+            CollationError::InvalidVersion { context, source } =>
+                write!(f, "{}: {}", context, source),
+            CollationError::NoRootPackage(manifest) =>
+                write!(
+                    f,
+                    "No crate found for {}, which may be a virtual workspace (collate its members with collate_workspace)",
+                    manifest
+                ),
+            CollationError::MissingDependencyGraph =>
+                write!(f, "No dependency graph found in the cargo metadata"),
+            CollationError::MissingResource(resource) =>
+                write!(f, "No resource found matching requirement {}", resource),
+            CollationError::NotRequired(resource) =>
+                write!(f, "Resource {} is not required by the crate", resource),
+            CollationError::NotDeclared { manifest, resource } =>
+                write!(f, "No crate available to {} declares resource {}", manifest, resource),
+            CollationError::InvalidOutputPathOverride { resource, output_path } =>
+                write!(
+                    f,
+                    "The requirement for {} overrides its output path with {}, which must be relative without ..",
+                    resource,
+                    output_path
+                ),
+            CollationError::InvalidRename { resource, rename_to } =>
+                write!(
+                    f,
+                    "The requirement for {} renames it to {}, which must be a file name without a path separator",
+                    resource,
+                    rename_to
+                ),
+            CollationError::InvalidRequiredSha { resource, required_sha, reason } =>
+                write!(f, "The required sha {} of resource {} {}", required_sha, resource, reason),
+            CollationError::MissingTargetTriple(resource_root) =>
+                write!(f, "Resource root {} needs a target triple, but none is given and TARGET isn't set", resource_root),
+            CollationError::FeatureNotEnabled { feature, needed_for } =>
+                write!(f, "{} needs cargo-resources installed with the {} feature", needed_for, feature),
+            CollationError::InvalidArgument { argument, value, expected } =>
+                write!(f, "'{}' parameter [{}] should be {}.", argument, value, expected),
+            CollationError::UnmatchedRequirement { resource, requirement, candidates } =>
+                write!(
+                    f,
+                    "No declaration of resource {} matches {} (declared by {})",
+                    resource,
+                    requirement,
+                    describe_declarations(candidates)
+                ),
+            CollationError::AmbiguousRequirement { resource, requirement, candidates } =>
+                write!(
+                    f,
+                    "Resource {} matching {} is ambiguous, being declared by {}",
+                    resource,
+                    requirement,
+                    describe_declarations(candidates)
+                ),
+            CollationError::NameConflicts(conflicts) => {
+                let descriptions: Vec<String> = conflicts.iter().map(|conflict| format!(
+                    "{} (declared by {})",
+                    conflict.resource_name,
+                    describe_declarations(&conflict.declarations)
+                )).collect();
+                write!(f, "Resource names are declared more than once: {}", descriptions.join("; "))
+            }
+            CollationError::UnusedResources(resources) => {
+                let descriptions: Vec<String> = resources.iter().map(|res_dec| format!(
+                    "{} (declared by {} {})",
+                    res_dec.resource_name,
+                    res_dec.declaring_crate_name,
+                    res_dec.declaring_crate_version
+                )).collect();
+                write!(f, "Resources are declared but not required: {}", descriptions.join("; "))
+            }
+            CollationError::ShaMismatch { resource, expected, actual } =>
+                write!(f, "Resource {} with sha {} does not match required sha {}.", resource, actual, expected),
+            CollationError::SourceChanged { resource, expected, actual } =>
+                write!(f, "Resource {} with sha {} does not match planned sha {}.", resource, actual, expected),
+            CollationError::CopyCorrupted { resource, output_path, expected, actual } =>
+                write!(
+                    f,
+                    "Resource {} copied to {} has sha {} rather than the source's sha {}.",
+                    resource,
+                    output_path,
+                    actual,
+                    expected
+                ),
+            CollationError::ResourcesOutOfDate(drift) => {
+                let descriptions: Vec<String> = drift.iter().map(|resource| resource.to_string()).collect();
+                write!(f, "Resources are not up to date: {}", descriptions.join("; "))
+            }
+            CollationError::LockOutOfDate(drift) => {
+                let descriptions: Vec<String> = drift.iter().map(|resource| resource.to_string()).collect();
+                write!(
+                    f,
+                    "Resources do not match {} (update it with cargo resources update-lock): {}",
+                    LOCK_FILE_NAME,
+                    descriptions.join("; ")
+                )
+            }
+            CollationError::DuplicateOutputPath { output_path, resources } =>
+                write!(f, "Resources {} have the same output path {}", resources.join(", "), output_path),
+            CollationError::ResourceTooLarge { resource, size, max_file_bytes } =>
+                write!(f, "Resource {} is {} bytes, more than the max_file_bytes of {}", resource, size, max_file_bytes),
+            CollationError::SizeBudgetExceeded { total_bytes, max_total_bytes, largest } => {
+                let descriptions: Vec<String> = largest.iter()
+                    .map(|(resource, size)| format!("{} ({} bytes)", resource, size))
+                    .collect();
+                write!(
+                    f,
+                    "Resources total {} bytes, more than the max_total_bytes of {}; the largest are {}",
+                    total_bytes,
+                    max_total_bytes,
+                    descriptions.join(", ")
+                )
+            }
+            CollationError::ResourceOutsideRoot { path, root, crate_name } => match (crate_name, root) {
+                (Some(crate_name), _) =>
+                    write!(f, "Crate {} declares an output path {} outside the resource root", crate_name, path),
+                (None, Some(root)) =>
+                    write!(f, "Can't copy to {:?} as not in resource root {:?}", path, root),
+                (None, None) =>
+                    write!(f, "Can't copy to {:?} as not in the resource root", path),
+            },
+            CollationError::NotWithinRoot { path, root } =>
+                write!(f, "{} is not within {}", path, root),
+            CollationError::NonUtf8Path(path) =>
+                write!(f, "Path {:?} is not UTF8", path),
+            CollationError::Io { context, source } =>
+                write!(f, "{}: {}", context, source),
+            CollationError::Parse { context, source } =>
+                write!(f, "{}: {}", context, source),
+            CollationError::Serialize { context, source } =>
+                write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+/// Describe declarations by their declaring crates and versions, e.g. "a 1.0.0, b 2.1.0"
+fn describe_declarations(declarations: &[ResourceSpecification]) -> String {
+    declarations.iter()
+        .map(|dec| format!("{} {}", dec.declaring_crate_name, dec.declaring_crate_version))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl std::error::Error for CollationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CollationError::MalformedDeclaration { source, .. } => Some(source),
+            CollationError::Metadata(source) => Some(source),
+            CollationError::InvalidGlob { source, .. } => Some(source),
+            CollationError::InvalidVersion { source, .. } => Some(source),
+            CollationError::Io { source, .. } => Some(source),
+            CollationError::Parse { source, .. } => Some(source.as_ref()),
+            CollationError::Serialize { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
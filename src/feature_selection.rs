@@ -0,0 +1,29 @@
+use cargo_metadata::CargoOpt;
+
+/// Which cargo features are enabled when resolving the dependencies of the consuming crate
+///
+/// Only the dependencies enabled by the selected features are searched for resources.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum FeatureSelection {
+    /// All the features of every crate in the workspace
+    #[default]
+    AllFeatures,
+    /// Just the default features
+    DefaultFeatures,
+    /// No features, not even the default ones
+    NoDefaultFeatures,
+    /// The given features, as well as the default ones
+    Features(Vec<String>),
+}
+
+impl FeatureSelection {
+    /// The cargo options for the selection (none for just the default features)
+    pub(crate) fn cargo_opt(&self) -> Option<CargoOpt> {
+        match self {
+            FeatureSelection::AllFeatures => Some(CargoOpt::AllFeatures),
+            FeatureSelection::DefaultFeatures => None,
+            FeatureSelection::NoDefaultFeatures => Some(CargoOpt::NoDefaultFeatures),
+            FeatureSelection::Features(features) => Some(CargoOpt::SomeFeatures(features.to_owned())),
+        }
+    }
+}
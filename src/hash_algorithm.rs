@@ -0,0 +1,74 @@
+/// The algorithm used to hash resources (for required shas and to skip unchanged copies)
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    /// SHA-256
+    #[default]
+    Sha256,
+    /// SHA-512
+    Sha512,
+    /// BLAKE3
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// The algorithm's name, as given in hash_algorithm and in the prefix of a required sha (e.g.
+    /// sha512:...)
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// The number of digits in a hex-encoded hash
+    pub(crate) fn hex_digits(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 | HashAlgorithm::Blake3 => 64,
+            HashAlgorithm::Sha512 => 128,
+        }
+    }
+
+    /// The extension of a checksum file holding a hash, as written by sha256sum, sha512sum or b3sum
+    pub fn checksum_extension(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake3 => "b3",
+        }
+    }
+}
+
+/// A hash of a file's content being worked out, with the algorithm's implementation
+pub(crate) enum FileHasher {
+    Ring(Box<ring::digest::Context>),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl FileHasher {
+    /// Start hashing with the given algorithm
+    pub(crate) fn new(algorithm: HashAlgorithm) -> FileHasher {
+        match algorithm {
+            HashAlgorithm::Sha256 => FileHasher::Ring(Box::new(ring::digest::Context::new(&ring::digest::SHA256))),
+            HashAlgorithm::Sha512 => FileHasher::Ring(Box::new(ring::digest::Context::new(&ring::digest::SHA512))),
+            HashAlgorithm::Blake3 => FileHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    /// Add the next block of content
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            FileHasher::Ring(context) => context.update(data),
+            FileHasher::Blake3(hasher) => { hasher.update(data); }
+        }
+    }
+
+    /// Finish hashing, giving the hex-encoded hash
+    pub(crate) fn finish(self) -> String {
+        match self {
+            FileHasher::Ring(context) => hex::encode(context.finish().as_ref()),
+            FileHasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use cargo_metadata::camino::Utf8Path;
+
+use crate::hash_algorithm::FileHasher;
+use crate::{CollationError, HashAlgorithm, ResourceSha, SriAlgorithm, DEFAULT_HASH_BUFFER_SIZE};
+
+/// Work out the hex-encoded hash of a file with the given algorithm, exactly as collation does, so
+/// the hash matches the sha of the resource in the record of resolved resources (and the shas
+/// given to an [crate::OutputSink]), e.g. for verifying resources deployed elsewhere:
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::{file_sha, HashAlgorithm};
+///
+/// let sha = file_sha(&Utf8PathBuf::from("static/framework.css"), HashAlgorithm::Sha256).unwrap();
+/// println!("framework.css has sha {}", sha);
+/// ```
+///
+/// The hash is lower case hex for every algorithm, and the file is read in blocks of
+/// [DEFAULT_HASH_BUFFER_SIZE] bytes.
+pub fn file_sha(path: &Utf8Path, algorithm: HashAlgorithm) -> Result<ResourceSha, CollationError> {
+    get_file_sha(path, algorithm, DEFAULT_HASH_BUFFER_SIZE)
+}
+
+/// Work out the hex-encoded hash of a file from the path, with the given algorithm
+pub(crate) fn get_file_sha(
+    path: &Utf8Path,
+    algorithm: HashAlgorithm,
+    buffer_size: usize,
+) -> Result<ResourceSha, CollationError> {
+    let mut sha = FileHasher::new(algorithm);
+    read_file_blocks(path, buffer_size, &format!("{:?} hash", algorithm), |block| sha.update(block))?;
+    Ok(sha.finish())
+}
+
+/// Get the Subresource Integrity string of a file, the algorithm's prefix and base64 encoded digest,
+/// e.g. "sha384-oqVuAfXRKap7fdgcCY5uykM6+R9GqQ8K/uxy9rx7HNQlGYl1kPzQho1wx4JwY8wC"
+pub(crate) fn get_file_integrity(
+    path: &Utf8Path,
+    algorithm: SriAlgorithm,
+    buffer_size: usize,
+) -> Result<String, CollationError> {
+    let mut context = ring::digest::Context::new(algorithm.digest_algorithm());
+    read_file_blocks(path, buffer_size, &format!("{} integrity", algorithm.prefix()), |block| context.update(block))?;
+    Ok(format!("{}-{}", algorithm.prefix(), BASE64_STANDARD.encode(context.finish())))
+}
+
+/// Read a file in blocks of the given size, passing each to the function in turn (e.g. to hash
+/// it), with what is being calculated for any error
+fn read_file_blocks(
+    path: &Utf8Path,
+    buffer_size: usize,
+    calculating: &str,
+    f: impl FnMut(&[u8]),
+) -> Result<(), CollationError> {
+    let file = File::open(path).map_err(|e| match e.kind() {
+        ErrorKind::PermissionDenied => CollationError::io(
+            format!("Permission denied opening {}, check the file is readable by the current user", path),
+            e,
+        ),
+        _ => CollationError::io(format!("Error opening {}", path), e)
+    })?;
+    read_blocks(file, buffer_size, f)
+        .map_err(|e| CollationError::io(format!("Error calculating {} of {}", calculating, path), e))
+}
+
+/// Read from the reader in blocks of the given size, passing each to the function in turn
+fn read_blocks(reader: impl Read, buffer_size: usize, mut f: impl FnMut(&[u8])) -> std::io::Result<()> {
+    // Read sensible sized blocks from disk (an empty buffer would never read anything)!
+    let mut reader = BufReader::with_capacity(buffer_size.max(1), reader);
+
+    loop {
+        let block = reader.fill_buf()?;
+        if block.is_empty() {
+            break;
+        }
+        let bytes_read = block.len();
+        f(block);
+        reader.consume(bytes_read);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::read_blocks;
+
+    /// A reader of the given number of bytes, counting the reads made of it
+    struct CountingReader {
+        remaining: usize,
+        reads: usize,
+    }
+
+    impl Read for &mut CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reads += 1;
+            let read = buf.len().min(self.remaining);
+            buf[..read].fill(b'x');
+            self.remaining -= read;
+            Ok(read)
+        }
+    }
+
+    /// Read a file of the given size in blocks of the given size, giving the number of reads made
+    /// and the bytes passed on
+    fn read_counts(file_size: usize, buffer_size: usize) -> (usize, usize) {
+        let mut reader = CountingReader { remaining: file_size, reads: 0 };
+        let mut bytes = 0;
+        read_blocks(&mut reader, buffer_size, |block| bytes += block.len()).unwrap();
+        (reader.reads, bytes)
+    }
+
+    #[test]
+    fn larger_buffers_read_large_files_in_fewer_calls() {
+        let file_size = 16 * 1024 * 1024;
+
+        // One read per full block, plus the read finding the end of the file
+        assert_eq!(read_counts(file_size, 4096), (4097, file_size));
+        assert_eq!(read_counts(file_size, crate::DEFAULT_HASH_BUFFER_SIZE), (257, file_size));
+        assert_eq!(read_counts(file_size, 1024 * 1024), (17, file_size));
+    }
+
+    #[test]
+    fn an_empty_buffer_still_reads_the_file() {
+        assert_eq!(read_counts(10, 0), (11, 10));
+    }
+}
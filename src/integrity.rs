@@ -0,0 +1,15 @@
+use cargo_metadata::camino::Utf8Path;
+
+use crate::manifest::generate_json_manifest;
+use crate::CollationError;
+
+/// Generate an integrity manifest: a JSON object mapping each output path (relative to the resource
+/// root) to its SRI string, e.g. "sha384-oqVuAfXRKap7fdgcCY5uykM6+R9GqQ8K/uxy9rx7HNQlGYl1kPzQho1wx4JwY8wC".
+///
+/// The paths are written in order, so the same resources always generate the same manifest.
+pub(crate) fn generate_integrity_manifest(resources: &[(&Utf8Path, String)]) -> Result<String, CollationError> {
+    generate_json_manifest(
+        resources.iter().map(|(output_path, integrity)| (output_path.as_str(), integrity.as_str())),
+        "integrity manifest",
+    )
+}
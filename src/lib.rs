@@ -14,353 +14,3608 @@
 //! // Collate resources from the crate's dependencies.
 //! let _r = collate_resources(&manifest_file);
 //! ```
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, ErrorKind, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use cargo_metadata::{CargoOpt, Metadata, Package};
-use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
-use ring::digest::{Context, Digest, SHA256};
+use cargo_metadata::{Metadata, Node, Package, PackageId};
+use cargo_metadata::camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use cargo_metadata::semver::Version;
 use serde_json::Value;
 
-pub use declarations::ResourceDataDeclaration;
+pub use cargo_flags::CargoFlags;
+pub use collate_options::CollateOptions;
+pub use collation_plan::{CollationPlan, PlannedResource};
+pub use collation_summary::CollationSummary;
+pub use copy_mode::CopyMode;
+pub use declarations::{ResourceConsumerDeclaration, ResourceDataDeclaration, ResourceRequirementDeclaration};
+pub use dependency_scope::DependencyScope;
+pub use duplicate_strategy::DuplicateStrategy;
+pub use error::CollationError;
+pub use feature_selection::FeatureSelection;
+pub use hash_algorithm::HashAlgorithm;
+pub use hashing::file_sha;
+pub use line_endings::LineEndings;
+pub use name_conflict::ResourceNameConflict;
+pub use output_sink::{FsSink, OutputSink};
+pub use output_target::OutputTarget;
+pub use path_style::PathStyle;
+pub use planned_changes::{PlannedChange, PlannedChanges};
+pub use record_format::RecordFormat;
+pub use required_sha::RequiredSha;
+pub use records::{merge_records, read_record, RecordConflict};
+pub use resolved_resource::ResolvedResource;
+pub use resource_drift::ResourceDrift;
+pub use reporting::{BuildRsReporter, DefaultReporter, JsonReporter, QuietReporter, ReportingTrait, VerboseReporter};
 pub use resource_encoding::ResourceEncoding;
-pub use specifications::ResourceSpecification;
+pub use sri_algorithm::SriAlgorithm;
+pub use specifications::{ResourceConsumerSpecification, ResourceRequirement, ResourceSpecification};
 
-use crate::declarations::ResourceConsumerDeclaration;
-use crate::specifications::{ResourceConsumerSpecification, ResourceRequirement};
+use crate::hash_algorithm::FileHasher;
+use crate::hashing::{get_file_integrity, get_file_sha};
+use crate::integrity::generate_integrity_manifest;
+use crate::lockfile::ResourceLock;
+use crate::manifest::{generate_json_manifest, generate_manifest};
+use crate::records::serialize_record;
+use crate::rust_module::generate_rust_module;
+use crate::staged_files::StagedFiles;
 
 mod resource_encoding;
 
+mod cargo_flags;
+
 mod declarations;
 
+mod dependency_scope;
+
+mod duplicate_strategy;
+
+mod error;
+
+mod feature_selection;
+
+mod hash_algorithm;
+
+mod hashing;
+
+mod line_endings;
+
 mod specifications;
 
+mod path_style;
+
+mod collate_options;
+
+mod collation_plan;
+
+mod collation_summary;
+
+mod copy_mode;
+
+mod output_target;
+
+mod output_sink;
+
+#[cfg(any(feature = "zip", feature = "tar"))]
+mod archive;
+
+mod record_format;
+
+mod records;
+
+mod resolved_resource;
+
+mod resource_drift;
+
+mod rust_module;
+
+mod integrity;
+
+mod manifest;
+
+mod lockfile;
+
+mod sri_algorithm;
+
+mod required_sha;
+
+mod planned_changes;
+
+mod name_conflict;
+
+mod reporting;
+
+mod staged_files;
+
 /// The Resource Name
 pub type ResourceName = String;
 
-/// The Resource's SHA 256 Value
+/// The Resource's hex-encoded hash (SHA 256 by default)
 pub type ResourceSha = String;
 
-/// Collate the resources for the given crate, into the crate.
+/// The file name of the record of resolved resources, written in the resource root
+///
+/// The record lists the resources in name order, so collating the same resources again writes an
+/// identical record. It reads back with [read_record]:
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::{collate_resources, read_record, DEFAULT_RECORD_FILE_NAME};
+///
+/// let summary = collate_resources(&Utf8PathBuf::from("Cargo.toml")).unwrap();
+/// let record = read_record(&summary.resource_root.join(DEFAULT_RECORD_FILE_NAME)).unwrap();
+/// ```
+pub const DEFAULT_RECORD_FILE_NAME: &str = "resolved_resources.json";
+
+/// The file, beside the consuming crate's Cargo.toml, which may hold its resource options (e.g. a
+/// long requires list) instead of [package.metadata.cargo_resources]. Reading it needs the toml
+/// feature.
+///
+/// Options set in the file take precedence over those set inline, which still apply otherwise (so
+/// a requires list in the file replaces the inline one, rather than adding to it):
+/// ```
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::plan_collation;
+///
+/// let manifest_file = Utf8PathBuf::from("tests/fixtures/config_file/Cargo.toml");
+/// # #[cfg(feature = "toml")]
+/// # {
+/// let plan = plan_collation(&manifest_file).unwrap();
+///
+/// // The file's resource root and requires list, and the inline preserve_mtime
+/// assert!(plan.consumer.resource_root.ends_with("target/config_resources"));
+/// assert_eq!(plan.resources.len(), 1);
+/// assert_eq!(plan.resources[0].specification.resource_name, "b.txt");
+/// assert!(plan.consumer.preserve_mtime);
+/// # }
+/// # #[cfg(not(feature = "toml"))]
+/// # assert!(plan_collation(&manifest_file).unwrap_err().to_string().contains("toml feature"));
+/// ```
+pub const CONSUMER_CONFIG_FILE_NAME: &str = "cargo-resources.toml";
+
+/// The file, beside the consuming crate's Cargo.toml, locking the sha of each resource it
+/// collates, so the team shares a reproducible set of resources.
+///
+/// Collation writes it (when there are resources to lock) and keeps it up to date, unless
+/// collating with [CollateOptions::locked] (or frozen), when the resources must match it instead.
+/// [update_lock] rewrites it deliberately, and [verify_resources] checks it.
+pub const LOCK_FILE_NAME: &str = "cargo-resources.lock";
+
+/// The file, in the resource root, mapping each resource's output path to the hashed one, when
+/// file names are hashed
+pub const HASHED_FILENAMES_MANIFEST: &str = "hashed_filenames.json";
+
+/// The size, in bytes, of the blocks read from a file to hash it, unless collating with a
+/// different [CollateOptions::hash_buffer_size]
+pub const DEFAULT_HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The number of the largest resources named when the combined size is over max_total_bytes
+const LARGEST_CONTRIBUTORS: usize = 5;
+
+/// The resource root used when the consuming crate doesn't declare one
+const DEFAULT_RESOURCE_ROOT: &str = "target/resources";
+
+/// The placeholder in a resource root that is replaced by the target triple
+const TARGET_TRIPLE_PLACEHOLDER: &str = "{triple}";
+
+/// Collate the resources for the given crate, into the crate, reporting to the console.
 ///
 /// # Arguments
 /// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
 ///
 /// # Returns
-/// Nothing on success, or a string error describing the failure.
-pub fn collate_resources(source_manifest: &Utf8PathBuf) -> Result<(), String> {
-    if !source_manifest.exists() {
-        Err(format!("Source manifest does not exist: {}", source_manifest))?
+/// A summary of the collation, or an error describing the failure.
+///
+/// # Example
+/// ```
+/// use std::env::current_dir;
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::collate_resources;
+///
+/// let cwd = current_dir().unwrap();
+/// let manifest_file = Utf8PathBuf::from_path_buf(cwd).unwrap().join("Cargo.toml");
+///
+/// let summary = collate_resources(&manifest_file).unwrap();
+/// println!("Copied {} resources into {}", summary.copied.len(), summary.resource_root);
+/// ```
+pub fn collate_resources(source_manifest: &Utf8PathBuf) -> Result<CollationSummary, CollationError> {
+    collate_resources_with_reporter(source_manifest, &DefaultReporter)
+}
+
+/// Collate the resources for the given crate, into the crate, with the given options.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// A summary of the collation (or, for a dry run, of what collation would do), or an error
+/// describing the failure.
+///
+/// # Example
+/// Resources can be nested in the resource root, however its path is written (on Windows, where
+/// canonical paths have a `\\?\` prefix, as well):
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::{collate_with_options, CollateOptions};
+///
+/// let resource_root = Utf8PathBuf::from("target/../target/./resources");
+/// let options = CollateOptions::default().resource_root_override(resource_root);
+/// let summary = collate_with_options(&Utf8PathBuf::from("Cargo.toml"), options).unwrap();
+/// ```
+///
+/// An output path escaping the resource root is rejected, before anything is created.
+pub fn collate_with_options(
+    source_manifest: &Utf8PathBuf,
+    options: CollateOptions,
+) -> Result<CollationSummary, CollationError> {
+    collate_with(source_manifest, &options, options.reporter.as_ref())
+}
+
+/// Collate the resources for the given crate, or for each member of the given virtual workspace,
+/// with the given options.
+///
+/// Each workspace member is collated as a consuming crate in its own right, into its own resource
+/// root. The workspace's metadata is only loaded once, and a resource root override has a
+/// directory for each member, named for it, so the members don't overwrite each other.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate or workspace.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// A summary of the collation of the crate (or of each workspace member), or an
+/// error describing the failure.
+///
+/// # Example
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::{collate_workspace, CollateOptions};
+///
+/// let manifest_file = Utf8PathBuf::from("Cargo.toml");
+/// let summaries = collate_workspace(&manifest_file, CollateOptions::default()).unwrap();
+/// println!("Collated {} workspace members", summaries.len());
+/// ```
+pub fn collate_workspace(
+    source_manifest: &Utf8PathBuf,
+    options: CollateOptions,
+) -> Result<Vec<CollationSummary>, CollationError> {
+    let metadata = get_metadata(source_manifest, &options.features, options.cargo_flags)?;
+    if let Some(root_package) = metadata.root_package() {
+        let crate_root = source_manifest.parent().expect("No manifest directory!");
+        let resolve_options = ResolveOptions::from(&options);
+        let reporter = options.reporter.as_ref();
+        return Ok(vec!(collate_package(&metadata, root_package, crate_root, &options, &resolve_options, reporter)?));
     }
-    // Now lets get the metadata of a package
-    let mut metadata_cmd = cargo_metadata::MetadataCommand::new();
-    let metadata: Metadata = metadata_cmd
-        .manifest_path(&source_manifest)
-        .features(CargoOpt::AllFeatures)
-        .exec()
-        .unwrap();
+
+    // The members share the workspace's metadata, but each needs its own resource root, so a root
+    // given for them all has a directory for each member
+    let mut summaries = vec!();
+    for member in metadata.workspace_packages() {
+        let mut resolve_options = ResolveOptions::from(&options);
+        resolve_options.resource_root = options.resource_root_override.as_ref()
+            .map(|resource_root| resource_root.join(member.name.as_str()));
+        let crate_root = member.manifest_path.parent().expect("No manifest directory!");
+        let reporter = options.reporter.as_ref();
+        summaries.push(collate_package(&metadata, member, crate_root, &options, &resolve_options, reporter)?);
+    }
+    Ok(summaries)
+}
+
+/// Collate the resources for the given crate with the given options, reporting to the given
+/// reporter (rather than the options')
+fn collate_with(
+    source_manifest: &Utf8PathBuf,
+    options: &CollateOptions,
+    reporter: &dyn ReportingTrait,
+) -> Result<CollationSummary, CollationError> {
+    let metadata = get_metadata(source_manifest, &options.features, options.cargo_flags)?;
+    let root_package = get_root_package(&metadata, source_manifest)?;
+    let crate_root = source_manifest.parent().expect("No manifest directory!");
+    collate_package(&metadata, root_package, crate_root, options, &ResolveOptions::from(options), reporter)
+}
+
+/// Collate the resources for the given package (with its crate root) of the metadata, with the
+/// given options, resolving its requirement with the given resolve options and reporting to the
+/// given reporter
+fn collate_package(
+    metadata: &Metadata,
+    package: &Package,
+    crate_root: &Utf8Path,
+    options: &CollateOptions,
+    resolve_options: &ResolveOptions,
+    reporter: &dyn ReportingTrait,
+) -> Result<CollationSummary, CollationError> {
+    let plan = plan_package(metadata, package, crate_root, reporter, resolve_options)?;
+
+    // The lock is an input: when it mustn't be updated the planned resources must match it, before
+    // anything is copied (copying then checks the sources still have their planned shas)
+    let lock_file = plan.crate_root.join(LOCK_FILE_NAME);
+    let lock = ResourceLock::read(&lock_file)?;
+    let is_locked = options.cargo_flags.is_locked();
+    if is_locked {
+        let drift = ResourceLock::drift(lock.as_ref(), &ResourceLock::for_plan(&plan).resources);
+        if !drift.is_empty() {
+            Err(CollationError::LockOutOfDate(drift))?
+        }
+    }
+
+    let run_options = RunOptions {
+        dry_run: options.dry_run,
+        prune: options.prune,
+        copy_mode: options.copy_mode,
+        always_hash: options.always_hash,
+        post_verify: options.post_verify,
+        emit_checksums: options.emit_checksums,
+        hash_buffer_size: options.hash_buffer_size,
+        record_path: options.record_path.to_owned(),
+        record_format: options.record_format,
+        write_record: options.write_record,
+        rust_module: options.generate_rust_module.to_owned(),
+        integrity_manifest: options.integrity_manifest.to_owned(),
+        integrity_algorithm: options.integrity_algorithm,
+        names_manifest: options.names_manifest.to_owned(),
+        output_target: options.output_target.to_owned(),
+    };
+    let mut staged = StagedFiles::default();
+    let summary = match &options.sink {
+        Some(sink) => collate_to_sink(&plan, sink.as_ref(), reporter, &run_options),
+        None => run_plan(&plan, reporter, &run_options, &mut staged),
+    }?;
+
+    // Once collated the lock is created, or updated to match, unless there is nothing to lock,
+    // along with the other files collation writes
+    if !is_locked && !options.dry_run && (lock.is_some() || !plan.resources.is_empty()) {
+        staged.stage(&lock_file, ResourceLock::for_plan(&plan).serialize()?)?;
+    }
+    staged.commit()?;
+    Ok(summary)
+}
+
+/// Write the crate's cargo-resources.lock ([LOCK_FILE_NAME]), locking the sha of every resource
+/// collation would copy, without collating them, e.g. after deliberately updating a dependency.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * options: The collation options (e.g. the features), see [CollateOptions].
+///
+/// # Returns
+/// The path of the lock file, or an error describing the failure.
+///
+/// # Example
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::{update_lock, CollateOptions};
+///
+/// let lock_file = update_lock(&Utf8PathBuf::from("Cargo.toml"), CollateOptions::default()).unwrap();
+/// println!("Locked the resources in {}", lock_file);
+/// ```
+pub fn update_lock(source_manifest: &Utf8PathBuf, options: CollateOptions) -> Result<Utf8PathBuf, CollationError> {
+    let plan = plan_with_resolve_options(source_manifest, options.reporter.as_ref(), &ResolveOptions::from(&options))?;
+    let lock_file = plan.crate_root.join(LOCK_FILE_NAME);
+    let mut staged = StagedFiles::default();
+    staged.stage(&lock_file, ResourceLock::for_plan(&plan).serialize()?)?;
+    staged.commit()?;
+    Ok(lock_file)
+}
+
+/// Collate the resources for the given crate, into the crate, reporting progress and problems to
+/// the given reporter (e.g. a [BuildRsReporter] when collating from a build script).
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * reporter: Where to report progress and problems.
+///
+/// # Returns
+/// A summary of the collation, or an error describing the failure.
+pub fn collate_resources_with_reporter(
+    source_manifest: &Utf8PathBuf,
+    reporter: &dyn ReportingTrait,
+) -> Result<CollationSummary, CollationError> {
+    collate_with(source_manifest, &CollateOptions::default(), reporter)
+}
+
+/// Work out what collating the resources for the given crate would do, without touching the disk.
+///
+/// The resources are resolved and hashed (checking any required_sha) as for collation, and each is
+/// compared with any existing file at its output path, but nothing is copied or written. The
+/// reporter is told which resources would be copied and which skipped as already up to date.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * reporter: Where to report progress and problems.
+///
+/// # Returns
+/// A summary of what collation would do (with output paths relative to the resource root, as in
+/// the plan), or an error describing the failure.
+pub fn collate_resources_dry_run(
+    source_manifest: &Utf8PathBuf,
+    reporter: &dyn ReportingTrait,
+) -> Result<CollationSummary, CollationError> {
+    let plan = plan_collation_with_reporter(source_manifest, reporter)?;
+    run_plan(&plan, reporter, &RunOptions { dry_run: true, ..RunOptions::default() }, &mut StagedFiles::default())
+}
+
+/// Plan the collation of the resources for the given crate, without touching the resource root.
+///
+/// The plan holds everything needed to perform the collation (absolute source paths, output paths
+/// and the expected SHA of each resource), so it can be serialized and executed later, or
+/// elsewhere, by [execute_plan] without any access to cargo. Any required_sha is checked now.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+///
+/// # Returns
+/// The collation plan, or an error describing the failure.
+pub fn plan_collation(source_manifest: &Utf8PathBuf) -> Result<CollationPlan, CollationError> {
+    plan_collation_with_reporter(source_manifest, &DefaultReporter)
+}
+
+/// Plan the collation of the resources for the given crate, as [plan_collation] does, reporting
+/// problems to the given reporter.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * reporter: Where to report progress and problems.
+///
+/// # Returns
+/// The collation plan, or an error describing the failure.
+pub fn plan_collation_with_reporter(
+    source_manifest: &Utf8PathBuf,
+    reporter: &dyn ReportingTrait,
+) -> Result<CollationPlan, CollationError> {
+    plan_with_resolve_options(source_manifest, reporter, &ResolveOptions::default())
+}
+
+/// Plan the collation of the resources for the given crate, as [plan_collation] does, with the
+/// given options.
+///
+/// The options resolving the resources (e.g. the features, resource root and hash algorithm) are
+/// those collation would use; those for copying are ignored.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// The collation plan, or an error describing the failure.
+pub fn plan_collation_with(source_manifest: &Utf8PathBuf, options: &CollateOptions) -> Result<CollationPlan, CollationError> {
+    plan_with_resolve_options(source_manifest, options.reporter.as_ref(), &ResolveOptions::from(options))
+}
+
+/// How the consuming crate's resources are resolved: the features to enable, options overriding
+/// those declared by the consuming crate, the block size for hashing sources, whether required
+/// shas are verified, which declaration of a duplicated name is used, and which dependencies are
+/// searched (or excluded)
+struct ResolveOptions {
+    features: FeatureSelection,
+    cargo_flags: CargoFlags,
+    resource_root: Option<Utf8PathBuf>,
+    target: Option<String>,
+    hash_algorithm: Option<HashAlgorithm>,
+    line_endings: Option<LineEndings>,
+    hashed_filenames: Option<bool>,
+    deny_unused: Option<bool>,
+    verify_shas: bool,
+    always_hash: bool,
+    hash_buffer_size: usize,
+    record_path: Option<Utf8PathBuf>,
+    record_format: RecordFormat,
+    duplicate_strategy: DuplicateStrategy,
+    dependency_scope: DependencyScope,
+    exclude_crates: Vec<String>,
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        ResolveOptions {
+            features: FeatureSelection::default(),
+            cargo_flags: CargoFlags::default(),
+            resource_root: None,
+            target: None,
+            hash_algorithm: None,
+            line_endings: None,
+            hashed_filenames: None,
+            deny_unused: None,
+            verify_shas: true,
+            always_hash: false,
+            hash_buffer_size: DEFAULT_HASH_BUFFER_SIZE,
+            record_path: None,
+            record_format: RecordFormat::default(),
+            duplicate_strategy: DuplicateStrategy::default(),
+            dependency_scope: DependencyScope::default(),
+            exclude_crates: vec!(),
+        }
+    }
+}
+
+impl From<&CollateOptions> for ResolveOptions {
+    fn from(options: &CollateOptions) -> Self {
+        ResolveOptions {
+            features: options.features.to_owned(),
+            cargo_flags: options.cargo_flags,
+            resource_root: options.resource_root_override.to_owned(),
+            target: options.target.to_owned(),
+            hash_algorithm: options.hash_algorithm,
+            line_endings: options.line_endings,
+            hashed_filenames: options.hashed_filenames,
+            deny_unused: options.deny_unused,
+            verify_shas: options.verify_shas,
+            always_hash: options.always_hash,
+            hash_buffer_size: options.hash_buffer_size,
+            record_path: options.record_path.to_owned(),
+            record_format: options.record_format,
+            duplicate_strategy: options.duplicate_strategy,
+            dependency_scope: options.dependency_scope,
+            exclude_crates: options.exclude_crates.to_owned(),
+        }
+    }
+}
+
+/// The resources declared for the consuming crate: every declaration of each name, in the order
+/// they were found, and the one chosen (by the duplicate strategy) for each name
+struct DeclaredResources {
+    candidates: BTreeMap<ResourceName, Vec<ResourceSpecification>>,
+    chosen: HashMap<ResourceName, ResourceSpecification>,
+}
+
+impl DeclaredResources {
+    /// Choose the declaration of each name with the duplicate strategy, reporting any name
+    /// declared more than once
+    fn new(
+        candidates: BTreeMap<ResourceName, Vec<ResourceSpecification>>,
+        duplicate_strategy: DuplicateStrategy,
+        trace: &[ResourceName],
+        reporter: &dyn ReportingTrait,
+    ) -> DeclaredResources {
+        let mut chosen = HashMap::new();
+        for (resource_name, declarations) in &candidates {
+            let mut declarations = declarations.iter();
+            let mut choice = match declarations.next() {
+                Some(first) => first,
+                None => continue,
+            };
+            for data in declarations {
+                let keep_new = match duplicate_strategy {
+                    DuplicateStrategy::LastWins | DuplicateStrategy::Error => true,
+                    DuplicateStrategy::FirstWins => false,
+                    DuplicateStrategy::HighestVersion => data.declaring_crate_version >= choice.declaring_crate_version,
+                };
+                let (replaced, replacement) = match keep_new {
+                    true => (choice, data),
+                    false => (data, choice),
+                };
+                reporter.report_duplicate_resource(replaced, replacement);
+                trace_resource(
+                    reporter,
+                    trace,
+                    resource_name,
+                    format!(
+                        "declared by {} {}, used rather than the declaration by {} {}",
+                        &replacement.declaring_crate_name,
+                        &replacement.declaring_crate_version,
+                        &replaced.declaring_crate_name,
+                        &replaced.declaring_crate_version
+                    ),
+                );
+                choice = replacement;
+            }
+            chosen.insert(resource_name.to_owned(), choice.clone());
+        }
+        DeclaredResources { candidates, chosen }
+    }
+
+    /// Select the declaration for a requirement: the one matching its crate and version when it
+    /// gives them (the highest version matching a version requirement), otherwise the chosen one.
+    /// None when no crate declares the resource.
+    fn select(&self, res_req: &ResourceRequirement) -> Result<Option<&ResourceSpecification>, CollationError> {
+        let requirement = match (&res_req.from_crate, &res_req.version_req) {
+            (None, None) => return Ok(self.chosen.get(&res_req.resource_name)),
+            (Some(from_crate), None) => format!("crate {}", from_crate),
+            (None, Some(version_req)) => format!("version {}", version_req),
+            (Some(from_crate), Some(version_req)) => format!("crate {} at version {}", from_crate, version_req),
+        };
+        let candidates = match self.candidates.get(&res_req.resource_name) {
+            Some(candidates) => candidates,
+            None => return Ok(None),
+        };
+
+        let mut matching: Vec<&ResourceSpecification> = candidates.iter()
+            .filter(|dec| res_req.from_crate.as_ref().is_none_or(|name| &dec.declaring_crate_name == name))
+            .filter(|dec| res_req.version_req.as_ref().is_none_or(|req| req.matches(&dec.declaring_crate_version)))
+            .collect();
+        if res_req.version_req.is_some() {
+            // Only the highest matching version is wanted, as cargo would resolve it
+            let highest = matching.iter().map(|dec| &dec.declaring_crate_version).max().cloned();
+            matching.retain(|dec| Some(&dec.declaring_crate_version) == highest.as_ref());
+        }
+        match matching.as_slice() {
+            [] => Err(CollationError::UnmatchedRequirement {
+                resource: res_req.resource_name.to_owned(),
+                requirement,
+                candidates: candidates.to_owned(),
+            }),
+            [declaration] => Ok(Some(declaration)),
+            _ => Err(CollationError::AmbiguousRequirement {
+                resource: res_req.resource_name.to_owned(),
+                requirement,
+                candidates: matching.into_iter().cloned().collect(),
+            }),
+        }
+    }
+}
+
+/// Plan the collation of the resources for the given crate, with the given resolve options
+fn plan_with_resolve_options(
+    source_manifest: &Utf8PathBuf,
+    reporter: &dyn ReportingTrait,
+    resolve_options: &ResolveOptions,
+) -> Result<CollationPlan, CollationError> {
+    let metadata = get_metadata(source_manifest, &resolve_options.features, resolve_options.cargo_flags)?;
+    let root_package = get_root_package(&metadata, source_manifest)?;
+    let crate_root = source_manifest.parent().expect("No manifest directory!");
+    plan_package(&metadata, root_package, crate_root, reporter, resolve_options)
+}
+
+/// Plan the collation of the resources for the given package (with its crate root) of the
+/// metadata, with the given resolve options
+fn plan_package(
+    metadata: &Metadata,
+    package: &Package,
+    crate_root: &Utf8Path,
+    reporter: &dyn ReportingTrait,
+    resolve_options: &ResolveOptions,
+) -> Result<CollationPlan, CollationError> {
+    let (required_resources_spec, declared_resources) =
+        resolve_package_requirements(metadata, package, reporter, resolve_options)?;
+    if !resolve_options.verify_shas {
+        reporter.report_warning(
+            "Required shas are NOT being verified (--no-verify), so changed resources will be collated; never do this in CI"
+        );
+    }
+
+    // Unless always hashing, sources unchanged since the previous collation aren't read again
+    let recorded = match resolve_options.always_hash {
+        true => HashMap::new(),
+        false => recorded_sources(&get_record_file(
+            &resolve_options.record_path,
+            resolve_options.record_format,
+            &required_resources_spec.resource_root,
+            crate_root,
+        )),
+    };
+
+    let mut planned_resources = vec!();
+    for (res_req, res_dec) in select_required_resources(&required_resources_spec, &declared_resources, reporter)? {
+        planned_resources.push(plan_resource(
+            res_req,
+            res_dec,
+            recorded.get(&(res_dec.resource_name.to_owned(), res_dec.full_crate_path.to_owned())),
+            &required_resources_spec,
+            resolve_options.hash_buffer_size,
+            resolve_options.verify_shas,
+            reporter,
+        )?);
+    }
+
+    // Collate in name order, so the output doesn't change between runs with the same resources
+    planned_resources.sort_by(|a, b| a.specification.resource_name.cmp(&b.specification.resource_name));
+
+    let missing_optional = required_resources_spec.required_resources.iter()
+        .filter(|res_req| res_req.optional && matches!(declared_resources.select(res_req), Ok(None)))
+        .map(|res_req| res_req.resource_name.to_owned())
+        .collect();
+
+    Ok(CollationPlan {
+        crate_root: crate_root.to_owned(),
+        consumer: required_resources_spec,
+        resources: planned_resources,
+        missing_optional,
+    })
+}
+
+/// Resolve the resources collation would copy for the given crate, without hashing or copying them.
+///
+/// This gives the declared resources the crate requires (all of those available when it has no
+/// requires list), e.g. to package them in a custom way.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+///
+/// # Returns
+/// The resource specifications (with any encoding and output path required by the crate) sorted by
+/// resource name, or an error describing the failure.
+pub fn resolve_resources(source_manifest: &Utf8PathBuf) -> Result<Vec<ResourceSpecification>, CollationError> {
+    let (required_resources_spec, declared_resources) =
+        resolve_requirements(source_manifest, &DefaultReporter, &ResolveOptions::default())?;
+
+    let mut resources: Vec<ResourceSpecification> =
+        select_required_resources(&required_resources_spec, &declared_resources, &DefaultReporter)?
+            .into_iter()
+            .map(|(res_req, res_dec)| required_specification(res_req, res_dec))
+            .collect();
+    resources.sort_by(|a, b| a.resource_name.cmp(&b.resource_name));
+    Ok(resources)
+}
+
+/// Stream a single resource of the given crate to a writer, without collating to the resource root.
+///
+/// The resource is resolved as it would be for collation (so must be required by the crate, and
+/// match any required_sha), then its source is copied to the writer (with the line endings
+/// collation gives it), e.g. to serve it on demand.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * resource_name: The name of the resource.
+/// * writer: Where to write the resource's content.
+///
+/// # Returns
+/// The planned resource (its specification and SHA), or an error describing the failure.
+pub fn collate_one_to<W: Write>(
+    source_manifest: &Utf8PathBuf,
+    resource_name: &str,
+    writer: &mut W,
+) -> Result<PlannedResource, CollationError> {
+    let (required_resources_spec, declared_resources) =
+        resolve_requirements(source_manifest, &DefaultReporter, &ResolveOptions::default())?;
+    let res_req = required_resources_spec.required_resources.iter()
+        .find(|res_req| res_req.resource_name == resource_name)
+        .ok_or_else(|| CollationError::NotRequired(resource_name.to_owned()))?;
+    let res_dec = declared_resources.select(res_req)?.ok_or_else(|| {
+        DefaultReporter.report_missing_resource(resource_name);
+        CollationError::MissingResource(resource_name.to_owned())
+    })?;
+    let planned_resource =
+        plan_resource(res_req, res_dec, None, &required_resources_spec, DEFAULT_HASH_BUFFER_SIZE, true, &DefaultReporter)?;
+
+    let source_path = &planned_resource.specification.full_crate_path;
+    match line_endings_for(&planned_resource.specification, &required_resources_spec) {
+        Some(line_endings) => writer.write_all(&read_normalized(source_path, line_endings)?),
+        None => {
+            let mut source = File::open(source_path)
+                .map_err(|e| CollationError::io(format!("Error opening {}", source_path), e))?;
+            io::copy(&mut source, writer).map(|_| ())
+        }
+    }.map_err(|e| CollationError::io(format!("Unable to write resource {}", resource_name), e))?;
+    Ok(planned_resource)
+}
+
+/// Collate the resources for the given crate into memory, rather than the resource root, e.g.
+/// where there is no file system to write them to.
+///
+/// The resources are resolved and planned as for collation (so any required_sha is checked),
+/// then each source is read whole, with the line endings collation gives a text resource. No
+/// output directory (or record) is touched.
+///
+/// Every resource is held in memory at once, so a large resource set (e.g. of media files) needs
+/// as much memory as the resources' total size; to handle one resource at a time, use
+/// [collate_one_to] instead.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+///
+/// # Returns
+/// The content of each resource, by name, or an error describing the failure.
+///
+/// # Example
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::collate_to_memory;
+///
+/// let resources = collate_to_memory(&Utf8PathBuf::from("Cargo.toml")).unwrap();
+/// for (name, content) in &resources {
+///     println!("{} is {} bytes", name, content.len());
+/// }
+/// ```
+pub fn collate_to_memory(source_manifest: &Utf8PathBuf) -> Result<HashMap<ResourceName, Vec<u8>>, CollationError> {
+    let plan = plan_collation(source_manifest)?;
+    let mut resources = HashMap::new();
+    for planned_resource in &plan.resources {
+        resources.insert(
+            planned_resource.specification.resource_name.to_owned(),
+            read_resource(planned_resource, &plan.consumer)?,
+        );
+    }
+    Ok(resources)
+}
+
+/// Resolve the consuming crate's resource requirement and all the resources declared for it
+fn resolve_requirements(
+    source_manifest: &Utf8PathBuf,
+    reporter: &dyn ReportingTrait,
+    resolve_options: &ResolveOptions,
+) -> Result<(ResourceConsumerSpecification, DeclaredResources), CollationError> {
+    let metadata = get_metadata(source_manifest, &resolve_options.features, resolve_options.cargo_flags)?;
+    let root_package = get_root_package(&metadata, source_manifest)?;
+    resolve_package_requirements(&metadata, root_package, reporter, resolve_options)
+}
+
+/// Resolve the given package's resource requirement, as the consuming crate, and all the resources
+/// declared for it in the metadata
+fn resolve_package_requirements(
+    metadata: &Metadata,
+    root_package: &Package,
+    reporter: &dyn ReportingTrait,
+    resolve_options: &ResolveOptions,
+) -> Result<(ResourceConsumerSpecification, DeclaredResources), CollationError> {
+    // Read the consuming crate's declaration first, as it may ask for resources to be traced
+    let mut consumer_declaration = get_consumer_declaration(root_package)?;
+    consumer_declaration.resource_root = resolve_options.resource_root.to_owned().or(consumer_declaration.resource_root);
+    consumer_declaration.hash_algorithm = resolve_options.hash_algorithm.or(consumer_declaration.hash_algorithm);
+    consumer_declaration.line_endings = resolve_options.line_endings.or(consumer_declaration.line_endings);
+    consumer_declaration.hashed_filenames = resolve_options.hashed_filenames.or(consumer_declaration.hashed_filenames);
+    let deny_unused = resolve_options.deny_unused.or(consumer_declaration.deny_unused).unwrap_or(false);
+    let trace = consumer_declaration.trace.clone().unwrap_or_default();
 
     // Find all the declared resources!
 
-    // Find the packages recursively
-    let all_packages: &Vec<Package> = &metadata.packages;
-    let mut declared_resources: HashMap<String, ResourceSpecification> = HashMap::new();
-    for package in all_packages {
-        get_package_resource_data(package, &mut declared_resources)?
+    // Find the packages recursively, only to the direct dependencies when asked to
+    let max_depth = match resolve_options.dependency_scope {
+        DependencyScope::All => consumer_declaration.max_depth,
+        DependencyScope::DirectOnly => Some(1),
+    };
+    let all_packages = get_package_details(metadata, root_package, max_depth)?;
+    let all_packages = exclude_packages(all_packages, &resolve_options.exclude_crates, reporter)?;
+
+    // When names must be unique check them all up front, so every conflict is reported at once
+    let duplicate_strategy = resolve_options.duplicate_strategy;
+    if consumer_declaration.unique_names.unwrap_or(false) || duplicate_strategy == DuplicateStrategy::Error {
+        let conflicts = find_name_conflicts(metadata, &all_packages, reporter)?;
+        if !conflicts.is_empty() {
+            Err(CollationError::NameConflicts(conflicts))?
+        }
+    }
+
+    let mut candidates: BTreeMap<ResourceName, Vec<ResourceSpecification>> = BTreeMap::new();
+    let mut malformed = Vec::new();
+    for (package, dependency_chain) in all_packages {
+        let in_workspace = metadata.workspace_members.contains(&package.id);
+        get_package_resource_data(package, in_workspace, &dependency_chain, &mut candidates, &mut malformed, &trace, reporter)?
     }
+    check_declarations(malformed)?;
+    let declared_resources = DeclaredResources::new(candidates, duplicate_strategy, &trace, reporter);
 
     // Find the resource requirement (for the consuming crate)
-    let root_package = metadata.root_package().expect("Unexpected error finding the consuming crate");
-    let required_resources_spec = get_resource_requirement(&root_package, &declared_resources)?;
+    let crate_root = root_package.manifest_path.parent().expect("No manifest directory!");
+    let requires_listed = consumer_declaration.requires.is_some();
+    let required_resources_spec = get_resource_requirement(
+        consumer_declaration,
+        crate_root,
+        resolve_options.target.as_deref(),
+        &declared_resources.chosen,
+        reporter,
+    )?;
+    for name in &trace {
+        if !required_resources_spec.required_resources.iter().any(|r| &r.resource_name == name) {
+            match declared_resources.chosen.contains_key(name) {
+                true => trace_resource(reporter, &trace, name, "not required by the consuming crate"),
+                false => trace_resource(reporter, &trace, name, "not declared by any crate"),
+            }
+        }
+    }
+
+    // A requires list leaving out declared resources may be a misconfiguration, so they're reported
+    // (without a list every declared resource is required)
+    if requires_listed {
+        let unused = find_unused_resources(&required_resources_spec, &declared_resources);
+        match deny_unused && !unused.is_empty() {
+            true => Err(CollationError::UnusedResources(unused))?,
+            false => unused.iter().for_each(|res_dec| reporter.report_unused_resource(res_dec)),
+        }
+    }
+
+    Ok((required_resources_spec, declared_resources))
+}
+
+/// Find the chosen declarations (in name order) of the names the consumer doesn't require
+fn find_unused_resources(
+    required_resources_spec: &ResourceConsumerSpecification,
+    declared_resources: &DeclaredResources,
+) -> Vec<ResourceSpecification> {
+    let required: HashSet<&ResourceName> = required_resources_spec.required_resources.iter()
+        .map(|res_req| &res_req.resource_name)
+        .collect();
+    let mut unused: Vec<ResourceSpecification> = declared_resources.chosen.values()
+        .filter(|res_dec| !required.contains(&res_dec.resource_name))
+        .cloned()
+        .collect();
+    unused.sort_by(|a, b| a.resource_name.cmp(&b.resource_name));
+    unused
+}
+
+/// Select the declared resource for each of the consumer's requirements, skipping optional ones
+/// no crate declares, and those that can't be read when the consumer asks to
+fn select_required_resources<'a>(
+    required_resources_spec: &'a ResourceConsumerSpecification,
+    declared_resources: &'a DeclaredResources,
+    reporter: &dyn ReportingTrait,
+) -> Result<Vec<(&'a ResourceRequirement, &'a ResourceSpecification)>, CollationError> {
+    let trace = &required_resources_spec.trace;
+    let mut selected = vec!();
+    for res_req in &required_resources_spec.required_resources {
+        let res_dec = match declared_resources.select(res_req)? {
+            None if res_req.optional => {
+                trace_resource(reporter, trace, &res_req.resource_name, "optional and not declared by any crate");
+                reporter.report_skipped_resource(&res_req.resource_name, "optional and not declared by any crate");
+                continue;
+            }
+            res_dec => res_dec,
+        };
+        let res_dec = res_dec.ok_or_else(|| {
+            trace_resource(reporter, trace, &res_req.resource_name, "required but not declared by any crate");
+            reporter.report_missing_resource(&res_req.resource_name);
+            CollationError::MissingResource(res_req.resource_name.to_owned())
+        })?;
+        if let Some(from_crate) = &res_req.from_crate {
+            trace_resource(
+                reporter,
+                trace,
+                &res_req.resource_name,
+                format!(
+                    "declared by {} {}, as required from crate {}",
+                    &res_dec.declaring_crate_name,
+                    &res_dec.declaring_crate_version,
+                    from_crate
+                ),
+            );
+        }
+        if required_resources_spec.skip_unreadable && !is_readable(&res_dec.full_crate_path) {
+            reporter.report_skipped_resource(
+                &res_dec.resource_name,
+                &format!(
+                    "from {} {} as {} is not readable (permission denied)",
+                    &res_dec.declaring_crate_name,
+                    &res_dec.declaring_crate_version,
+                    &res_dec.full_crate_path
+                ),
+            );
+            continue;
+        }
+        selected.push((res_req, res_dec));
+    }
+    Ok(selected)
+}
+
+/// Execute a collation plan, copying its resources into the resource root and writing the record.
+///
+/// No cargo metadata is needed; only the file system is used. Each resource's source must still
+/// have the SHA it had when the plan was made.
+///
+/// # Arguments
+/// * plan: The plan, as produced by [plan_collation].
+///
+/// # Returns
+/// A summary of the collation, or an error describing the failure.
+pub fn execute_plan(plan: &CollationPlan) -> Result<CollationSummary, CollationError> {
+    execute_plan_with_reporter(plan, &DefaultReporter)
+}
+
+/// Execute a collation plan, as [execute_plan] does, reporting progress to the given reporter.
+///
+/// # Arguments
+/// * plan: The plan, as produced by [plan_collation].
+/// * reporter: Where to report progress and problems.
+///
+/// # Returns
+/// A summary of the collation, or an error describing the failure.
+pub fn execute_plan_with_reporter(
+    plan: &CollationPlan,
+    reporter: &dyn ReportingTrait,
+) -> Result<CollationSummary, CollationError> {
+    let mut staged = StagedFiles::default();
+    let summary = run_plan(plan, reporter, &RunOptions::default(), &mut staged)?;
+    staged.commit()?;
+    Ok(summary)
+}
+
+/// How a collation plan is run: whether it is a dry run, whether stale resources are pruned, how
+/// resources are placed in the resource root, how they are hashed (and whether copies are
+/// verified, or given checksum files), where (and whether) the record
+/// is written, where any Rust module or integrity manifest of the resources is generated, and
+/// whether the resources are written to the resource root or an archive
+struct RunOptions {
+    dry_run: bool,
+    prune: bool,
+    copy_mode: CopyMode,
+    always_hash: bool,
+    post_verify: bool,
+    emit_checksums: bool,
+    hash_buffer_size: usize,
+    record_path: Option<Utf8PathBuf>,
+    record_format: RecordFormat,
+    write_record: bool,
+    rust_module: Option<Utf8PathBuf>,
+    integrity_manifest: Option<Utf8PathBuf>,
+    integrity_algorithm: SriAlgorithm,
+    names_manifest: Option<Utf8PathBuf>,
+    output_target: OutputTarget,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            dry_run: false,
+            prune: false,
+            copy_mode: CopyMode::default(),
+            always_hash: false,
+            post_verify: false,
+            emit_checksums: false,
+            hash_buffer_size: DEFAULT_HASH_BUFFER_SIZE,
+            record_path: None,
+            record_format: RecordFormat::default(),
+            write_record: true,
+            rust_module: None,
+            integrity_manifest: None,
+            integrity_algorithm: SriAlgorithm::default(),
+            names_manifest: None,
+            output_target: OutputTarget::default(),
+        }
+    }
+}
+
+/// Run a collation plan, or (for a dry run) work out what running it would do without touching
+/// the disk.
+///
+/// The resources are copied into place, and the files written beside them (the record, checksum
+/// files and manifests) staged, to be moved into place together by the caller.
+fn run_plan(
+    plan: &CollationPlan,
+    reporter: &dyn ReportingTrait,
+    run_options: &RunOptions,
+    staged: &mut StagedFiles,
+) -> Result<CollationSummary, CollationError> {
+    match &run_options.output_target {
+        OutputTarget::Directory => {}
+        #[cfg(feature = "zip")]
+        OutputTarget::ZipArchive(archive) =>
+            return archive::collate_to_archive(
+                plan,
+                &plan.crate_root.join(archive),
+                run_options,
+                reporter,
+                staged,
+                archive::write_zip_archive,
+            ),
+        #[cfg(feature = "tar")]
+        OutputTarget::TarArchive(archive) =>
+            return archive::collate_to_archive(
+                plan,
+                &plan.crate_root.join(archive),
+                run_options,
+                reporter,
+                staged,
+                match archive::is_gzipped(archive) {
+                    true => archive::write_tar_gz_archive,
+                    false => archive::write_tar_archive,
+                },
+            ),
+    }
 
+    // Resources copied to the same path would replace each other (in any order, when copied in
+    // parallel), so they are rejected before anything is copied
+    check_unique_output_paths(plan.resources.iter().map(|planned_resource| {
+        let res_dec = &planned_resource.specification;
+        (plan.consumer.root_for(res_dec).join(&res_dec.output_path), &res_dec.resource_name)
+    }))?;
+    // Nor is anything copied when the resources are over the consumer's size limits
+    check_planned_size_budget(plan)?;
+
+    let dry_run = run_options.dry_run;
     // Where do we put the resources?
-    let resource_root = required_resources_spec.resource_root;
-    create_output_directory(&resource_root)?;
+    let resource_root = &plan.consumer.resource_root;
+    if !dry_run {
+        create_output_directory(resource_root, plan.consumer.directory_mode)?;
+        for group_root in plan.consumer.roots.values() {
+            create_output_directory(group_root, plan.consumer.directory_mode)?;
+        }
+    }
+
+    let mut summary = CollationSummary {
+        resource_root: resource_root.to_owned(),
+        copied: vec!(),
+        skipped: vec!(),
+        missing_optional: plan.missing_optional.to_owned(),
+        bytes_copied: 0,
+        total_bytes: 0,
+    };
+    if plan.resources.is_empty() {
+        reporter.report_no_resources_found();
+        let writes_listing = run_options.rust_module.is_some()
+            || run_options.integrity_manifest.is_some()
+            || run_options.names_manifest.is_some();
+        if dry_run || !(run_options.prune || writes_listing) {
+            return Ok(summary);
+        }
+    }
+
+    let record_file_path =
+        get_record_file(&run_options.record_path, run_options.record_format, resource_root, &plan.crate_root);
+    let recorded = match dry_run {
+        true => HashMap::new(),
+        false => recorded_resources(&record_file_path, &plan.consumer, &plan.crate_root),
+    };
+
+    // Each resource is copied independently (in parallel, with the rayon feature), and the outcomes
+    // then accounted for in plan order
+    let outcomes = map_resources(&plan.resources, |planned_resource| {
+        let res_dec = &planned_resource.specification;
+        Ok(match dry_run {
+            true => (
+                check_resource(planned_resource, &plan.consumer, run_options, reporter)?,
+                res_dec.output_path.to_owned(),
+            ),
+            false => (
+                copy_resource(
+                    planned_resource,
+                    recorded.get(&plan.consumer.root_for(res_dec).join(&res_dec.output_path)),
+                    &plan.consumer,
+                    run_options,
+                    reporter,
+                )?,
+                styled_output_path(&res_dec.output_path, plan.consumer.root_for(res_dec), &plan.consumer, &plan.crate_root)?,
+            ),
+        })
+    })?;
+
+    let mut resolved_resources = vec!();
+    for (planned_resource, (outcome, output_path)) in plan.resources.iter().zip(outcomes) {
+        let res_dec = &planned_resource.specification;
+        // Record the mode collation gave the resource, so verify can check it (a link has its
+        // source's mode, so none is recorded for one), and when it was modified
+        let output_resources_path = plan.consumer.root_for(res_dec).join(&res_dec.output_path);
+        let (mode, modified) = match dry_run {
+            true => (None, None),
+            false => (
+                output_mode(&res_dec.full_crate_path, res_dec, &plan.consumer)?
+                    .and_then(|_| placed_mode(&res_dec.full_crate_path, &output_resources_path)),
+                fs::metadata(&output_resources_path).ok().as_ref().and_then(modified_nanos),
+            ),
+        };
+        let resolved_resource = ResolvedResource {
+            specification: ResourceSpecification { output_path, ..res_dec.clone() },
+            output_sha: planned_resource.expected_sha.to_owned(),
+            source_sha: planned_resource.source_sha.to_owned(),
+            hash_algorithm: plan.consumer.hash_algorithm,
+            size: match outcome {
+                CopyOutcome::Copied(size) | CopyOutcome::AlreadyExisted(size) => size,
+            },
+            mode,
+            modified,
+            source_modified: planned_resource.source_modified,
+        };
+        match outcome {
+            CopyOutcome::Copied(size) => {
+                summary.bytes_copied += size;
+                summary.copied.push(resolved_resource.clone());
+            }
+            CopyOutcome::AlreadyExisted(_) => summary.skipped.push(resolved_resource.clone()),
+        }
+        summary.total_bytes += resolved_resource.size;
+        resolved_resources.push(resolved_resource);
+    }
+
+    if run_options.emit_checksums {
+        for planned_resource in &plan.resources {
+            stage_checksum_file(planned_resource, &plan.consumer, dry_run, staged, reporter)?;
+        }
+    }
+    if dry_run {
+        return Ok(summary);
+    }
+
+    // Prune only after all the required resources are in place, using the previous record
+    if run_options.prune {
+        prune_resources(plan, &record_file_path, reporter)?;
+    }
+    if let Some(rust_module) = &run_options.rust_module {
+        stage_rust_module(plan, &plan.crate_root.join(rust_module), staged)?;
+    }
+    if let Some(names_manifest) = &run_options.names_manifest {
+        stage_names_manifest(plan, &plan.crate_root.join(names_manifest), staged)?;
+    }
+    if let Some(integrity_manifest) = &run_options.integrity_manifest {
+        stage_integrity_manifest(plan, &plan.crate_root.join(integrity_manifest), run_options, staged)?;
+    }
+    if plan.consumer.hashed_filenames {
+        stage_hashed_filenames_manifest(plan, &resource_root.join(HASHED_FILENAMES_MANIFEST), staged)?;
+    }
+    if !run_options.write_record {
+        return Ok(summary);
+    }
+
+    // Record the resources, only after all the resources themselves are in place, in name order
+    // so the record doesn't change between runs with the same resources
+    resolved_resources.sort_by(|a, b| a.specification.resource_name.cmp(&b.specification.resource_name));
+    staged.stage(&record_file_path, serialize_record(&resolved_resources, run_options.record_format)?)?;
+    Ok(summary)
+}
+
+/// Collate a plan's resources to a sink, or (for a dry run) work out what doing so would do
+/// without writing anything, with the record of resolved resources (when written) as a file at
+/// the top level.
+///
+/// Each resource is read whole, and only written when the sink doesn't already hold it.
+fn collate_to_sink(
+    plan: &CollationPlan,
+    sink: &dyn OutputSink,
+    reporter: &dyn ReportingTrait,
+    run_options: &RunOptions,
+) -> Result<CollationSummary, CollationError> {
+    let dry_run = run_options.dry_run;
+    let hash_algorithm = plan.consumer.hash_algorithm;
+    if plan.resources.is_empty() {
+        reporter.report_no_resources_found();
+    }
+    check_unique_output_paths(plan.resources.iter().map(|planned_resource| {
+        let res_dec = &planned_resource.specification;
+        (res_dec.output_path.to_owned(), &res_dec.resource_name)
+    }))?;
+    check_planned_size_budget(plan)?;
+
+    let outcomes = map_resources(&plan.resources, |planned_resource| {
+        let res_dec = &planned_resource.specification;
+        let output_path = &res_dec.output_path;
+        let already_exists = sink.exists_with_sha(output_path, &planned_resource.expected_sha, hash_algorithm)?;
+        let content = read_resource(planned_resource, &plan.consumer)?;
+        let mode = output_mode(&res_dec.full_crate_path, res_dec, &plan.consumer)?;
+        match dry_run {
+            true => reporter.report_dry_run_collection(res_dec, already_exists, output_path, &planned_resource.expected_sha),
+            false => {
+                if !already_exists {
+                    sink.write_resource(output_path, &content, mode)?;
+                }
+                reporter.report_resource_collection(res_dec, already_exists, output_path, &planned_resource.expected_sha);
+            }
+        }
+        Ok((already_exists, content.len() as u64, mode))
+    })?;
+
+    let mut summary = CollationSummary {
+        resource_root: plan.consumer.resource_root.to_owned(),
+        copied: vec!(),
+        skipped: vec!(),
+        missing_optional: plan.missing_optional.to_owned(),
+        bytes_copied: 0,
+        total_bytes: 0,
+    };
+    let mut resolved_resources = vec!();
+    for (planned_resource, (already_exists, size, mode)) in plan.resources.iter().zip(outcomes) {
+        let resolved_resource = ResolvedResource {
+            specification: planned_resource.specification.clone(),
+            output_sha: planned_resource.expected_sha.to_owned(),
+            source_sha: planned_resource.source_sha.to_owned(),
+            hash_algorithm,
+            size,
+            mode,
+            modified: None,
+            source_modified: None,
+        };
+        match already_exists {
+            true => summary.skipped.push(resolved_resource.clone()),
+            false => {
+                summary.bytes_copied += size;
+                summary.copied.push(resolved_resource.clone());
+            }
+        }
+        summary.total_bytes += size;
+        resolved_resources.push(resolved_resource);
+    }
+
+    if !dry_run && run_options.write_record {
+        let record_path = Utf8Path::new(DEFAULT_RECORD_FILE_NAME).with_extension(run_options.record_format.extension());
+        let record = serialize_record(&resolved_resources, run_options.record_format)?;
+        sink.write_resource(&record_path, record.as_bytes(), None)?;
+    }
+    Ok(summary)
+}
+
+/// Check no two resources have the same output path (e.g. the same path within the same root),
+/// as one would replace the other; the first such path (in path order) fails collation, naming
+/// the resources
+pub(crate) fn check_unique_output_paths<'a>(
+    output_paths: impl IntoIterator<Item = (Utf8PathBuf, &'a ResourceName)>,
+) -> Result<(), CollationError> {
+    let mut resources_by_path: BTreeMap<Utf8PathBuf, Vec<&ResourceName>> = BTreeMap::new();
+    for (output_path, resource_name) in output_paths {
+        resources_by_path.entry(output_path).or_default().push(resource_name);
+    }
+    if let Some((output_path, names)) = resources_by_path.into_iter().find(|(_, names)| names.len() > 1) {
+        Err(CollationError::DuplicateOutputPath {
+            output_path,
+            resources: names.into_iter().cloned().collect(),
+        })?
+    }
+    Ok(())
+}
+
+/// Check the planned resources against the consumer's size limits before any is collated, each
+/// with the size it is collated with (i.e. after normalizing any line endings)
+fn check_planned_size_budget(plan: &CollationPlan) -> Result<(), CollationError> {
+    let consumer_spec = &plan.consumer;
+    if consumer_spec.max_file_bytes.is_none() && consumer_spec.max_total_bytes.is_none() {
+        return Ok(());
+    }
+    let mut sizes = vec!();
+    for planned_resource in &plan.resources {
+        let res_dec = &planned_resource.specification;
+        let size = match line_endings_for(res_dec, consumer_spec) {
+            Some(line_endings) => read_normalized(&res_dec.full_crate_path, line_endings)?.len() as u64,
+            None => fs::metadata(&res_dec.full_crate_path)
+                .map_err(|e| CollationError::io(format!("Unable to read the size of {}", &res_dec.full_crate_path), e))?
+                .len(),
+        };
+        sizes.push((res_dec.resource_name.to_owned(), size));
+    }
+    check_size_budget(&sizes, consumer_spec)
+}
+
+/// Check the sizes of the named resources against the consumer's size limits: the first (in plan
+/// order) larger than max_file_bytes, and then their combined size against max_total_bytes,
+/// naming the largest resources when it's over
+pub(crate) fn check_size_budget(
+    sizes: &[(ResourceName, u64)],
+    consumer_spec: &ResourceConsumerSpecification,
+) -> Result<(), CollationError> {
+    if let Some(max_file_bytes) = consumer_spec.max_file_bytes {
+        if let Some((resource_name, size)) = sizes.iter().find(|(_, size)| *size > max_file_bytes) {
+            Err(CollationError::ResourceTooLarge {
+                resource: resource_name.to_owned(),
+                size: *size,
+                max_file_bytes,
+            })?
+        }
+    }
+
+    let total_bytes: u64 = sizes.iter().map(|(_, size)| size).sum();
+    if let Some(max_total_bytes) = consumer_spec.max_total_bytes {
+        if total_bytes > max_total_bytes {
+            let mut largest = sizes.to_vec();
+            largest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            largest.truncate(LARGEST_CONTRIBUTORS);
+            Err(CollationError::SizeBudgetExceeded { total_bytes, max_total_bytes, largest })?
+        }
+    }
+    Ok(())
+}
+
+/// Apply the function to each planned resource in turn, giving the results in plan order or the
+/// first error
+#[cfg(not(feature = "rayon"))]
+fn map_resources<T>(
+    resources: &[PlannedResource],
+    f: impl Fn(&PlannedResource) -> Result<T, CollationError>,
+) -> Result<Vec<T>, CollationError> {
+    resources.iter().map(f).collect()
+}
+
+/// Apply the function to every planned resource in parallel, giving the results in plan order or
+/// the first error (in plan order) once all have finished
+#[cfg(feature = "rayon")]
+fn map_resources<T: Send>(
+    resources: &[PlannedResource],
+    f: impl Fn(&PlannedResource) -> Result<T, CollationError> + Sync + Send,
+) -> Result<Vec<T>, CollationError> {
+    use rayon::prelude::*;
+    let results: Vec<Result<T, CollationError>> = resources.par_iter().map(f).collect();
+    results.into_iter().collect()
+}
+
+/// Stage the Rust module mapping the plan's resource names to their output paths, unless it is
+/// already up to date (so code including it isn't rebuilt needlessly)
+pub(crate) fn stage_rust_module(
+    plan: &CollationPlan,
+    module_file: &Utf8PathBuf,
+    staged: &mut StagedFiles,
+) -> Result<(), CollationError> {
+    let resources: Vec<(&ResourceName, &Utf8Path)> = plan.resources.iter()
+        .map(|planned_resource| (
+            &planned_resource.specification.resource_name,
+            planned_resource.specification.output_path.as_path(),
+        ))
+        .collect();
+    staged.stage(module_file, generate_rust_module(&resources))
+}
+
+/// Stage the names manifest mapping each resource name to its output path (relative to the
+/// resource root), unless it is already up to date
+pub(crate) fn stage_names_manifest(
+    plan: &CollationPlan,
+    manifest_file: &Utf8PathBuf,
+    staged: &mut StagedFiles,
+) -> Result<(), CollationError> {
+    let manifest = generate_manifest(
+        plan.resources.iter().map(|planned_resource| (
+            planned_resource.specification.resource_name.as_str(),
+            planned_resource.specification.output_path.as_str(),
+        )),
+        "names manifest",
+        manifest_file,
+    )?;
+    staged.stage(manifest_file, manifest)
+}
+
+/// Stage the integrity manifest mapping the plan's output paths to the SRI strings of the collated
+/// resources, unless it is already up to date
+fn stage_integrity_manifest(
+    plan: &CollationPlan,
+    manifest_file: &Utf8PathBuf,
+    run_options: &RunOptions,
+    staged: &mut StagedFiles,
+) -> Result<(), CollationError> {
+    let mut resources: Vec<(&Utf8Path, String)> = vec!();
+    for planned_resource in &plan.resources {
+        let output_path = &planned_resource.specification.output_path;
+        let integrity = get_file_integrity(
+            &plan.consumer.root_for(&planned_resource.specification).join(output_path),
+            run_options.integrity_algorithm,
+            run_options.hash_buffer_size,
+        )?;
+        resources.push((output_path, integrity));
+    }
+    staged.stage(manifest_file, generate_integrity_manifest(&resources)?)
+}
+
+/// Stage the manifest mapping each resource's output path, before its file name was hashed, to the
+/// hashed output path (both relative to the resource root)
+fn stage_hashed_filenames_manifest(
+    plan: &CollationPlan,
+    manifest_file: &Utf8PathBuf,
+    staged: &mut StagedFiles,
+) -> Result<(), CollationError> {
+    let manifest = generate_json_manifest(
+        plan.resources.iter().filter_map(|planned_resource| {
+            let res_dec = &planned_resource.specification;
+            res_dec.unhashed_output_path.as_ref().map(|unhashed| (unhashed.as_str(), res_dec.output_path.as_str()))
+        }),
+        "hashed filenames manifest",
+    )?;
+    staged.stage(manifest_file, manifest)
+}
+
+/// Get where the record of resolved resources is written: the given path (relative to the crate
+/// root), or the default file in the resource root (with the format's extension)
+fn get_record_file(
+    record_path: &Option<Utf8PathBuf>,
+    record_format: RecordFormat,
+    resource_root: &Utf8Path,
+    crate_root: &Utf8Path,
+) -> Utf8PathBuf {
+    match record_path {
+        Some(record_path) => crate_root.join(record_path),
+        None => resource_root.join(DEFAULT_RECORD_FILE_NAME).with_extension(record_format.extension()),
+    }
+}
+
+/// Remove the resources in the previous record that the plan no longer requires
+fn prune_resources(
+    plan: &CollationPlan,
+    record_file: &Utf8PathBuf,
+    reporter: &dyn ReportingTrait,
+) -> Result<(), CollationError> {
+    if !record_file.is_file() {
+        return Ok(()); // Nothing has been recorded, so nothing can be pruned
+    }
+
+    let (removed, _kept) = remove_stale_resources(
+        read_record(record_file)?,
+        &planned_output_paths(plan),
+        &plan.consumer,
+        &plan.crate_root,
+        false,
+    )?;
+    for resource in removed {
+        reporter.report_resource_pruned(&plan.consumer.root_for(&resource.specification).join(&resource.specification.output_path));
+    }
+    Ok(())
+}
+
+/// The output paths (joined to their roots) of the plan's resources
+fn planned_output_paths(plan: &CollationPlan) -> HashSet<Utf8PathBuf> {
+    plan.resources.iter()
+        .map(|planned_resource| {
+            let res_dec = &planned_resource.specification;
+            plan.consumer.root_for(res_dec).join(&res_dec.output_path)
+        })
+        .collect()
+}
+
+/// Work out what executing a collation plan would change in the resource root, without changing it.
+///
+/// Each planned resource is compared with any existing file at its output path, giving the files
+/// that would be added, overwritten (with the old and new SHA) or left unchanged.
+///
+/// # Arguments
+/// * plan: The plan, as produced by [plan_collation].
+///
+/// # Returns
+/// The planned changes, or an error describing the failure.
+pub fn plan_changes(plan: &CollationPlan) -> Result<PlannedChanges, CollationError> {
+    plan_changes_with(plan, &CollateOptions::default())
+}
+
+/// Work out what executing a collation plan with the given options would change in the resource
+/// root, as [plan_changes] does.
+///
+/// When the options prune, the recorded resources the plan no longer requires (as read from the
+/// record the options give) are also listed, as files that would be removed.
+///
+/// # Arguments
+/// * plan: The plan, as produced by [plan_collation_with] with the same options.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// The planned changes, or an error describing the failure.
+pub fn plan_changes_with(plan: &CollationPlan, options: &CollateOptions) -> Result<PlannedChanges, CollationError> {
+    let mut changes = vec!();
+    for planned_resource in &plan.resources {
+        let res_dec = &planned_resource.specification;
+        let output_resources_path = plan.consumer.root_for(res_dec).join(&res_dec.output_path);
+        let resource_name = res_dec.resource_name.to_owned();
+        let output_path = res_dec.output_path.to_owned();
+        let new_sha = planned_resource.expected_sha.to_owned();
+
+        let change = match output_resources_path.is_file() {
+            false => PlannedChange::Add { resource_name, output_path, new_sha },
+            true => {
+                let old_sha = get_file_sha(&output_resources_path, plan.consumer.hash_algorithm, DEFAULT_HASH_BUFFER_SIZE)?;
+                match old_sha == new_sha {
+                    true => PlannedChange::Unchanged { resource_name, output_path, sha: new_sha },
+                    false => PlannedChange::Overwrite { resource_name, output_path, old_sha, new_sha },
+                }
+            }
+        };
+        changes.push(change);
+    }
+
+    if options.prune {
+        let record_file =
+            get_record_file(&options.record_path, options.record_format, &plan.consumer.resource_root, &plan.crate_root);
+        if record_file.is_file() {
+            let (removed, _kept) = remove_stale_resources(
+                read_record(&record_file)?,
+                &planned_output_paths(plan),
+                &plan.consumer,
+                &plan.crate_root,
+                true,
+            )?;
+            changes.extend(removed.into_iter().map(|resource| PlannedChange::Remove {
+                resource_name: resource.specification.resource_name,
+                output_path: resource.specification.output_path,
+                old_sha: resource.output_sha,
+            }));
+        }
+    }
+
+    Ok(PlannedChanges { resource_root: plan.consumer.resource_root.to_owned(), changes })
+}
+
+/// Verify that the resource root already holds the resources collation would put there, without
+/// changing it, e.g. to check it is up to date in CI.
+///
+/// The resources are resolved and hashed as for collation, and each is compared with the file at
+/// its output path. Nothing is copied, no directories are created and the record isn't written.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+///
+/// # Returns
+/// Nothing when the resource root is up to date, otherwise a
+/// [CollationError::ResourcesOutOfDate] listing (by name) every resource that is missing, out
+/// of date, without the mode collation gives it, fails its required_sha or (when the crate has a
+/// cargo-resources.lock) doesn't match the lock, or an error describing the failure.
+pub fn verify_resources(source_manifest: &Utf8PathBuf) -> Result<(), CollationError> {
+    verify_resources_with(source_manifest, &CollateOptions::default())
+}
+
+/// Verify that the resource root already holds the resources collation would put there, as
+/// [verify_resources] does, with the given options.
+///
+/// The options are those the resources were collated with (e.g. the features, resource root and
+/// hash algorithm), so the resources are resolved as collation resolved them; those for copying
+/// are ignored. When required shas aren't verified, a resource not matching them is only reported.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// Nothing when the resource root is up to date, otherwise a [CollationError::ResourcesOutOfDate]
+/// listing the resources that aren't, or an error describing the failure.
+pub fn verify_resources_with(source_manifest: &Utf8PathBuf, options: &CollateOptions) -> Result<(), CollationError> {
+    let reporter = options.reporter.as_ref();
+    let hash_buffer_size = options.hash_buffer_size;
+    let (required_resources_spec, declared_resources) =
+        resolve_requirements(source_manifest, reporter, &ResolveOptions::from(options))?;
+    let hash_algorithm = required_resources_spec.hash_algorithm;
+    let crate_root = source_manifest.parent().expect("No manifest directory!");
+    let lock = ResourceLock::read(&crate_root.join(LOCK_FILE_NAME))?;
+
+    let mut drift = vec!();
+    let mut shas = BTreeMap::new();
+    for (res_req, res_dec) in select_required_resources(&required_resources_spec, &declared_resources, reporter)? {
+        // The resource is compared in the form collation gives it, e.g. with normalized line endings,
+        // at the output path the consumer requires
+        let output_spec = required_specification(res_req, res_dec);
+        let resource_name = output_spec.resource_name.to_owned();
+        let output_path = output_spec.output_path.to_owned();
+        let source_sha = get_file_sha(&res_dec.full_crate_path, hash_algorithm, hash_buffer_size)?;
+        let mismatch =
+            required_sha_mismatch(res_req, res_dec, &source_sha, &required_resources_spec, hash_buffer_size)?;
+        if let Some((expected, actual)) = mismatch {
+            match options.verify_shas {
+                true => {
+                    shas.insert(resource_name.to_owned(), source_sha);
+                    drift.push(ResourceDrift::ShaMismatch { resource_name, expected, actual });
+                    continue;
+                }
+                false => reporter.report_unverified_sha(&resource_name, &expected, &actual),
+            }
+        }
+        let source_sha = match line_endings_for(&output_spec, &required_resources_spec) {
+            Some(_) => get_resource_sha(&output_spec, &required_resources_spec, hash_buffer_size)?,
+            None => source_sha,
+        };
+        shas.insert(resource_name.to_owned(), source_sha.to_owned());
+        let resource_root = required_resources_spec.root_for(&output_spec).to_owned();
+        let output_path = match required_resources_spec.hashed_filenames {
+            true => hash_file_name(output_spec, &source_sha).output_path,
+            false => output_path,
+        };
+
+        let output_resources_path = resource_root.join(&output_path);
+        if !output_resources_path.is_file() {
+            drift.push(ResourceDrift::Missing { resource_name, output_path });
+            continue;
+        }
+        let existing_sha = get_file_sha(&output_resources_path, hash_algorithm, hash_buffer_size)?;
+        if existing_sha != source_sha {
+            drift.push(ResourceDrift::OutOfDate { resource_name, output_path, expected: source_sha, actual: existing_sha });
+            continue;
+        }
+        let expected_mode = output_mode(&res_dec.full_crate_path, res_dec, &required_resources_spec)?;
+        if let Some(expected) = expected_mode {
+            let actual = placed_mode(&res_dec.full_crate_path, &output_resources_path);
+            if let Some(actual) = actual.filter(|actual| *actual != expected) {
+                drift.push(ResourceDrift::ModeMismatch { resource_name, output_path, expected, actual });
+            }
+        }
+    }
+
+    if lock.is_some() {
+        drift.extend(ResourceLock::drift(lock.as_ref(), &shas));
+    }
+
+    if !drift.is_empty() {
+        drift.sort_by(|a, b| a.resource_name().cmp(b.resource_name()));
+        Err(CollationError::ResourcesOutOfDate(drift))?
+    }
+    Ok(())
+}
+
+/// Remove the resources recorded by the last collation that are no longer required, e.g. after a
+/// dependency is removed or a resource renamed.
+///
+/// Only files listed in the record of resolved resources (and no longer required) are removed,
+/// along with any directories they leave empty, so files not placed by collation are never
+/// touched. The record is then rewritten without them.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * dry_run: When true, only find the files that would be removed, without touching the disk.
+///
+/// # Returns
+/// The paths of the removed (or, for a dry run, removable) files, sorted, or an error describing
+/// the failure.
+pub fn clean_resources(source_manifest: &Utf8PathBuf, dry_run: bool) -> Result<Vec<Utf8PathBuf>, CollationError> {
+    clean_with_options(source_manifest, CollateOptions::default().dry_run(dry_run))
+}
+
+/// Remove the resources recorded by the last collation that are no longer required, as
+/// [clean_resources] does, with the given options.
+///
+/// The options are those the resources were collated with, so the same record and resource
+/// root are used; the dry run option is honoured, and those for copying ignored.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// The paths of the removed (or, for a dry run, removable) files, sorted, or an error describing
+/// the failure.
+pub fn clean_with_options(
+    source_manifest: &Utf8PathBuf,
+    options: CollateOptions,
+) -> Result<Vec<Utf8PathBuf>, CollationError> {
+    let dry_run = options.dry_run;
+    let reporter = options.reporter.as_ref();
+    let (required_resources_spec, declared_resources) =
+        resolve_requirements(source_manifest, reporter, &ResolveOptions::from(&options))?;
+    let crate_root = source_manifest.parent().expect("No manifest directory!");
+    let record_file = get_record_file(
+        &options.record_path,
+        options.record_format,
+        &required_resources_spec.resource_root,
+        crate_root,
+    );
+    if !record_file.is_file() {
+        return Ok(vec!()); // Nothing has been collated, so there is nothing to clean
+    }
+
+    let required_output_paths: HashSet<Utf8PathBuf> =
+        select_required_resources(&required_resources_spec, &declared_resources, reporter)?
+            .into_iter()
+            .map(|(res_req, res_dec)| {
+                let output_spec = required_specification(res_req, res_dec);
+                required_resources_spec.root_for(&output_spec).join(&output_spec.output_path)
+            })
+            .collect();
+    let (removed, kept) = remove_stale_resources(
+        read_record(&record_file)?,
+        &required_output_paths,
+        &required_resources_spec,
+        crate_root,
+        dry_run,
+    )?;
+
+    if !dry_run && options.write_record && !removed.is_empty() {
+        let mut staged = StagedFiles::default();
+        staged.stage(&record_file, serialize_record(&kept, options.record_format)?)?;
+        staged.commit()?;
+    }
+    Ok(removed.into_iter()
+        .map(|resource| required_resources_spec.root_for(&resource.specification).join(&resource.specification.output_path))
+        .collect())
+}
+
+/// Remove the recorded resources whose output paths (joined to their roots) aren't required, and
+/// any directories that leaves empty, giving the removed resources (sorted by path, each with its
+/// output path relative to its root again) and the record entries kept
+fn remove_stale_resources(
+    recorded: Vec<ResolvedResource>,
+    required_output_paths: &HashSet<Utf8PathBuf>,
+    consumer_spec: &ResourceConsumerSpecification,
+    crate_root: &Utf8Path,
+    dry_run: bool,
+) -> Result<(Vec<ResolvedResource>, Vec<ResolvedResource>), CollationError> {
+    let mut removed = vec!();
+    let mut kept = vec!();
+    for resource in recorded {
+        let resource_root = consumer_spec.root_for(&resource.specification).to_owned();
+        let resource_root = &resource_root;
+        let output_path =
+            unstyled_output_path(&resource.specification.output_path, resource_root, consumer_spec, crate_root)?;
+        let output_resources_path = resource_root.join(&output_path);
+        if required_output_paths.contains(&output_resources_path) {
+            kept.push(resource);
+            continue;
+        }
+
+        if !output_resources_path.is_file() {
+            continue; // Already gone
+        }
+        verify_resource_is_in_root(&output_resources_path, resource_root)?;
+        if !dry_run {
+            fs::remove_file(&output_resources_path)
+                .map_err(|e| CollationError::io(format!("Unable to remove resource {}", &output_resources_path), e))?;
+            remove_empty_directories(&output_resources_path, resource_root)?;
+        }
+        let specification = ResourceSpecification { output_path, ..resource.specification };
+        removed.push((output_resources_path, ResolvedResource { specification, ..resource }));
+    }
+    removed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok((removed.into_iter().map(|(_, resource)| resource).collect(), kept))
+}
+
+/// Remove the directories containing a removed resource that are now empty, up to (but not
+/// including) the resource root
+fn remove_empty_directories(removed_path: &Utf8Path, resource_root: &Utf8Path) -> Result<(), CollationError> {
+    for directory in removed_path.ancestors().skip(1) {
+        if directory == resource_root || !directory.starts_with(resource_root) {
+            break;
+        }
+        let is_empty = fs::read_dir(directory)
+            .map_err(|e| CollationError::io(format!("Unable to read directory {}", directory), e))?
+            .next()
+            .is_none();
+        if !is_empty {
+            break;
+        }
+        fs::remove_dir(directory)
+            .map_err(|e| CollationError::io(format!("Unable to remove empty directory {}", directory), e))?;
+    }
+    Ok(())
+}
+
+/// Export the resources declared by the given crate itself (not those of its dependencies).
+///
+/// This is intended for publishing a catalogue of the resources a crate provides.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+///
+/// # Returns
+/// The crate's resource specifications sorted by resource name, or an error describing the
+/// failure.
+pub fn export_declarations(source_manifest: &Utf8PathBuf) -> Result<Vec<ResourceSpecification>, CollationError> {
+    export_declarations_with(source_manifest, &CollateOptions::default())
+}
+
+/// Export the resources declared by the given crate itself, as [export_declarations] does, with
+/// the given options (of which the features and cargo flags are used to read the crate's
+/// metadata).
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// The crate's resource specifications sorted by resource name, or an error describing the
+/// failure.
+pub fn export_declarations_with(
+    source_manifest: &Utf8PathBuf,
+    options: &CollateOptions,
+) -> Result<Vec<ResourceSpecification>, CollationError> {
+    let metadata = get_metadata(source_manifest, &options.features, options.cargo_flags)?;
+    let root_package = get_root_package(&metadata, source_manifest)?;
+
+    let mut declared_resources: BTreeMap<ResourceName, Vec<ResourceSpecification>> = BTreeMap::new();
+    let mut malformed = Vec::new();
+    let dependency_chain = [package_label(root_package)];
+    let reporter = options.reporter.as_ref();
+    get_package_resource_data(root_package, true, &dependency_chain, &mut declared_resources, &mut malformed, &[], reporter)?;
+    check_declarations(malformed)?;
+
+    // The map is in name order; a name the crate declares twice gives its last declaration
+    Ok(declared_resources.into_values().filter_map(|mut declarations| declarations.pop()).collect())
+}
+
+/// List the resources declared across the given crate's dependency tree (including the crate
+/// itself), whether or not it requires them, without copying anything.
+///
+/// This is intended for discovering what resources are available, before requiring them:
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::list_available_resources;
+///
+/// for res_dec in list_available_resources(&Utf8PathBuf::from("Cargo.toml")).unwrap() {
+///     println!("{} from {} {}", res_dec.resource_name, res_dec.declaring_crate_name, res_dec.declaring_crate_version);
+/// }
+/// ```
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+///
+/// # Returns
+/// Every declaration found sorted by resource name (a name declared more than once is listed once
+/// per declaration, in the order the crates were scanned), or an error describing the failure.
+pub fn list_available_resources(source_manifest: &Utf8PathBuf) -> Result<Vec<ResourceSpecification>, CollationError> {
+    list_available_resources_with(source_manifest, &CollateOptions::default())
+}
+
+/// List the resources declared across the given crate's dependency tree, as
+/// [list_available_resources] does, with the given options.
+///
+/// The features, cargo flags, dependency scope and crate exclusions choose the crates searched, as
+/// they would for collation.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// Every declaration found sorted by resource name, or an error describing the failure.
+pub fn list_available_resources_with(
+    source_manifest: &Utf8PathBuf,
+    options: &CollateOptions,
+) -> Result<Vec<ResourceSpecification>, CollationError> {
+    let reporter = options.reporter.as_ref();
+    let metadata = get_metadata(source_manifest, &options.features, options.cargo_flags)?;
+    let root_package = get_root_package(&metadata, source_manifest)?;
+    let consumer_declaration = get_consumer_declaration(root_package)?;
+    let max_depth = match options.dependency_scope {
+        DependencyScope::All => consumer_declaration.max_depth,
+        DependencyScope::DirectOnly => Some(1),
+    };
+    let all_packages = get_package_details(&metadata, root_package, max_depth)?;
+    let all_packages = exclude_packages(all_packages, &options.exclude_crates, reporter)?;
+
+    let mut declared_resources: BTreeMap<ResourceName, Vec<ResourceSpecification>> = BTreeMap::new();
+    let mut malformed = Vec::new();
+    for (package, dependency_chain) in all_packages {
+        let in_workspace = metadata.workspace_members.contains(&package.id);
+        get_package_resource_data(package, in_workspace, &dependency_chain, &mut declared_resources, &mut malformed, &[], reporter)?;
+    }
+    check_declarations(malformed)?;
+    Ok(declared_resources.into_values().flatten().collect())
+}
+
+/// Explain why a resource is available to the given crate: each declaration of it across the
+/// crate's dependency tree, with the chain of dependencies through which its declaring crate was
+/// found.
+///
+/// This is intended for auditing large dependency trees, where a resource appears unexpectedly:
+/// ```
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::explain_resource;
+///
+/// let manifest_file = Utf8PathBuf::from("tests/fixtures/virtual_workspace/member_b/Cargo.toml");
+/// let declarations = explain_resource(&manifest_file, "a.txt").unwrap();
+///
+/// assert_eq!(declarations.len(), 1);
+/// assert_eq!(declarations[0].dependency_chain, ["member_b@0.1.0", "member_a@0.1.0"]);
+/// assert!(explain_resource(&manifest_file, "c.txt").is_err());
+/// ```
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * resource_name: The name of the resource.
+///
+/// # Returns
+/// Every declaration of the resource, in the order the crates were scanned, or an error when no
+/// crate declares it or describing the failure.
+pub fn explain_resource(source_manifest: &Utf8PathBuf, resource_name: &str) -> Result<Vec<ResourceSpecification>, CollationError> {
+    explain_resource_with(source_manifest, resource_name, &CollateOptions::default())
+}
+
+/// Explain why a resource is available to the given crate, as [explain_resource] does, searching
+/// the crates [list_available_resources_with] would with the given options.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * resource_name: The name of the resource.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// Every declaration of the resource, in the order the crates were scanned, or an error when no
+/// crate declares it or describing the failure.
+pub fn explain_resource_with(
+    source_manifest: &Utf8PathBuf,
+    resource_name: &str,
+    options: &CollateOptions,
+) -> Result<Vec<ResourceSpecification>, CollationError> {
+    let declarations: Vec<ResourceSpecification> = list_available_resources_with(source_manifest, options)?
+        .into_iter()
+        .filter(|res_dec| res_dec.resource_name == resource_name)
+        .collect();
+    match declarations.is_empty() {
+        true => Err(CollationError::NotDeclared {
+            manifest: source_manifest.to_owned(),
+            resource: resource_name.to_owned(),
+        }),
+        false => Ok(declarations),
+    }
+}
+
+/// Get where the record of resolved resources is written when collating for the given crate.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+///
+/// # Returns
+/// The path of the record file, or an error describing the failure.
+pub fn record_file_path(source_manifest: &Utf8PathBuf) -> Result<Utf8PathBuf, CollationError> {
+    record_file_path_with(source_manifest, &CollateOptions::default())
+}
+
+/// Get where the record of resolved resources is written when collating for the given crate with
+/// the given options, as [record_file_path] does: the options' record path, otherwise the record
+/// file (with the extension of the options' record format) in the resource root.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// The path of the record file, or an error describing the failure.
+pub fn record_file_path_with(source_manifest: &Utf8PathBuf, options: &CollateOptions) -> Result<Utf8PathBuf, CollationError> {
+    let metadata = get_metadata(source_manifest, &options.features, options.cargo_flags)?;
+    let root_package = get_root_package(&metadata, source_manifest)?;
+    let consumer_declaration = get_consumer_declaration(root_package)?;
+    let crate_root = source_manifest.parent().expect("No manifest directory!");
+    let declared_root = options.resource_root_override.to_owned().or(consumer_declaration.resource_root);
+    let resource_root = get_resource_root(declared_root, crate_root, options.target.as_deref())?;
+    Ok(get_record_file(&options.record_path, options.record_format, &resource_root, crate_root))
+}
+
+/// Check that every resource name available to the given crate is declared only once.
+///
+/// Collation uses the last declaration found for a name, so this reports every group of
+/// conflicting declarations at once, before anything is copied.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+///
+/// # Returns
+/// The conflicts (empty when all names are unique) sorted by name, or an error describing
+/// the failure.
+pub fn check_unique_names(source_manifest: &Utf8PathBuf) -> Result<Vec<ResourceNameConflict>, CollationError> {
+    check_unique_names_with(source_manifest, &CollateOptions::default())
+}
+
+/// Check that every resource name available to the given crate is declared only once, as
+/// [check_unique_names] does, with the given options.
+///
+/// The features, cargo flags, dependency scope and crate exclusions choose the crates searched, as
+/// they would for collation, so the conflicts are those collation would find, and any problems are
+/// reported to the options' reporter.
+///
+/// # Arguments
+/// * source_manifest: The path of the cargo manifest (Cargo.toml) of the crate.
+/// * options: The collation options, see [CollateOptions].
+///
+/// # Returns
+/// The conflicts (empty when all names are unique) sorted by name, or an error describing
+/// the failure.
+pub fn check_unique_names_with(
+    source_manifest: &Utf8PathBuf,
+    options: &CollateOptions,
+) -> Result<Vec<ResourceNameConflict>, CollationError> {
+    let reporter = options.reporter.as_ref();
+    let metadata = get_metadata(source_manifest, &options.features, options.cargo_flags)?;
+    let root_package = get_root_package(&metadata, source_manifest)?;
+    let consumer_declaration = get_consumer_declaration(root_package)?;
+    let max_depth = match options.dependency_scope {
+        DependencyScope::All => consumer_declaration.max_depth,
+        DependencyScope::DirectOnly => Some(1),
+    };
+    let all_packages = get_package_details(&metadata, root_package, max_depth)?;
+    let all_packages = exclude_packages(all_packages, &options.exclude_crates, reporter)?;
+    find_name_conflicts(&metadata, &all_packages, reporter)
+}
+
+/// Find the resource names declared more than once by the packages
+fn find_name_conflicts(
+    metadata: &Metadata,
+    packages: &[(&Package, Vec<String>)],
+    reporter: &dyn ReportingTrait,
+) -> Result<Vec<ResourceNameConflict>, CollationError> {
+    let mut declarations: BTreeMap<ResourceName, Vec<ResourceSpecification>> = BTreeMap::new();
+    let mut malformed = Vec::new();
+    for (package, dependency_chain) in packages {
+        let in_workspace = metadata.workspace_members.contains(&package.id);
+        get_package_resource_data(package, in_workspace, dependency_chain, &mut declarations, &mut malformed, &[], reporter)?;
+    }
+    check_declarations(malformed)?;
+
+    Ok(
+        declarations.into_iter()
+            .filter(|(_, specifications)| specifications.len() > 1)
+            .map(|(resource_name, declarations)| ResourceNameConflict { resource_name, declarations })
+            .collect()
+    )
+}
+
+/// Get the package of the crate with the given manifest, which a virtual workspace doesn't have
+fn get_root_package<'a>(metadata: &'a Metadata, source_manifest: &Utf8PathBuf) -> Result<&'a Package, CollationError> {
+    metadata.root_package().ok_or_else(|| CollationError::NoRootPackage(source_manifest.to_owned()))
+}
+
+/// Get the cargo metadata for the crate with the given manifest, resolved with the given features
+fn get_metadata(
+    source_manifest: &Utf8PathBuf,
+    features: &FeatureSelection,
+    cargo_flags: CargoFlags,
+) -> Result<Metadata, CollationError> {
+    if !source_manifest.exists() {
+        Err(CollationError::ManifestNotFound(source_manifest.to_owned()))?
+    }
+    // Now lets get the metadata of a package
+    let mut metadata_cmd = cargo_metadata::MetadataCommand::new();
+    metadata_cmd.manifest_path(source_manifest);
+    if let Some(cargo_opt) = features.cargo_opt() {
+        metadata_cmd.features(cargo_opt);
+    }
+    metadata_cmd.other_options(cargo_flags.other_options());
+    metadata_cmd.exec().map_err(CollationError::Metadata)
+}
+
+/// Get the packages the consuming package depends on (including itself), walking the dependency
+/// graph breadth first to at most max_depth edges from the consuming package.
+///
+/// The packages are given in order of name, then version, so duplicate resource names are resolved
+/// the same way however cargo lists them.
+///
+/// Each package is given with the chain of packages (as name@version) through which the walk first
+/// reached it, from the consuming package to the package itself, i.e. its shortest dependency path.
+fn get_package_details<'a>(
+    metadata: &'a Metadata,
+    root_package: &Package,
+    max_depth: Option<usize>,
+) -> Result<Vec<(&'a Package, Vec<String>)>, CollationError> {
+    let resolve = metadata.resolve.as_ref()
+        .ok_or(CollationError::MissingDependencyGraph)?;
+    let nodes: HashMap<&PackageId, &Node> = resolve.nodes.iter()
+        .map(|node| (&node.id, node))
+        .collect();
+
+    let mut depths: HashMap<&PackageId, usize> = HashMap::from([(&root_package.id, 0)]);
+    let mut parents: HashMap<&PackageId, &PackageId> = HashMap::new();
+    let mut to_visit = VecDeque::from([(&root_package.id, 0)]);
+    while let Some((package_id, depth)) = to_visit.pop_front() {
+        if max_depth.is_some_and(|max| depth >= max) {
+            continue;
+        }
+        let Some(node) = nodes.get(package_id) else { continue };
+        for dependency_id in &node.dependencies {
+            if !depths.contains_key(dependency_id) {
+                depths.insert(dependency_id, depth + 1);
+                parents.insert(dependency_id, package_id);
+                to_visit.push_back((dependency_id, depth + 1));
+            }
+        }
+    }
+
+    let labels: HashMap<&PackageId, String> = metadata.packages.iter()
+        .filter(|package| depths.contains_key(&package.id))
+        .map(|package| (&package.id, package_label(package)))
+        .collect();
+    let mut packages: Vec<(&Package, Vec<String>)> = metadata.packages.iter()
+        .filter(|package| depths.contains_key(&package.id))
+        .map(|package| {
+            let mut dependency_chain = vec!(labels[&package.id].to_owned());
+            let mut package_id = &package.id;
+            while let Some(parent_id) = parents.get(package_id) {
+                dependency_chain.push(labels[parent_id].to_owned());
+                package_id = parent_id;
+            }
+            dependency_chain.reverse();
+            (package, dependency_chain)
+        })
+        .collect();
+    packages.sort_by(|(a, _), (b, _)| (&a.name, &a.version, &a.id).cmp(&(&b.name, &b.version, &b.id)));
+    Ok(packages)
+}
+
+/// The package as it's given in a dependency chain, i.e. name@version
+fn package_label(package: &Package) -> String {
+    format!("{}@{}", package.name, package.version)
+}
+
+/// Remove the packages matching the exclusions (each a crate name, optionally qualified by an exact
+/// version as name@version), reporting each removed and any exclusion matching no package
+fn exclude_packages<'a>(
+    packages: Vec<(&'a Package, Vec<String>)>,
+    exclusions: &[String],
+    reporter: &dyn ReportingTrait,
+) -> Result<Vec<(&'a Package, Vec<String>)>, CollationError> {
+    let mut parsed_exclusions = vec!();
+    for exclusion in exclusions {
+        let (name, version) = match exclusion.split_once('@') {
+            Some((name, version)) => {
+                let version = Version::parse(version)
+                    .map_err(|e| CollationError::InvalidVersion {
+                        context: format!("Invalid version in crate exclusion {}", exclusion),
+                        source: e,
+                    })?;
+                (name, Some(version))
+            }
+            None => (exclusion.as_str(), None),
+        };
+        parsed_exclusions.push((exclusion, name, version));
+    }
+
+    let mut matched = HashSet::new();
+    let mut included = vec!();
+    for (package, dependency_chain) in packages {
+        let matching = parsed_exclusions.iter().find(|(_, name, version)| {
+            package.name == *name && version.as_ref().is_none_or(|version| package.version == *version)
+        });
+        match matching {
+            Some((exclusion, _, _)) => {
+                matched.insert(exclusion.as_str());
+                let declarations = package.metadata["cargo_resources"]["provides"].as_array().map_or(0, Vec::len);
+                reporter.report_excluded_crate(&package.name, &package.version, declarations);
+            }
+            None => included.push((package, dependency_chain)),
+        }
+    }
+    for (exclusion, _, _) in &parsed_exclusions {
+        if !matched.contains(exclusion.as_str()) {
+            reporter.report_warning(&format!("No crate matches the exclusion {}", exclusion));
+        }
+    }
+    Ok(included)
+}
+
+/// Get all the resources information declared by a package
+///
+/// Resources declared as workspace_only are skipped unless the package is a member of the
+/// consuming crate's workspace (i.e. listed in the metadata's workspace_members).
+///
+/// Every declaration is added to resources under its name, so the declarations of a name given
+/// more than once are all kept, in the order they were found.
+///
+/// Malformed declarations (that can't be read, or are invalid, e.g. with an absolute path) are
+/// reported and added to malformed, rather than failing, so that every one can be found in a
+/// single scan (see [check_declarations]).
+///
+/// The dependency chain (from the consuming crate to the package) is given to each specification
+/// as its provenance.
+fn get_package_resource_data(
+    package: &Package,
+    in_workspace: bool,
+    dependency_chain: &[String],
+    resources: &mut BTreeMap<ResourceName, Vec<ResourceSpecification>>,
+    malformed: &mut Vec<CollationError>,
+    trace: &[ResourceName],
+    reporter: &dyn ReportingTrait,
+) -> Result<(), CollationError> {
+    // We have the metadata, resources uses cargo_resources.provides as a collection within this!
+    let cargo_resource_metadata: &Value = &package.metadata["cargo_resources"];
+    if !cargo_resource_metadata.is_object() {
+        return Ok(()); // No metadata for us
+    }
+    check_min_tool_version(package, &cargo_resource_metadata["min_tool_version"], reporter)?;
+
+    let provides_metadata = &cargo_resource_metadata["provides"];
+    match provides_metadata {
+        Value::Array(resource_entries) => {
+            for resource_entry in resource_entries {
+                let declaration_result = serde_json::from_value::<ResourceDataDeclaration>(resource_entry.clone());
+                match declaration_result {
+                    Ok(declaration) => {
+                        if declaration.workspace_only.unwrap_or(false) && !in_workspace {
+                            reporter.report_skipped_resource(
+                                declaration.crate_path.as_str(),
+                                &format!("workspace only resource from {} which is not a workspace member", &package.name),
+                            );
+                            continue;
+                        }
+
+                        let specifications = match declared_specifications(package, declaration, dependency_chain) {
+                            Ok(specifications) => specifications,
+                            Err(err) => {
+                                reporter.report_malformed_resource_declaration(&package.name, &err.to_string());
+                                malformed.push(err);
+                                continue;
+                            }
+                        };
+                        for data in specifications {
+                            trace_resource(
+                                reporter,
+                                trace,
+                                &data.resource_name,
+                                format!(
+                                    "declared by {} {} at {}",
+                                    &data.declaring_crate_name,
+                                    &data.declaring_crate_version,
+                                    &data.full_crate_path
+                                ),
+                            );
+
+                            // Duplicates are kept, to be chosen between by DeclaredResources
+                            resources.entry(data.resource_name.to_owned()).or_default().push(data);
+                        }
+                    }
+
+                    Err(err) => {
+                        reporter.report_malformed_resource_declaration(&package.name, &err.to_string());
+                        malformed.push(CollationError::MalformedDeclaration {
+                            crate_name: package.name.to_owned(),
+                            source: err,
+                        });
+                    }
+                }
+            }
+            Ok(())
+        }
+        Value::Null => Ok(()),
+        _ => {
+            Err(CollationError::MalformedMetadata {
+                crate_name: package.name.to_owned(),
+                key: "cargo_resources.provides".to_string(),
+            })
+        }
+    }
+}
+
+/// Check a package's resource declaration and expand it into the specification of each resource
+/// it declares, failing for a declaration that is invalid (e.g. with an absolute path, or a glob
+/// matching no files).
+fn declared_specifications(
+    package: &Package,
+    declaration: ResourceDataDeclaration,
+    dependency_chain: &[String],
+) -> Result<Vec<ResourceSpecification>, CollationError> {
+    // Paths should be relative (though environment variables may expand to absolute source paths,
+    // e.g. ${OUT_DIR}/generated.css)
+    if declaration.crate_path.is_absolute() {
+        Err(CollationError::AbsoluteResourcePath {
+            crate_name: package.name.to_owned(),
+            crate_path: declaration.crate_path.to_owned(),
+        })?
+    }
+    // A source made absolute by a variable (e.g. ${OUT_DIR}/css/generated.css) is output, by
+    // default, at its path below the variable's value (css/generated.css), rather than its own path
+    let crate_path = expand_environment_variables(&declaration.crate_path, &package.name)?;
+    let default_output_path = match crate_path.is_absolute() {
+        true => path_below_leading_variable(&declaration.crate_path, &crate_path, &package.name)?,
+        false => crate_path.to_owned(),
+    };
+    let declaration = ResourceDataDeclaration {
+        crate_path,
+        output_path: match &declaration.output_path {
+            Some(output_path) => Some(expand_environment_variables(output_path, &package.name)?),
+            None => None,
+        },
+        ..declaration
+    };
+    if let Some(output_path) = declaration.output_path.as_ref().filter(|p| p.is_absolute()) {
+        Err(CollationError::AbsoluteOutputPath {
+            crate_name: package.name.to_owned(),
+            output_path: output_path.to_owned(),
+        })?
+    }
+    // Names are used in output paths (for directories), so mustn't be paths themselves
+    if let Some(name) = declaration.resource_name.as_ref().filter(|n| n.contains(['/', '\\'])) {
+        Err(CollationError::PathSeparatorInName {
+            crate_name: package.name.to_owned(),
+            resource_name: name.to_owned(),
+        })?
+    }
+
+    // Without an encoding, each file's is detected
+    let encoding = declaration.encoding.to_owned().unwrap_or(ResourceEncoding::Auto);
+    let mut specifications = vec!();
+    for (crate_path, resolved_output_path, resolved_name) in expand_declaration(package, &declaration, &default_output_path)? {
+        if resolved_output_path.is_absolute() {
+            Err(CollationError::AbsoluteOutputPath {
+                crate_name: package.name.to_owned(),
+                output_path: resolved_output_path.to_owned(),
+            })?
+        }
+        // Rejected here, as well as by the in-root check when copying, whatever the resource root
+        if resolved_output_path.components().any(|c| c == Utf8Component::ParentDir) {
+            Err(CollationError::ResourceOutsideRoot {
+                path: resolved_output_path.to_owned(),
+                root: None,
+                crate_name: Some(package.name.to_owned()),
+            })?
+        }
+        // The metadata reflects [patch] and [replace] resolutions, so the manifest path is that
+        // of the overriding source and its resources are the ones collated.
+        let full_source_path = package
+            .manifest_path.parent().expect("No manifest directory!")
+            .join(crate_path);
+        specifications.push(ResourceSpecification {
+            declaring_crate_name: package.name.to_owned(),
+            declaring_crate_version: package.version.to_owned(),
+            encoding: encoding.clone().resolve(&full_source_path),
+            full_crate_path: full_source_path,
+            output_path: resolved_output_path,
+            resource_name: resolved_name,
+            executable: declaration.executable.unwrap_or(false),
+            renamed_to: None,
+            unhashed_output_path: None,
+            group: declaration.group.to_owned(),
+            dependency_chain: dependency_chain.to_vec(),
+        });
+    }
+    Ok(specifications)
+}
+
+/// Fail with the malformed declarations found by a scan, if any: the error itself when there is
+/// just one, otherwise all of them together.
+fn check_declarations(mut malformed: Vec<CollationError>) -> Result<(), CollationError> {
+    match malformed.len() {
+        0 => Ok(()),
+        1 => Err(malformed.remove(0)),
+        _ => Err(CollationError::MultipleDeclarationErrors(malformed)),
+    }
+}
+
+/// Get the path below the value of the variable a declared path starts with, from the path as
+/// expanded, e.g. css/generated.css for ${OUT_DIR}/css/generated.css (or the file name, when
+/// nothing follows the variable)
+fn path_below_leading_variable(
+    declared: &Utf8Path,
+    expanded: &Utf8Path,
+    crate_name: &str,
+) -> Result<Utf8PathBuf, CollationError> {
+    let leading = declared.components().next().map(|component| component.as_str()).unwrap_or_default();
+    let value = expand_environment_variables(Utf8Path::new(leading), crate_name)?;
+    match expanded.strip_prefix(&value) {
+        Ok(below) if !below.as_str().is_empty() => Ok(below.to_owned()),
+        _ => Ok(expanded.file_name().map(Utf8PathBuf::from).unwrap_or_default()),
+    }
+}
+
+/// Expand a resource declaration into the crate path, output path and name of each resource it
+/// declares: a single resource, or one per file when the crate path is a glob or a directory.
+///
+/// Without a declared output path, each is output at its crate path (or the path within the glob
+/// root or directory), relative to the given default output path.
+fn expand_declaration(
+    package: &Package,
+    declaration: &ResourceDataDeclaration,
+    default_output_path: &Utf8Path,
+) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf, ResourceName)>, CollationError> {
+    let package_dir = package.manifest_path.parent().expect("No manifest directory!");
+    let full_source_path = package_dir.join(&declaration.crate_path);
+    if full_source_path.is_dir() {
+        // Each file is named and output by its path within the directory, below the directory's
+        // resource name and output path
+        let name_root = declaration.resource_name.to_owned().unwrap_or(
+            declaration.crate_path.file_name()
+                .expect("Illegal resource name").to_string()
+        );
+        let output_root = declaration.output_path.to_owned().unwrap_or(default_output_path.to_owned());
+        let files = get_directory_files(&full_source_path)?;
+        if files.is_empty() {
+            Err(CollationError::EmptyResourceDirectory {
+                crate_name: package.name.to_owned(),
+                crate_path: declaration.crate_path.to_owned(),
+            })?
+        }
+        return Ok(
+            files.into_iter().map(|relative_path| {
+                let resolved_name = relative_path.components()
+                    .fold(name_root.to_owned(), |name, component| format!("{}/{}", name, component));
+                (declaration.crate_path.join(&relative_path), output_root.join(&relative_path), resolved_name)
+            }).collect()
+        );
+    }
+
+    if !is_glob(&declaration.crate_path) {
+        // Do the conversions for optionals
+        let resolved_output_path = declaration
+            .output_path.to_owned()
+            .unwrap_or(default_output_path.to_owned());
+        let resolved_name = declaration.resource_name.to_owned().unwrap_or(
+            declaration.crate_path.file_name()
+                .expect("Illegal resource name").to_string()
+        );
+        return Ok(vec!((declaration.crate_path.to_owned(), resolved_output_path, resolved_name)));
+    }
+
+    // Each match is named by its file name, and output below the output path (or the glob root)
+    let glob_root: Utf8PathBuf = declaration.crate_path.components()
+        .take_while(|component| !is_glob(Utf8Path::new(component.as_str())))
+        .collect();
+    let output_root = declaration.output_path.to_owned().unwrap_or_else(|| default_output_path.components()
+        .take_while(|component| !is_glob(Utf8Path::new(component.as_str())))
+        .collect());
+    let pattern = match declaration.crate_path.is_absolute() {
+        true => declaration.crate_path.to_string(),
+        false => format!("{}/{}", glob::Pattern::escape(package_dir.as_str()), &declaration.crate_path),
+    };
+    let matches = glob::glob(&pattern).map_err(|e| CollationError::InvalidGlob {
+        crate_name: package.name.to_owned(),
+        crate_path: declaration.crate_path.to_owned(),
+        source: e,
+    })?;
 
-    if required_resources_spec.required_resources.len() <= 0 {
-        println!("No resources were found - finishing early.");
-        return Ok(());
+    let mut expanded = vec!();
+    for matched in matches {
+        let matched = matched.map_err(|e| CollationError::io(
+            format!("Unable to expand resource glob {} of crate {}", &declaration.crate_path, &package.name),
+            e.into(),
+        ))?;
+        let matched = Utf8PathBuf::from_path_buf(matched)
+            .map_err(CollationError::NonUtf8Path)?;
+        if !matched.is_file() {
+            continue;
+        }
+        // A glob made absolute by an environment variable may match outside the crate
+        let crate_path = matched.strip_prefix(package_dir).unwrap_or(&matched).to_owned();
+        let root_relative_path = crate_path.strip_prefix(&glob_root).expect("Glob matched outside its root");
+        let resolved_name = crate_path.file_name().expect("Illegal resource name").to_string();
+        expanded.push((crate_path.to_owned(), output_root.join(root_relative_path), resolved_name));
     }
 
-    let mut resolved_resources = vec!();
-    for res_req in required_resources_spec.required_resources {
-        let res_dec = declared_resources.get(&res_req.resource_name).ok_or(
-            format!("No resource found matching requirement {}", res_req.resource_name)
-        )?;
-        copy_resource(&res_req, &res_dec, &resource_root)?;
-        resolved_resources.push(res_dec);
+    if expanded.is_empty() {
+        Err(CollationError::UnmatchedGlob {
+            crate_name: package.name.to_owned(),
+            crate_path: declaration.crate_path.to_owned(),
+        })?
     }
+    Ok(expanded)
+}
 
-    // Write a record of the resources
-    let res = serde_json::to_string(&resolved_resources)
-        .expect("Unable to serialize the set of resolved resources");
+/// Expand the environment variables, as $VAR or ${VAR}, in a path declared by the named crate,
+/// failing when one isn't set (rather than giving a path that isn't the one intended).
+///
+/// A $ not followed by a variable name is left as it is.
+fn expand_environment_variables(path: &Utf8Path, crate_name: &str) -> Result<Utf8PathBuf, CollationError> {
+    let declared = path.as_str();
+    let mut expanded = String::new();
+    let mut rest = declared;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remainder) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => Err(CollationError::UnclosedVariable { crate_name: crate_name.to_owned(), path: path.to_owned() })?,
+            },
+            None => {
+                let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+        if name.is_empty() {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let value = std::env::var(name).map_err(|_| CollationError::UnsetVariable {
+            crate_name: crate_name.to_owned(),
+            path: path.to_owned(),
+            variable: name.to_owned(),
+        })?;
+        expanded.push_str(&value);
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    Ok(Utf8PathBuf::from(expanded))
+}
 
-    let record_file_path = resource_root.join("resolved_resources.json");
-    fs::write(record_file_path, res).map_err(|e| format!("Failed writing record file:{:?}", e))?;
+/// Get the paths of all the files within a directory and its subdirectories, relative to it, sorted
+fn get_directory_files(directory: &Utf8Path) -> Result<Vec<Utf8PathBuf>, CollationError> {
+    let mut files = vec!();
+    let mut to_visit = vec!(Utf8PathBuf::new());
+    while let Some(relative_dir) = to_visit.pop() {
+        let full_dir = directory.join(&relative_dir);
+        let entries = full_dir.read_dir_utf8()
+            .map_err(|e| CollationError::io(format!("Unable to read resource directory {}", full_dir), e))?;
+        for entry in entries {
+            let entry = entry
+                .map_err(|e| CollationError::io(format!("Unable to read resource directory {}", full_dir), e))?;
+            let relative_path = relative_dir.join(entry.file_name());
+            match entry.path().is_dir() {
+                true => to_visit.push(relative_path),
+                false => files.push(relative_path),
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
 
-    Ok(())
+/// Whether a declared crate path is a glob pattern, rather than the path of a single resource
+fn is_glob(crate_path: &Utf8Path) -> bool {
+    crate_path.as_str().contains(['*', '?', '['])
 }
 
-/// Get all the resources information declared by a package
-fn get_package_resource_data(
+/// Warn when a package needs a newer version of cargo-resources than this one to read its metadata
+fn check_min_tool_version(
     package: &Package,
-    resources: &mut HashMap<String, ResourceSpecification>,
-) -> Result<(), String> {
-    // We have the metadata, resources uses cargo_resources.provides as a collection within this!
-    let cargo_resource_metadata: &Value = &package.metadata["cargo_resources"];
-    if !cargo_resource_metadata.is_object() {
-        return Ok(()); // No metadata for us
+    min_tool_version: &Value,
+    reporter: &dyn ReportingTrait,
+) -> Result<(), CollationError> {
+    let min_version = match min_tool_version {
+        Value::Null => return Ok(()),
+        Value::String(version) => Version::parse(version)
+            .map_err(|e| CollationError::InvalidVersion {
+                context: format!("Crate {} declares an invalid min_tool_version {}", &package.name, version),
+                source: e,
+            })?,
+        _ => Err(CollationError::MalformedMetadata {
+            crate_name: package.name.to_owned(),
+            key: "cargo_resources.min_tool_version".to_string(),
+        })?
+    };
+    let tool_version = Version::parse(env!("CARGO_PKG_VERSION")).expect("Invalid cargo-resources version");
+    if tool_version < min_version {
+        reporter.report_warning(&format!(
+            "crate {} needs cargo-resources {} or later, but this is {}; some of its resource declarations may be ignored.",
+            &package.name,
+            min_version,
+            tool_version
+        ));
     }
-    let provides_metadata = &cargo_resource_metadata["provides"];
-    match provides_metadata {
-        Value::Array(resource_entries) => {
-            for resource_entry in resource_entries {
-                let declaration_result = serde_json::from_value::<ResourceDataDeclaration>(resource_entry.clone());
-                match declaration_result {
-                    Ok(declaration) => {
-                        // Do the conversions for optionals
-                        let resolved_output_path = declaration
-                            .output_path.
-                            unwrap_or(declaration.crate_path.to_owned());
-                        let resolved_name = declaration.resource_name.unwrap_or(
-                            declaration.crate_path.file_name()
-                                .expect("Illegal resource name").to_string().into()
-                        );
-
-                        // Paths should be relative
-                        if declaration.crate_path.is_absolute() {
-                            Err(
-                                format!(
-                                    "Crate {} declares an absolute resource path {}",
-                                    &package.name,
-                                    &declaration.crate_path
-                                )
-                            )?
-                        }
-                        if resolved_output_path.is_absolute() {
-                            Err(
-                                format!(
-                                    "Crate {} declares an absolute output path {}",
-                                    &package.name,
-                                    &resolved_output_path
-                                )
-                            )?
-                        }
-
-                        let full_source_path = package
-                            .manifest_path.parent().expect("No manifest directory!")
-                            .join(declaration.crate_path);
-                        let data = ResourceSpecification {
-                            declaring_crate_name: package.name.to_owned(),
-                            declaring_crate_version: package.version.to_owned(),
-                            encoding: declaration.encoding.unwrap_or(ResourceEncoding::Txt),
-                            full_crate_path: full_source_path,
-                            output_path: resolved_output_path,
-                            resource_name: resolved_name.to_owned(),
-                        };
+    Ok(())
+}
 
-                        // Later resources will overwrite old ones!
-                        resources.insert(resolved_name.to_owned(), data);
-                    }
+/// Get the resource declaration of the consuming package, with the options in any
+/// cargo-resources.toml beside its manifest overriding those in its metadata
+fn get_consumer_declaration(package: &Package) -> Result<ResourceConsumerDeclaration, CollationError> {
+    // We have the metadata, requirements are declared in  cargo_resources.
+    let mut cargo_resource_metadata: Value = package.metadata["cargo_resources"].clone();
 
-                    Err(err) => {
-                        return Err(format!("Malformed resource declaration in {}: {}",
-                                           package.name,
-                                           err));
-                    }
-                }
-            }
-            Ok(())
+    let config_file = package.manifest_path.with_file_name(CONSUMER_CONFIG_FILE_NAME);
+    let has_config_file = config_file.is_file();
+    if has_config_file {
+        let config = read_consumer_config(&config_file)?;
+        if cargo_resource_metadata.is_null() {
+            cargo_resource_metadata = Value::Object(serde_json::Map::new());
         }
-        Value::Null => Ok(()),
-        _ => {
-            Err(
-                "unexpected type for [package.metadata.cargo_resources].provides in the json-metadata".to_owned()
-            )
+        if let Value::Object(metadata) = &mut cargo_resource_metadata {
+            metadata.extend(config);
         }
     }
-}
-
-/// Get the resource requirement for a package
-fn get_resource_requirement(
-    package: &Package,
-    available_resources: &HashMap<String, ResourceSpecification>,
-) -> Result<ResourceConsumerSpecification, String> {
-    // We have the metadata, requirements are declared in  cargo_resources.
-    let cargo_resource_metadata: &Value = &package.metadata["cargo_resources"];
 
     // When nothing is specified use default options and packages
     let consumer_declaration = match &cargo_resource_metadata {
-        Value::Null => ResourceConsumerDeclaration {
-            resource_root: None,
-            requires: None,
-        },
+        Value::Null => ResourceConsumerDeclaration::default(),
         Value::Object(_) => {
-            serde_json::from_value(cargo_resource_metadata.clone())
-                .map_err(|e| format!("Unable to read consuming crates [package.metadata.cargo_resources]: {}", e.to_string()))?
+            serde_json::from_value(cargo_resource_metadata)
+                .map_err(|e| match has_config_file {
+                    true => CollationError::parse(
+                        format!("Unable to read consuming crates [package.metadata.cargo_resources] with {}", config_file),
+                        e,
+                    ),
+                    false => CollationError::parse("Unable to read consuming crates [package.metadata.cargo_resources]", e),
+                })?
         }
-        _ => panic!("Misconfigured [package.metadata.cargo_resources] in consuming package.")
+        _ => Err(CollationError::MalformedMetadata {
+            crate_name: package.name.to_owned(),
+            key: "cargo_resources".to_string(),
+        })?
     };
+    Ok(consumer_declaration)
+}
+
+/// Read the consumer's options from its cargo-resources.toml, to merge with its metadata
+#[cfg(feature = "toml")]
+fn read_consumer_config(config_file: &Utf8Path) -> Result<serde_json::Map<String, Value>, CollationError> {
+    let contents = fs::read_to_string(config_file)
+        .map_err(|e| CollationError::io(format!("Unable to read {}", config_file), e))?;
+    let config = toml::from_str(&contents)
+        .map_err(|e| CollationError::parse(format!("Unable to parse {}", config_file), e))?;
+    Ok(config)
+}
+
+/// Reading a cargo-resources.toml needs the toml feature, so is an error without it (rather than
+/// the file being silently ignored)
+#[cfg(not(feature = "toml"))]
+fn read_consumer_config(config_file: &Utf8Path) -> Result<serde_json::Map<String, Value>, CollationError> {
+    Err(CollationError::FeatureNotEnabled { feature: "toml".to_string(), needed_for: format!("Reading {}", config_file) })
+}
+
+/// Get the resource root from the consumer's declared root (if any).
+///
+/// A {triple} placeholder is replaced with the given target triple (e.g. from the --target option
+/// of the cargo tool), otherwise that from the TARGET environment variable, as set by cargo for
+/// build scripts.
+///
+/// A relative root is relative to the crate root, so is joined to it unless the crate root is the
+/// current directory (e.g. in a build script), keeping the root short for output.
+fn get_resource_root(
+    declared_root: Option<Utf8PathBuf>,
+    crate_root: &Utf8Path,
+    target: Option<&str>,
+) -> Result<Utf8PathBuf, CollationError> {
+    let mut resource_root = declared_root.unwrap_or(Utf8PathBuf::from(DEFAULT_RESOURCE_ROOT));
+    if resource_root.as_str().contains(TARGET_TRIPLE_PLACEHOLDER) {
+        let triple = match target {
+            Some(target) => target.to_owned(),
+            None => std::env::var("TARGET")
+                .map_err(|_e| CollationError::MissingTargetTriple(resource_root.to_owned()))?,
+        };
+        resource_root = resource_root.as_str().replace(TARGET_TRIPLE_PLACEHOLDER, &triple).into();
+    }
+
+    let is_current_dir = std::env::current_dir().ok()
+        .and_then(|dir| dir.canonicalize().ok())
+        .is_some_and(|dir| crate_root.canonicalize().is_ok_and(|root| root == dir));
+    match resource_root.is_relative() && !is_current_dir {
+        true => Ok(crate_root.join(resource_root)),
+        false => Ok(resource_root),
+    }
+}
 
-    let resource_root = consumer_declaration.resource_root.unwrap_or(Utf8PathBuf::from("target/resources"));
+/// Get the resource requirement from the consuming package's declaration
+fn get_resource_requirement(
+    consumer_declaration: ResourceConsumerDeclaration,
+    crate_root: &Utf8Path,
+    target: Option<&str>,
+    available_resources: &HashMap<String, ResourceSpecification>,
+    reporter: &dyn ReportingTrait,
+) -> Result<ResourceConsumerSpecification, CollationError> {
+    let trace = consumer_declaration.trace.unwrap_or_default();
+    let hash_algorithm = consumer_declaration.hash_algorithm.unwrap_or_default();
+    let resource_root = get_resource_root(consumer_declaration.resource_root, crate_root, target)?;
+    let roots = consumer_declaration.roots.unwrap_or_default().into_iter()
+        .map(|(group, root)| Ok((group, get_resource_root(Some(root), crate_root, target)?)))
+        .collect::<Result<BTreeMap<String, Utf8PathBuf>, CollationError>>()?;
 
     let required_resources: Vec<ResourceRequirement> = match consumer_declaration.requires {
         None => { // Default is to use all available resources with default options
-            available_resources.values().map(|res_spec| ResourceRequirement {
-                resource_name: res_spec.resource_name.to_owned(),
-                required_sha: None,
+            available_resources.values().map(|res_spec| {
+                trace_resource(reporter, &trace, &res_spec.resource_name, "required as no requires list is declared");
+                ResourceRequirement {
+                    resource_name: res_spec.resource_name.to_owned(),
+                    required_sha: None,
+                    encoding: None,
+                    from_crate: None,
+                    version_req: None,
+                    optional: false,
+                    output_path_override: None,
+                    rename_to: None,
+                    group: None,
+                }
             }).collect()
         }
         Some(declarations) => { // Just convert each declaration to a spec
-            declarations.into_iter().map(|dec| ResourceRequirement {
-                resource_name: dec.resource_name.to_owned(),
-                required_sha: dec.required_sha.to_owned(),
-            }).collect()
+            declarations.into_iter().map(|dec| {
+                trace_resource(reporter, &trace, &dec.resource_name, "required by the consuming crate's requires list");
+                // The override is held to the rules for declared output paths
+                if let Some(output_path) = &dec.output_path_override {
+                    if output_path.is_absolute() || output_path.components().any(|c| c == Utf8Component::ParentDir) {
+                        Err(CollationError::InvalidOutputPathOverride {
+                            resource: dec.resource_name.to_owned(),
+                            output_path: output_path.to_owned(),
+                        })?
+                    }
+                }
+                if let Some(file_name) = &dec.rename_to {
+                    let is_file_name = !matches!(file_name.as_str(), "" | "." | "..") && !file_name.contains(['/', '\\']);
+                    if !is_file_name {
+                        Err(CollationError::InvalidRename {
+                            resource: dec.resource_name.to_owned(),
+                            rename_to: file_name.to_owned(),
+                        })?
+                    }
+                }
+                let res_req = ResourceRequirement {
+                    resource_name: dec.resource_name.to_owned(),
+                    required_sha: dec.required_sha.to_owned(),
+                    encoding: dec.encoding,
+                    from_crate: dec.from_crate,
+                    version_req: dec.version_req,
+                    optional: dec.optional.unwrap_or(false),
+                    output_path_override: dec.output_path_override,
+                    rename_to: dec.rename_to,
+                    group: dec.group,
+                };
+                // A malformed required sha is rejected up front, rather than failing to match
+                res_req.required_digests(hash_algorithm)?;
+                Ok(res_req)
+            }).collect::<Result<Vec<ResourceRequirement>, CollationError>>()?
         }
     };
 
-    Ok(ResourceConsumerSpecification { resource_root, required_resources })
+    Ok(ResourceConsumerSpecification {
+        resource_root,
+        roots,
+        required_resources,
+        preserve_mtime: consumer_declaration.preserve_mtime.unwrap_or(false),
+        preserve_mode: consumer_declaration.preserve_mode.unwrap_or(false),
+        trace,
+        path_style: consumer_declaration.path_style.unwrap_or_default(),
+        skip_unreadable: consumer_declaration.skip_unreadable.unwrap_or(false),
+        directory_mode: consumer_declaration.directory_mode,
+        file_mode: consumer_declaration.file_mode,
+        hash_algorithm,
+        line_endings: consumer_declaration.line_endings,
+        hashed_filenames: consumer_declaration.hashed_filenames.unwrap_or(false),
+        max_total_bytes: consumer_declaration.max_total_bytes,
+        max_file_bytes: consumer_declaration.max_file_bytes,
+    })
 }
 
-/// Copy the resource to the resources folder (if it doesn't already exist)
-fn copy_resource(
+/// Plan the collation of a required resource, checking the source against any required sha.
+///
+/// The expected sha is that of the resource as collated, i.e. after any normalizing of its line
+/// endings (the required sha being that of the source).
+///
+/// When required shas aren't verified a mismatch is only reported, and the resource planned with
+/// its actual sha.
+///
+/// Without a required sha to check (or line endings to normalize), a source with the size and
+/// modified time the previous collation recorded for it is planned with its recorded sha, without
+/// being read.
+fn plan_resource(
     res_req: &ResourceRequirement,
     res_dec: &ResourceSpecification,
-    resource_root: &Utf8PathBuf,
-) -> Result<(), String> {
+    recorded: Option<&ResolvedResource>,
+    consumer_spec: &ResourceConsumerSpecification,
+    hash_buffer_size: usize,
+    verify_shas: bool,
+    reporter: &dyn ReportingTrait,
+) -> Result<PlannedResource, CollationError> {
+    // When the source was modified is read before hashing it, so an edit while hashing isn't missed
+    let source_metadata = fs::metadata(&res_dec.full_crate_path).ok();
+    let source_modified = source_metadata.as_ref().and_then(modified_nanos);
+    let specification = required_specification(res_req, res_dec);
+    let recorded_sha = recorded
+        .filter(|recorded| res_req.required_sha.is_none()
+            && line_endings_for(&specification, consumer_spec).is_none()
+            && recorded.hash_algorithm == consumer_spec.hash_algorithm
+            && source_modified.is_some()
+            && recorded.source_modified == source_modified
+            && source_metadata.as_ref().is_some_and(|metadata| metadata.len() == recorded.size))
+        .map(|recorded| recorded.output_sha.to_owned());
+    if let Some(recorded_sha) = recorded_sha {
+        let specification = match consumer_spec.hashed_filenames {
+            true => hash_file_name(specification, &recorded_sha),
+            false => specification,
+        };
+        return Ok(PlannedResource {
+            specification,
+            source_sha: recorded_sha.to_owned(),
+            expected_sha: recorded_sha,
+            source_modified,
+        });
+    }
+
+    let source_sha = get_file_sha(&res_dec.full_crate_path, consumer_spec.hash_algorithm, hash_buffer_size)
+        .map_err(|e| match e {
+            CollationError::Io { context, source } => CollationError::Io {
+                context: format!(
+                    "{} (resource {} declared by {} {})",
+                    context,
+                    &res_dec.resource_name,
+                    &res_dec.declaring_crate_name,
+                    &res_dec.declaring_crate_version
+                ),
+                source,
+            },
+            e => e,
+        })?;
+
+    // Return error if the required sha is set and doesn't match.
+    if let Some((expected, actual)) = required_sha_mismatch(res_req, res_dec, &source_sha, consumer_spec, hash_buffer_size)? {
+        match verify_shas {
+            true => Err(
+                CollationError::ShaMismatch {
+                    resource: res_req.resource_name.to_owned(),
+                    expected,
+                    actual,
+                }
+            )?,
+            false => reporter.report_unverified_sha(&res_req.resource_name, &expected, &actual),
+        }
+    }
+
+    let expected_sha = match line_endings_for(&specification, consumer_spec) {
+        Some(_) => get_resource_sha(&specification, consumer_spec, hash_buffer_size)?,
+        None => source_sha.to_owned(),
+    };
+    let specification = match consumer_spec.hashed_filenames {
+        true => hash_file_name(specification, &expected_sha),
+        false => specification,
+    };
+    Ok(PlannedResource { specification, expected_sha, source_sha, source_modified })
+}
+
+/// Compare the resource's source against any required shas, giving the expected and actual shas when
+/// it matches none of them (the actual prefixed by its algorithm when a required sha is).
+///
+/// The source's sha in the consumer's hash_algorithm is given, so the source is only hashed again
+/// when the required sha names another algorithm.
+fn required_sha_mismatch(
+    res_req: &ResourceRequirement,
+    res_dec: &ResourceSpecification,
+    source_sha: &ResourceSha,
+    consumer_spec: &ResourceConsumerSpecification,
+    hash_buffer_size: usize,
+) -> Result<Option<(RequiredSha, ResourceSha)>, CollationError> {
+    let Some((hash_algorithm, digests)) = res_req.required_digests(consumer_spec.hash_algorithm)? else {
+        return Ok(None);
+    };
+    let actual = match hash_algorithm == consumer_spec.hash_algorithm {
+        true => source_sha.to_owned(),
+        false => get_file_sha(&res_dec.full_crate_path, hash_algorithm, hash_buffer_size)?,
+    };
+    if digests.contains(&actual.as_str()) {
+        return Ok(None);
+    }
+    let expected = res_req.required_sha.to_owned().expect("No required sha!");
+    let actual = match expected.shas().iter().any(|sha| sha.contains(':')) {
+        true => format!("{}:{}", hash_algorithm.name(), actual),
+        false => actual,
+    };
+    Ok(Some((expected, actual)))
+}
+
+/// The number of hex digits of a resource's sha put in its file name, when file names are hashed
+const HASHED_FILENAME_DIGITS: usize = 8;
+
+/// Put the start of the resource's sha in its output path's file name, before any extension (e.g.
+/// css/app.3f2a1b9c.css), keeping the output path it replaces
+fn hash_file_name(res_dec: ResourceSpecification, sha: &ResourceSha) -> ResourceSpecification {
+    let output_path = &res_dec.output_path;
+    let digits = &sha[..sha.len().min(HASHED_FILENAME_DIGITS)];
+    let file_name = match (output_path.file_stem(), output_path.extension()) {
+        (Some(stem), Some(extension)) => format!("{}.{}.{}", stem, digits, extension),
+        _ => format!("{}.{}", output_path.file_name().expect("Illegal output path"), digits),
+    };
+    ResourceSpecification {
+        output_path: output_path.with_file_name(file_name),
+        unhashed_output_path: Some(output_path.to_owned()),
+        ..res_dec
+    }
+}
+
+/// The line endings a resource is given when copied, if any: those asked for by the consumer,
+/// for a text resource (binary resources are never changed)
+fn line_endings_for(res_dec: &ResourceSpecification, consumer_spec: &ResourceConsumerSpecification) -> Option<LineEndings> {
+    consumer_spec.line_endings.filter(|_| matches!(res_dec.encoding, ResourceEncoding::Txt))
+}
+
+/// Read a text file's content with its line endings normalized
+fn read_normalized(path: &Utf8Path, line_endings: LineEndings) -> Result<Vec<u8>, CollationError> {
+    let content = fs::read(path).map_err(|e| CollationError::io(format!("Error reading {}", path), e))?;
+    Ok(line_endings.normalize(&content))
+}
+
+/// Read a planned resource's source whole, in the form it is collated (i.e. with any normalized
+/// line endings), checking it still has the planned sha
+fn read_resource(
+    planned_resource: &PlannedResource,
+    consumer_spec: &ResourceConsumerSpecification,
+) -> Result<Vec<u8>, CollationError> {
+    let res_dec = &planned_resource.specification;
+    let content = match line_endings_for(res_dec, consumer_spec) {
+        Some(line_endings) => read_normalized(&res_dec.full_crate_path, line_endings)?,
+        None => fs::read(&res_dec.full_crate_path)
+            .map_err(|e| CollationError::io(format!("Error reading {}", &res_dec.full_crate_path), e))?,
+    };
+
+    // Return error if the source has changed since the collation was planned.
+    let mut sha = FileHasher::new(consumer_spec.hash_algorithm);
+    sha.update(&content);
+    let sha = sha.finish();
+    if sha != planned_resource.expected_sha {
+        Err(
+            CollationError::SourceChanged {
+                resource: res_dec.resource_name.to_owned(),
+                expected: planned_resource.expected_sha.to_owned(),
+                actual: sha,
+            }
+        )?
+    }
+    Ok(content)
+}
+
+/// Get the hash of a resource's source in the form it is collated, i.e. with any normalized line
+/// endings
+fn get_resource_sha(
+    res_dec: &ResourceSpecification,
+    consumer_spec: &ResourceConsumerSpecification,
+    hash_buffer_size: usize,
+) -> Result<ResourceSha, CollationError> {
+    match line_endings_for(res_dec, consumer_spec) {
+        Some(line_endings) => {
+            let mut sha = FileHasher::new(consumer_spec.hash_algorithm);
+            sha.update(&read_normalized(&res_dec.full_crate_path, line_endings)?);
+            Ok(sha.finish())
+        }
+        None => get_file_sha(&res_dec.full_crate_path, consumer_spec.hash_algorithm, hash_buffer_size),
+    }
+}
+
+/// Get the specification of a required resource as this consumer collates it, with the encoding
+/// and output path (including any new file name) it requires
+fn required_specification(res_req: &ResourceRequirement, res_dec: &ResourceSpecification) -> ResourceSpecification {
+    let output_path = res_req.output_path_override.to_owned().unwrap_or(res_dec.output_path.to_owned());
+    ResourceSpecification {
+        encoding: required_encoding(res_req, res_dec),
+        output_path: match &res_req.rename_to {
+            Some(file_name) => output_path.with_file_name(file_name),
+            None => output_path,
+        },
+        renamed_to: res_req.rename_to.to_owned(),
+        group: res_req.group.to_owned().or(res_dec.group.to_owned()),
+        ..res_dec.clone()
+    }
+}
+
+/// Get the encoding of a required resource: the consumer's encoding, when given, takes precedence
+/// over the provider's for this collation (and is detected from the file, when Auto)
+fn required_encoding(res_req: &ResourceRequirement, res_dec: &ResourceSpecification) -> ResourceEncoding {
+    res_req.encoding.clone().unwrap_or(res_dec.encoding.clone()).resolve(&res_dec.full_crate_path)
+}
+
+/// What copying a resource did, with the size of the resource in bytes
+enum CopyOutcome {
+    Copied(u64),
+    AlreadyExisted(u64),
+}
+
+/// Copy (or link) the resource to the resources folder (if it doesn't already exist)
+fn copy_resource(
+    planned_resource: &PlannedResource,
+    recorded: Option<&ResolvedResource>,
+    consumer_spec: &ResourceConsumerSpecification,
+    run_options: &RunOptions,
+    reporter: &dyn ReportingTrait,
+) -> Result<CopyOutcome, CollationError> {
+    let copy_mode = run_options.copy_mode;
+    let res_dec = &planned_resource.specification;
+    let resource_root = consumer_spec.root_for(res_dec);
     let output_resources_path = resource_root
         .join(&res_dec.output_path);
     // Before copying, we should check the path isn't outside the resources root.
-    verify_resource_is_in_root(&output_resources_path, &resource_root)?;
+    verify_resource_is_in_root(&output_resources_path, resource_root)?;
 
     // Create the output directory if it doesn't exist!
     let output_directory = output_resources_path.parent().unwrap();
-    create_output_directory(output_directory)?;
+    create_output_directory(output_directory, consumer_spec.directory_mode)?;
 
-    // Use sha256 to check if the file has changed, and verify against a required_sha
-    let new_sha = hex::encode(get_file_sha(&res_dec.full_crate_path)?.as_ref());
+    // Only copy when the sha doesn't match (to avoid timestamp updates on the file), or the existing
+    // file isn't placed as the copy mode asks (e.g. a link, when copying)
+    let is_placed = output_resources_path.exists()
+        && is_placed_as(&res_dec.full_crate_path, &output_resources_path, copy_mode);
 
-    // Return error if the required sha is set and doesn't match.
-    match res_req.required_sha {
-        Some(ref req) => {
-            if *req != new_sha {
+    // Without a required sha to check, an existing file unchanged since the previous collation
+    // recorded it with the planned sha is taken to be up to date without hashing it again
+    let has_required_sha = consumer_spec.required_resources.iter()
+        .any(|res_req| res_req.resource_name == res_dec.resource_name && res_req.required_sha.is_some());
+    let mut already_exists = !run_options.always_hash
+        && is_placed
+        && !has_required_sha
+        && is_unchanged_since_recorded(planned_resource, recorded, consumer_spec, &output_resources_path);
+
+    let new_sha = match already_exists {
+        true => planned_resource.expected_sha.to_owned(),
+        false => {
+            // Use the hash to check if the file has changed, and verify against the planned sha
+            let new_sha = get_resource_sha(res_dec, consumer_spec, run_options.hash_buffer_size)?;
+
+            // Return error if the source has changed since the collation was planned.
+            if planned_resource.expected_sha != new_sha {
                 Err(
-                    format!("Resource {} with sha {} does not match required sha {}.",
-                            res_req.resource_name,
-                            new_sha,
-                            req
-                    )
+                    CollationError::SourceChanged {
+                        resource: res_dec.resource_name.to_owned(),
+                        expected: planned_resource.expected_sha.to_owned(),
+                        actual: new_sha.to_owned(),
+                    }
                 )?
             }
+
+            if is_placed {
+                let existing_sha = get_file_sha(&output_resources_path, consumer_spec.hash_algorithm, run_options.hash_buffer_size)?;
+                if existing_sha == new_sha {
+                    already_exists = true;
+                }
+            }
+            new_sha
         }
-        _ => {}
-    }
+    };
+
+    let mode = output_mode(&res_dec.full_crate_path, res_dec, consumer_spec)?;
+    // A link is the source itself, so a resource whose line endings are normalized is always copied
+    let line_endings = line_endings_for(res_dec, consumer_spec);
+    let mut linked = false;
+    let outcome = match already_exists {
+        true => CopyOutcome::AlreadyExisted(
+            fs::metadata(&output_resources_path)
+                .map_err(|e| CollationError::io(format!("Unable to read the size of {}", &output_resources_path), e))?
+                .len()
+        ),
+        false => {
+            if copy_mode != CopyMode::Copy && line_endings.is_none() {
+                // A link can't be made over, or written through, any existing file
+                if output_resources_path.symlink_metadata().is_ok() {
+                    fs::remove_file(&output_resources_path)
+                        .map_err(|e| CollationError::io(format!("Unable to replace {}", &output_resources_path), e))?;
+                }
+                linked = link_resource(&res_dec.full_crate_path, &output_resources_path, copy_mode, reporter);
+            }
+            match linked {
+                true => CopyOutcome::Copied(
+                    fs::metadata(&output_resources_path)
+                        .map_err(|e| CollationError::io(format!("Unable to read the size of {}", &output_resources_path), e))?
+                        .len()
+                ),
+                false => CopyOutcome::Copied(
+                    copy_atomically(&res_dec.full_crate_path, &output_resources_path, consumer_spec, mode, line_endings)?
+                ),
+            }
+        }
+    };
 
-    // Only copy when the sha doesn't match (to avoid timestamp updates on the file)
-    let mut already_exists = false;
-    if output_resources_path.exists() {
-        let existing_sha = hex::encode(get_file_sha(&output_resources_path)?.as_ref());
-        if existing_sha == new_sha {
-            already_exists = true;
+    // An up to date copy may still need its mode correcting (a link's mode is its source's)
+    if let Some(mode) = mode.filter(|_| already_exists) {
+        if placed_mode(&res_dec.full_crate_path, &output_resources_path).is_some_and(|placed| placed != mode) {
+            set_mode(&output_resources_path, mode)?;
         }
     }
 
-    if !already_exists {
-        fs::copy(&res_dec.full_crate_path, &output_resources_path)
-            .map_err(|e|
-                format!("Unable to copy resource {} to {}: {}",
-                        &res_dec.full_crate_path,
-                        &output_resources_path,
-                        e
-                )
-            )?;
+    // Check the copy against the source, as storage can silently corrupt it (a link is the source)
+    let verify = run_options.post_verify && !already_exists && !linked;
+    if verify {
+        let copied_sha = get_file_sha(&output_resources_path, consumer_spec.hash_algorithm, run_options.hash_buffer_size)?;
+        if copied_sha != new_sha {
+            // Remove the corrupt copy, which could otherwise pass for up to date by its size and time
+            let _ = fs::remove_file(&output_resources_path);
+            Err(
+                CollationError::CopyCorrupted {
+                    resource: res_dec.resource_name.to_owned(),
+                    output_path: output_resources_path.to_owned(),
+                    expected: new_sha.to_owned(),
+                    actual: copied_sha,
+                }
+            )?
+        }
     }
 
-    println!(
-        "Resource {} {:50} {}",
-        match already_exists {
-            true => "existed:",
-            false => " copied:"
-        }.to_string(),
-        &output_resources_path,
-        &new_sha,
+    trace_resource(
+        reporter,
+        &consumer_spec.trace,
+        &res_dec.resource_name,
+        format!(
+            "{} {} from {} {}",
+            match (already_exists, linked) {
+                (true, _) => "already up to date at",
+                (false, true) => "linked to",
+                (false, false) => "copied to",
+            },
+            &output_resources_path,
+            &res_dec.declaring_crate_name,
+            &res_dec.declaring_crate_version
+        ),
     );
-    Ok(())
+
+    reporter.report_resource_collection(res_dec, already_exists, &output_resources_path, &new_sha);
+    if verify {
+        reporter.report_resource_verified(&output_resources_path, &new_sha);
+    }
+    Ok(outcome)
 }
 
-/// Work out the SHA 256 value of a file from the path
-fn get_file_sha(path: &Utf8PathBuf) -> Result<Digest, String> {
-    let mut sha = Context::new(&SHA256);
-    let mut file = File::open(path).map_err(|e| format!("Error opening {}, {}", path, e))?;
-    let mut buffer = [0; 4096]; // Read sensible sized blocks from disk!
+/// Copy a resource's source to its output path by way of a temporary file in the same directory,
+/// renamed into place once complete (replacing any existing file, or link, in one step), so the
+/// output is never seen part written. Gives the number of bytes copied.
+///
+/// The content is written with the given line endings, when normalizing a text resource, and the
+/// consumer's modified time and the given mode are applied to the temporary file, before the
+/// rename.
+fn copy_atomically(
+    source: &Utf8PathBuf,
+    destination: &Utf8PathBuf,
+    consumer_spec: &ResourceConsumerSpecification,
+    mode: Option<u32>,
+    line_endings: Option<LineEndings>,
+) -> Result<u64, CollationError> {
+    let temporary = temporary_path(destination, "tmp");
 
-    loop {
-        let bytes_read = file.read(&mut buffer)
-            .map_err(|e| format!("Error calculating SHA256 {}", e))?;
-        if bytes_read == 0 {
-            break;
-        }
-        sha.update(&buffer[..bytes_read]);
+    let copied = match line_endings {
+        Some(line_endings) => read_normalized(source, line_endings).and_then(|content| {
+            fs::write(&temporary, &content)
+                .map(|_| content.len() as u64)
+                .map_err(|e| CollationError::io(format!("Unable to copy resource {} to {}", source, destination), e))
+        }),
+        None => fs::copy(source, &temporary)
+            .map_err(|e| CollationError::io(format!("Unable to copy resource {} to {}", source, destination), e)),
+    };
+    let result = copied
+        .and_then(|size| {
+            if consumer_spec.preserve_mtime {
+                copy_modified_time(source, &temporary)?;
+            }
+            if let Some(mode) = mode {
+                set_mode(&temporary, mode)?;
+            }
+            fs::rename(&temporary, destination)
+                .map_err(|e| CollationError::io(format!("Unable to move {} into place at {}", &temporary, destination), e))?;
+            Ok(size)
+        });
+    if result.is_err() {
+        // Don't leave a part written copy behind (it may not exist, so any failure is ignored)
+        let _ = fs::remove_file(&temporary);
     }
+    result
+}
 
-    Ok(sha.finish())
+/// The number of the next temporary file named by [temporary_path]
+static TEMPORARY_FILE_NUMBER: AtomicUsize = AtomicUsize::new(0);
+
+/// Name a temporary file beside the given path, with the given extension, hidden and unique to
+/// this process and this file (e.g. .logo.png.1234.5.tmp), so that even files written in parallel
+/// never share one
+pub(crate) fn temporary_path(path: &Utf8Path, extension: &str) -> Utf8PathBuf {
+    let number = TEMPORARY_FILE_NUMBER.fetch_add(1, Ordering::Relaxed);
+    let file_name = path.file_name().expect("No file name!");
+    path.with_file_name(format!(".{}.{}.{}.{}", file_name, std::process::id(), number, extension))
 }
 
-// Check whether the resource is in the root!
-fn verify_resource_is_in_root(
-    resource_path: &Utf8PathBuf,
-    root_path: &Utf8PathBuf,
-) -> Result<(), String> {
-    let can_root_path = root_path.canonicalize_utf8()
-        .map_err(
-            |e| format!(
-                "Unable to canonicalize root path: {}: {}",
-                root_path,
-                e
-            )
-        )?;
+/// Link the resource's output path to its source, as the copy mode asks, reporting a warning and
+/// giving false (so the resource is copied instead) when the link can't be made
+fn link_resource(
+    source: &Utf8PathBuf,
+    destination: &Utf8PathBuf,
+    copy_mode: CopyMode,
+    reporter: &dyn ReportingTrait,
+) -> bool {
+    let result = match copy_mode {
+        CopyMode::Symlink => symlink_file(source, destination),
+        _ => fs::hard_link(source, destination),
+    };
+    if let Err(e) = result {
+        reporter.report_warning(&format!("Unable to link {} to {} ({}), copying it instead", destination, source, e));
+        return false;
+    }
+    true
+}
+
+/// The resources in the previous record whose recorded shas can be taken to be their sources' while
+/// the sources keep their recorded size and modified time, by name and source path.
+///
+/// A source recorded as modified no earlier than the record was written may have been changed
+/// again within the same tick of the file system's clock, so is left out (to be hashed).
+fn recorded_sources(record_file: &Utf8Path) -> HashMap<(ResourceName, Utf8PathBuf), ResolvedResource> {
+    let record_modified = fs::metadata(record_file).ok().as_ref().and_then(modified_nanos);
+    let (Some(record_modified), Ok(recorded)) = (record_modified, read_record(record_file)) else {
+        return HashMap::new();
+    };
+    recorded.into_iter()
+        .filter(|resource| resource.source_modified.is_some_and(|modified| modified < record_modified))
+        .map(|resource| {
+            let specification = &resource.specification;
+            ((specification.resource_name.to_owned(), specification.full_crate_path.to_owned()), resource)
+        })
+        .collect()
+}
+
+/// The resources in the previous record, by their output paths joined to their roots, or none when
+/// there is no record (or it can't be read, in which case every existing resource is hashed)
+fn recorded_resources(
+    record_file: &Utf8Path,
+    consumer_spec: &ResourceConsumerSpecification,
+    crate_root: &Utf8Path,
+) -> HashMap<Utf8PathBuf, ResolvedResource> {
+    let Ok(recorded) = read_record(record_file) else {
+        return HashMap::new();
+    };
+    recorded.into_iter()
+        .filter_map(|resource| {
+            let resource_root = consumer_spec.root_for(&resource.specification);
+            let output_path =
+                unstyled_output_path(&resource.specification.output_path, resource_root, consumer_spec, crate_root).ok()?;
+            Some((resource_root.join(output_path), resource))
+        })
+        .collect()
+}
+
+/// Whether an existing output file is as the previous collation recorded it, with the planned sha:
+/// the same size, and modified at exactly the recorded time. A source edited (or restored) since
+/// has another sha, whatever its modified time, so is copied again.
+fn is_unchanged_since_recorded(
+    planned_resource: &PlannedResource,
+    recorded: Option<&ResolvedResource>,
+    consumer_spec: &ResourceConsumerSpecification,
+    destination: &Utf8Path,
+) -> bool {
+    let (Some(recorded), Ok(destination)) = (recorded, fs::metadata(destination)) else {
+        return false;
+    };
+    recorded.output_sha == planned_resource.expected_sha
+        && recorded.hash_algorithm == consumer_spec.hash_algorithm
+        && recorded.size == destination.len()
+        && recorded.modified.is_some_and(|modified| modified_nanos(&destination) == Some(modified))
+}
+
+/// When a file was last modified, in nanoseconds since the unix epoch
+fn modified_nanos(metadata: &fs::Metadata) -> Option<u64> {
+    let modified = metadata.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    modified.as_nanos().try_into().ok()
+}
+
+/// Whether an existing output file is placed as the copy mode asks: a symbolic link, a hard link to
+/// the source, or a file of its own
+fn is_placed_as(source: &Utf8Path, destination: &Utf8Path, copy_mode: CopyMode) -> bool {
+    let is_symlink = destination.symlink_metadata()
+        .is_ok_and(|metadata| metadata.file_type().is_symlink());
+    match copy_mode {
+        CopyMode::Symlink => is_symlink,
+        CopyMode::Hardlink => !is_symlink && is_same_file(source, destination) != Some(false),
+        CopyMode::Copy => !is_symlink && is_same_file(source, destination) != Some(true),
+    }
+}
+
+/// Whether two paths are the same file (e.g. hard links to it), when that can be told.
+#[cfg(unix)]
+fn is_same_file(a: &Utf8Path, b: &Utf8Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let (a, b) = (fs::metadata(a).ok()?, fs::metadata(b).ok()?);
+    Some(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+/// Files can't be identified elsewhere, so whether two paths are the same file can't be told.
+#[cfg(not(unix))]
+fn is_same_file(_a: &Utf8Path, _b: &Utf8Path) -> Option<bool> {
+    None
+}
+
+/// Symbolically link the destination to the source file.
+#[cfg(unix)]
+fn symlink_file(source: &Utf8Path, destination: &Utf8Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(source, destination)
+}
+
+/// Symbolically link the destination to the source file (which may need extra privileges).
+#[cfg(windows)]
+fn symlink_file(source: &Utf8Path, destination: &Utf8Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(source, destination)
+}
 
-    // Create interim folders to allow parentage check
-    if !resource_path.parent().is_some() {
+/// There is no way to symbolically link elsewhere, so the resource is copied instead.
+#[cfg(not(any(unix, windows)))]
+fn symlink_file(_source: &Utf8Path, _destination: &Utf8Path) -> io::Result<()> {
+    Err(io::Error::new(ErrorKind::Unsupported, "symbolic links are not supported on this platform"))
+}
+
+/// Work out whether copying the resource would copy it or skip it as already up to date, without
+/// touching the disk
+fn check_resource(
+    planned_resource: &PlannedResource,
+    consumer_spec: &ResourceConsumerSpecification,
+    run_options: &RunOptions,
+    reporter: &dyn ReportingTrait,
+) -> Result<CopyOutcome, CollationError> {
+    let res_dec = &planned_resource.specification;
+    let resource_root = consumer_spec.root_for(res_dec);
+    let output_resources_path = resource_root.join(&res_dec.output_path);
+    // As when copying, the path mustn't be outside the resources root (which is checked without
+    // touching the disk)
+    verify_resource_is_in_root(&output_resources_path, resource_root)?;
+    let size = fs::metadata(&res_dec.full_crate_path)
+        .map_err(|e| CollationError::io(format!("Unable to read the size of {}", &res_dec.full_crate_path), e))?
+        .len();
+
+    let already_exists = output_resources_path.is_file()
+        && get_file_sha(&output_resources_path, consumer_spec.hash_algorithm, run_options.hash_buffer_size)?
+            == planned_resource.expected_sha;
+
+    trace_resource(
+        reporter,
+        &consumer_spec.trace,
+        &res_dec.resource_name,
+        format!(
+            "{} {} from {} {}",
+            match already_exists {
+                true => "would be left up to date at",
+                false => "would be copied to"
+            },
+            &output_resources_path,
+            &res_dec.declaring_crate_name,
+            &res_dec.declaring_crate_version
+        ),
+    );
+
+    reporter.report_dry_run_collection(res_dec, already_exists, &output_resources_path, &planned_resource.expected_sha);
+    Ok(match already_exists {
+        true => CopyOutcome::AlreadyExisted(size),
+        false => CopyOutcome::Copied(size),
+    })
+}
+
+/// Stage a checksum file beside a collated resource (e.g. logo.png.sha256 for SHA 256) in its root,
+/// holding its hash and file name as sha256sum does, unless the file is already up to date. For a
+/// dry run, the file that would be written is only reported.
+fn stage_checksum_file(
+    planned_resource: &PlannedResource,
+    consumer_spec: &ResourceConsumerSpecification,
+    dry_run: bool,
+    staged: &mut StagedFiles,
+    reporter: &dyn ReportingTrait,
+) -> Result<(), CollationError> {
+    let res_dec = &planned_resource.specification;
+    let resource_root = consumer_spec.root_for(res_dec);
+    let output_path = resource_root.join(&res_dec.output_path);
+    let checksum_path = Utf8PathBuf::from(
+        format!("{}.{}", output_path, consumer_spec.hash_algorithm.checksum_extension())
+    );
+    let contents = format!("{}  {}\n", planned_resource.expected_sha, output_path.file_name().expect("No output file name!"));
+    if fs::read_to_string(&checksum_path).is_ok_and(|existing| existing == contents) {
         return Ok(());
     }
-    let mut walked_directory = Utf8PathBuf::new();
-    let target_components = resource_path.parent().unwrap().components();
-    for component in target_components {
+    if !dry_run {
+        verify_resource_is_in_root(&checksum_path, resource_root)?;
+        staged.stage(&checksum_path, contents)?;
+    }
+    reporter.report_checksum_file(&checksum_path, dry_run);
+    Ok(())
+}
+
+/// Express a resource's output path (relative to its root) in the consumer's path style
+fn styled_output_path(
+    output_path: &Utf8Path,
+    resource_root: &Utf8Path,
+    consumer_spec: &ResourceConsumerSpecification,
+    crate_root: &Utf8Path,
+) -> Result<Utf8PathBuf, CollationError> {
+    if let PathStyle::RelativeToRoot = consumer_spec.path_style {
+        return Ok(output_path.to_owned());
+    }
 
-        walked_directory = walked_directory.join(component);
-        create_output_directory(&mut walked_directory)?;
+    let absolute_path = resource_root.canonicalize_utf8()
+        .map_err(|e| CollationError::io(format!("Unable to canonicalize root path: {}", resource_root), e))?
+        .join(output_path);
+    if let PathStyle::Absolute = consumer_spec.path_style {
+        return Ok(absolute_path);
     }
-    let can_resource_path = resource_path.parent().unwrap().canonicalize_utf8()
-        .map_err(
-            |e| format!(
-                "Unable to canonicalize resource path: {}: {}",
-                resource_path,
-                e
-            )
-        )?;
 
-    if !can_resource_path.starts_with(&can_root_path) {
+    let can_crate_root = crate_root.canonicalize_utf8()
+        .map_err(|e| CollationError::io(format!("Unable to canonicalize crate root: {}", crate_root), e))?;
+    absolute_path.strip_prefix(&can_crate_root)
+        .map(|path| path.to_owned())
+        .map_err(|_e| CollationError::NotWithinRoot { path: absolute_path.to_owned(), root: can_crate_root.to_owned() })
+}
+
+/// Get a recorded output path (in the consumer's path style) relative to its root again
+fn unstyled_output_path(
+    recorded_path: &Utf8Path,
+    resource_root: &Utf8Path,
+    consumer_spec: &ResourceConsumerSpecification,
+    crate_root: &Utf8Path,
+) -> Result<Utf8PathBuf, CollationError> {
+    let absolute_path = match consumer_spec.path_style {
+        PathStyle::RelativeToRoot => return Ok(recorded_path.to_owned()),
+        PathStyle::Absolute => recorded_path.to_owned(),
+        PathStyle::RelativeToCrate => crate_root.canonicalize_utf8()
+            .map_err(|e| CollationError::io(format!("Unable to canonicalize crate root: {}", crate_root), e))?
+            .join(recorded_path),
+    };
+
+    let can_root_path = resource_root.canonicalize_utf8()
+        .map_err(|e| CollationError::io(format!("Unable to canonicalize root path: {}", resource_root), e))?;
+    absolute_path.strip_prefix(&can_root_path)
+        .map(|path| path.to_owned())
+        .map_err(|_e| CollationError::NotWithinRoot { path: absolute_path.to_owned(), root: can_root_path.to_owned() })
+}
+
+/// Report a step in the resolution of a resource, when that resource is being traced
+fn trace_resource(
+    reporter: &dyn ReportingTrait,
+    trace: &[ResourceName],
+    resource_name: &str,
+    step: impl std::fmt::Display,
+) {
+    if trace.iter().any(|name| name == resource_name) {
+        reporter.report_trace(resource_name, &step.to_string());
+    }
+}
+
+/// Set the modified time of the copied resource to match its source
+fn copy_modified_time(source: &Utf8PathBuf, destination: &Utf8PathBuf) -> Result<(), CollationError> {
+    let modified = fs::metadata(source)
+        .and_then(|m| m.modified())
+        .map_err(|e| CollationError::io(format!("Unable to read modified time of {}", source), e))?;
+    File::options().write(true).open(destination)
+        .and_then(|f| f.set_modified(modified))
+        .map_err(|e| CollationError::io(format!("Unable to set modified time of {}", destination), e))
+}
+
+/// Whether the file can be opened for reading (i.e. isn't denied by its permissions)
+fn is_readable(path: &Utf8PathBuf) -> bool {
+    !matches!(File::open(path), Err(e) if e.kind() == ErrorKind::PermissionDenied)
+}
+
+/// Check the resource's path is in the resource root, without touching the disk (so no
+/// directories are created, even for a path that is rejected).
+///
+/// Both paths are resolved with [resolve_path], so links already in the resource root are followed
+/// and `.` and `..` components resolved; the resource itself (which may be a link to its source)
+/// isn't followed.
+fn verify_resource_is_in_root(resource_path: &Utf8Path, root_path: &Utf8Path) -> Result<(), CollationError> {
+    let resolved_root = resolve_path(root_path)?;
+    let resolved_path = match (resource_path.parent(), resource_path.file_name()) {
+        (Some(parent), Some(file_name)) => resolve_path(parent)?.join(file_name),
+        _ => resolve_path(resource_path)?, // Ends in .. (or is a root), so can't be a file in the root
+    };
+
+    if !resolved_path.parent().is_some_and(|parent| parent.starts_with(&resolved_root)) {
         Err(
-            format!(
-                "Can't copy to {:?} as not in resource root {:?}",
-                can_resource_path,
-                can_root_path
-            )
+            CollationError::ResourceOutsideRoot {
+                path: resolved_path,
+                root: Some(resolved_root),
+                crate_name: None,
+            }
         )?
     }
     Ok(())
 }
 
-/// Create the output directory if it doesn't exist.
-fn create_output_directory(output_dir: &Utf8Path) -> Result<(), String> {
+/// Resolve a path (relative to the current directory) as the file system would, without changing
+/// anything: each existing directory along it is canonicalized, following links, and the
+/// components beyond those that exist applied logically, resolving `.` and `..`.
+fn resolve_path(path: &Utf8Path) -> Result<Utf8PathBuf, CollationError> {
+    let mut resolved = match path.is_absolute() {
+        true => Utf8PathBuf::new(),
+        false => std::env::current_dir()
+            .map_err(|e| CollationError::io(format!("Unable to resolve {} from the current directory", path), e))
+            .and_then(|dir| Utf8PathBuf::from_path_buf(dir).map_err(CollationError::NonUtf8Path))
+            .map(strip_verbatim_prefix)?,
+    };
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                resolved.pop();
+            }
+            Utf8Component::Normal(_) => {
+                resolved.push(component);
+                if let Ok(canonical) = resolved.canonicalize_utf8() {
+                    resolved = strip_verbatim_prefix(canonical);
+                }
+            }
+            _ => resolved.push(component), // The prefix and root of an absolute path
+        }
+    }
+    Ok(resolved)
+}
+
+/// Remove the extended-length prefix (`\\?\`, or `\\?\UNC\` for a network share) that Windows gives
+/// canonical paths, so that paths compare the same however they were resolved
+fn strip_verbatim_prefix(path: Utf8PathBuf) -> Utf8PathBuf {
+    match path.as_str() {
+        verbatim if verbatim.starts_with(r"\\?\UNC\") => Utf8PathBuf::from(format!(r"\\{}", &verbatim[8..])),
+        verbatim if verbatim.starts_with(r"\\?\") => Utf8PathBuf::from(&verbatim[4..]),
+        _ => path,
+    }
+}
+
+/// Create the output directory if it doesn't exist, setting the mode of each directory created.
+fn create_output_directory(output_dir: &Utf8Path, mode: Option<u32>) -> Result<(), CollationError> {
     if !output_dir.exists() {
-        fs::create_dir_all(&output_dir)
+        let created_dirs: Vec<&Utf8Path> = output_dir.ancestors()
+            .take_while(|dir| !dir.as_str().is_empty() && !dir.exists())
+            .collect();
+        fs::create_dir_all(output_dir)
             .map_err(|e|
-                format!("Unable to create output directory {}: {}", &output_dir, e)
-            )?
+                CollationError::io(format!("Unable to create output directory {}", &output_dir), e)
+            )?;
+        if let Some(mode) = mode {
+            for dir in created_dirs {
+                set_mode(dir, mode)?;
+            }
+        }
     }
     Ok(())
+}
+
+/// Set the permissions of a file or directory to the given mode (e.g. 0o644), ignoring the umask.
+#[cfg(unix)]
+fn set_mode(path: &Utf8Path, mode: u32) -> Result<(), CollationError> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .map_err(|e| CollationError::io(format!("Unable to set the mode of {}", path), e))
+}
+
+/// Modes are a unix concept, so there is nothing to set elsewhere.
+#[cfg(not(unix))]
+fn set_mode(_path: &Utf8Path, _mode: u32) -> Result<(), CollationError> {
+    Ok(())
+}
+
+/// Get the mode (e.g. 0o644) of a file, when it can be read.
+#[cfg(unix)]
+fn get_mode(path: &Utf8Path) -> io::Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).map(|metadata| metadata.permissions().mode() & 0o7777)
+}
+
+/// Modes are a unix concept, so there is none to get elsewhere.
+#[cfg(not(unix))]
+fn get_mode(_path: &Utf8Path) -> io::Result<u32> {
+    Err(io::Error::new(ErrorKind::Unsupported, "modes are not supported on this platform"))
+}
+
+/// The mode collation gives a copied resource, if any: the consumer's file_mode, or its source's
+/// mode when the consumer preserves modes or the resource is executable, with the execute bits
+/// added (wherever it can be read) for an executable resource.
+fn output_mode(
+    source: &Utf8Path,
+    res_dec: &ResourceSpecification,
+    consumer_spec: &ResourceConsumerSpecification,
+) -> Result<Option<u32>, CollationError> {
+    if !cfg!(unix) {
+        return Ok(None);
+    }
+    let mode = match consumer_spec.file_mode {
+        Some(mode) => Some(mode),
+        None if consumer_spec.preserve_mode || res_dec.executable => Some(
+            get_mode(source).map_err(|e| CollationError::io(format!("Unable to read the mode of {}", source), e))?
+        ),
+        None => None,
+    };
+    Ok(mode.map(|mode| match res_dec.executable {
+        true => mode | ((mode & 0o444) >> 2),
+        false => mode,
+    }))
+}
+
+/// The mode of a resource placed as a copy (rather than a link to its source), if any.
+fn placed_mode(source: &Utf8Path, destination: &Utf8Path) -> Option<u32> {
+    match is_placed_as(source, destination, CopyMode::Copy) {
+        true => get_mode(destination).ok(),
+        false => None,
+    }
 }
\ No newline at end of file
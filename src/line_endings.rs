@@ -0,0 +1,24 @@
+/// The line endings text (Txt encoded) resources are given when copied
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEndings {
+    /// Unix line endings, a line feed alone
+    Lf,
+    /// Windows line endings, a carriage return and line feed
+    Crlf,
+}
+
+impl LineEndings {
+    /// Give the content with every line ending (LF or CRLF) in this style
+    pub(crate) fn normalize(&self, content: &[u8]) -> Vec<u8> {
+        let mut normalized = Vec::with_capacity(content.len());
+        for (index, byte) in content.iter().enumerate() {
+            match byte {
+                b'\r' if content.get(index + 1) == Some(&b'\n') => {}
+                b'\n' if *self == LineEndings::Crlf => normalized.extend_from_slice(b"\r\n"),
+                _ => normalized.push(*byte),
+            }
+        }
+        normalized
+    }
+}
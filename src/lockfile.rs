@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use cargo_metadata::camino::Utf8Path;
+
+use crate::{CollationError, CollationPlan, HashAlgorithm, ResourceDrift, ResourceName, ResourceSha};
+
+/// The shas of a crate's collated resources, as written to its cargo-resources.lock
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub(crate) struct ResourceLock {
+    /// The algorithm the shas were computed with
+    pub(crate) hash_algorithm: HashAlgorithm,
+
+    /// The sha of each collated resource (as collated, e.g. with normalized line endings), by name
+    pub(crate) resources: BTreeMap<ResourceName, ResourceSha>,
+}
+
+impl ResourceLock {
+    /// The lock of the plan's resources, each with its planned sha
+    pub(crate) fn for_plan(plan: &CollationPlan) -> ResourceLock {
+        ResourceLock {
+            hash_algorithm: plan.consumer.hash_algorithm,
+            resources: plan.resources.iter()
+                .map(|planned| (planned.specification.resource_name.to_owned(), planned.expected_sha.to_owned()))
+                .collect(),
+        }
+    }
+
+    /// Read the lock file, if there is one
+    pub(crate) fn read(lock_file: &Utf8Path) -> Result<Option<ResourceLock>, CollationError> {
+        if !lock_file.is_file() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(lock_file)
+            .map_err(|e| CollationError::io(format!("Unable to read lock file {}", lock_file), e))?;
+        let lock = serde_json::from_str(&contents)
+            .map_err(|e| CollationError::parse(format!("Unable to parse lock file {}", lock_file), e))?;
+        Ok(Some(lock))
+    }
+
+    /// The lock as written to the lock file, in name order so it only changes with the resources
+    pub(crate) fn serialize(&self) -> Result<String, CollationError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| CollationError::serialize("Unable to serialize the lock file", e))?;
+        Ok(json + "\n")
+    }
+
+    /// How the resources' shas (in the lock's algorithm) differ from those locked: each resource
+    /// not locked or with a different sha, and each locked resource no longer collated, in name
+    /// order
+    pub(crate) fn drift(locked: Option<&ResourceLock>, resources: &BTreeMap<ResourceName, ResourceSha>) -> Vec<ResourceDrift> {
+        let empty = BTreeMap::new();
+        let locked_resources = locked.map_or(&empty, |lock| &lock.resources);
+        let mut drift = vec!();
+        for (resource_name, actual) in resources {
+            match locked_resources.get(resource_name) {
+                None => drift.push(ResourceDrift::Unlocked {
+                    resource_name: resource_name.to_owned(),
+                    actual: actual.to_owned(),
+                }),
+                Some(expected) if expected != actual => drift.push(ResourceDrift::LockMismatch {
+                    resource_name: resource_name.to_owned(),
+                    expected: expected.to_owned(),
+                    actual: actual.to_owned(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (resource_name, expected) in locked_resources {
+            if !resources.contains_key(resource_name) {
+                drift.push(ResourceDrift::NotCollated {
+                    resource_name: resource_name.to_owned(),
+                    expected: expected.to_owned(),
+                });
+            }
+        }
+        drift.sort_by(|a, b| a.resource_name().cmp(b.resource_name()));
+        drift
+    }
+}
@@ -4,13 +4,27 @@
 use cargo_metadata::camino::Utf8PathBuf;
 use clap::Parser;
 
-use cargo_resources::collate_resources;
-pub use resource_args::ResourceArgs;
+use cargo_resources::{
+    clean_with_options, collate_workspace, explain_resource_with, export_declarations_with,
+    list_available_resources_with, plan_changes_with, plan_collation_with, update_lock, verify_resources_with,
+    CollationError, JsonReporter,
+};
+pub use resource_args::{ResourceArgs, ResourceCommand};
 
 mod resource_args;
 
 fn main() -> Result<(), String> {
     let args = ResourceArgs::parse();
+    let json_messages = args.json_messages();
+    run(args).map_err(|e| {
+        if json_messages {
+            JsonReporter.report_error(&e);
+        }
+        e.to_string()
+    })
+}
+
+fn run(args: ResourceArgs) -> Result<(), CollationError> {
 
     match args.tool_name.as_deref() {
         None => println!("invoked without args - not from cargo"),
@@ -18,21 +32,108 @@ fn main() -> Result<(), String> {
         Some(&_) => panic!("incorrect invocation - call as a cargo tool - cargo resource ...")
     }
 
-    let package_path = match args.package {
+    let source_manifest = match args.manifest_path.to_owned() {
+        Some(manifest_path) => {
+            if !manifest_path.is_file() || manifest_path.file_name() != Some("Cargo.toml") {
+                Err(CollationError::InvalidArgument {
+                    argument: "manifest-path".to_string(),
+                    value: manifest_path.to_string(),
+                    expected: "a Cargo.toml file".to_string(),
+                })?
+            }
+            manifest_path
+        }
         None => {
-            Utf8PathBuf::from_path_buf(
-                std::env::current_dir().map_err(|_e|"Can't find current directory!".to_string())?
-            ).map_err(|e| format!("Unable to convert provided package path to UTF8: {:?}", e))?
+            let package_path = match args.package.to_owned() {
+                None => {
+                    let current_dir = std::env::current_dir().map_err(|e| CollationError::Io {
+                        context: "Can't find current directory".to_string(),
+                        source: e,
+                    })?;
+                    Utf8PathBuf::from_path_buf(current_dir).map_err(CollationError::NonUtf8Path)?
+                }
+                Some(p) => p
+            };
+            if !package_path.is_dir() {
+                Err(CollationError::InvalidArgument {
+                    argument: "package".to_string(),
+                    value: package_path.to_string(),
+                    expected: "a directory".to_string(),
+                })?
+            }
+            package_path.join("Cargo.toml")
         }
-        Some(p) => p
     };
-    if !package_path.is_dir() {
-        Err(format!("'package' parameter [{}] should be a directory.", package_path))?
-    }
-    let source_manifest = package_path.join("Cargo.toml");
 
     // Use the library to do the actual work
-    collate_resources(&source_manifest)
+    match args.command {
+        None => {
+            collate_workspace(&source_manifest, args.collate_options()?)?;
+            Ok(())
+        }
+        Some(ResourceCommand::Export) => {
+            let declarations = export_declarations_with(&source_manifest, &args.collate_options()?)?;
+            let json = serde_json::to_string_pretty(&declarations)
+                .map_err(|e| CollationError::Serialize {
+                    context: "Unable to serialize the resource declarations".to_string(),
+                    source: Box::new(e),
+                })?;
+            println!("{}", json);
+            Ok(())
+        }
+        Some(ResourceCommand::List { json }) => {
+            let available = list_available_resources_with(&source_manifest, &args.collate_options()?)?;
+            match json {
+                true => {
+                    let json = serde_json::to_string_pretty(&available)
+                        .map_err(|e| CollationError::Serialize {
+                            context: "Unable to serialize the available resources".to_string(),
+                            source: Box::new(e),
+                        })?;
+                    println!("{}", json);
+                }
+                false => {
+                    for res_dec in available {
+                        println!("{} (from {})", res_dec, res_dec.full_crate_path);
+                    }
+                }
+            }
+            Ok(())
+        }
+        Some(ResourceCommand::Plan) => {
+            let options = args.collate_options()?;
+            let changes = plan_changes_with(&plan_collation_with(&source_manifest, &options)?, &options)?;
+            print!("{}", changes);
+            Ok(())
+        }
+        Some(ResourceCommand::Verify) => {
+            verify_resources_with(&source_manifest, &args.collate_options()?)?;
+            println!("Resources are up to date");
+            Ok(())
+        }
+        Some(ResourceCommand::Why { ref resource_name }) => {
+            for res_dec in explain_resource_with(&source_manifest, resource_name, &args.collate_options()?)? {
+                println!("{}", res_dec);
+                println!("  {}", res_dec.dependency_chain.join(" -> "));
+            }
+            Ok(())
+        }
+        Some(ResourceCommand::UpdateLock) => {
+            let lock_file = update_lock(&source_manifest, args.collate_options()?)?;
+            println!("Updated {}", lock_file);
+            Ok(())
+        }
+        Some(ResourceCommand::Clean { dry_run }) => {
+            let dry_run = dry_run || args.dry_run;
+            for removed in clean_with_options(&source_manifest, args.collate_options()?.dry_run(dry_run))? {
+                match dry_run {
+                    true => println!("Would remove resource {}", removed),
+                    false => println!("Removed resource {}", removed),
+                }
+            }
+            Ok(())
+        }
+    }
 }
 
 
@@ -0,0 +1,37 @@
+use std::collections::BTreeMap;
+
+use cargo_metadata::camino::Utf8Path;
+
+use crate::CollationError;
+
+/// Generate a manifest: a JSON object mapping each key (e.g. a resource name or output path) to
+/// its value, described by the kind of manifest in any error.
+///
+/// The keys are written in order, so the same resources always generate the same manifest.
+pub(crate) fn generate_json_manifest<'a>(
+    entries: impl IntoIterator<Item = (&'a str, &'a str)>,
+    kind: &str,
+) -> Result<String, CollationError> {
+    let manifest: BTreeMap<&str, &str> = entries.into_iter().collect();
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| CollationError::serialize(format!("Unable to serialize the {}", kind), e))?;
+    Ok(json + "\n")
+}
+
+/// Generate a manifest in the format given by the manifest file's extension: .toml for TOML and
+/// .yaml (or .yml) for YAML (with the toml and yaml features), otherwise JSON.
+pub(crate) fn generate_manifest<'a>(
+    entries: impl IntoIterator<Item = (&'a str, &'a str)>,
+    kind: &str,
+    manifest_file: &Utf8Path,
+) -> Result<String, CollationError> {
+    match manifest_file.extension() {
+        #[cfg(feature = "toml")]
+        Some("toml") => toml::to_string(&entries.into_iter().collect::<BTreeMap<&str, &str>>())
+            .map_err(|e| CollationError::serialize(format!("Unable to serialize the {}", kind), e)),
+        #[cfg(feature = "yaml")]
+        Some("yaml" | "yml") => serde_yaml::to_string(&entries.into_iter().collect::<BTreeMap<&str, &str>>())
+            .map_err(|e| CollationError::serialize(format!("Unable to serialize the {}", kind), e)),
+        _ => generate_json_manifest(entries, kind),
+    }
+}
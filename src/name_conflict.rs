@@ -0,0 +1,11 @@
+use crate::{ResourceName, ResourceSpecification};
+
+/// A resource name declared by more than one crate (or crate version).
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ResourceNameConflict {
+    /// The resource name declared more than once
+    pub resource_name: ResourceName,
+
+    /// Every declaration of the name, in discovery order (the last one is used when collating)
+    pub declarations: Vec<ResourceSpecification>,
+}
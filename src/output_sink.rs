@@ -0,0 +1,85 @@
+use std::fs;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{
+    create_output_directory, file_sha, set_mode, verify_resource_is_in_root, CollationError, HashAlgorithm, ResourceSha,
+};
+
+/// Where collated resources are written, when not copied to the resource root (e.g. to object
+/// storage, or a test double), as used by [crate::CollateOptions::sink].
+///
+/// Each resource is given with its output path (relative to the resource root), and its content
+/// in the form it is collated. Sinks are shared between threads when resources are collated in
+/// parallel (with the rayon feature), so must be Sync.
+pub trait OutputSink: Sync {
+    /// Whether the sink already holds the resource at the output path, with the given sha (hashed
+    /// with the given algorithm), so it needn't be written again
+    fn exists_with_sha(
+        &self,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<bool, CollationError>;
+
+    /// Write the resource's content at the output path, with the (unix) mode collation gives it,
+    /// if any
+    fn write_resource(&self, output_path: &Utf8Path, content: &[u8], mode: Option<u32>) -> Result<(), CollationError>;
+}
+
+/// A sink writing each resource to its output path below a directory, by way of a temporary file
+/// renamed into place, rejecting any output path outside the directory.
+///
+/// Resources are written from memory, without the links, modified times or checksum files of
+/// collating to the resource root itself:
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::{collate_with_options, CollateOptions, FsSink};
+///
+/// let options = CollateOptions::default().sink(Box::new(FsSink::new(Utf8PathBuf::from("site"))));
+/// collate_with_options(&Utf8PathBuf::from("Cargo.toml"), options).unwrap();
+/// ```
+pub struct FsSink {
+    /// The directory the resources are written below
+    pub root: Utf8PathBuf,
+}
+
+impl FsSink {
+    /// A sink writing below the given directory
+    pub fn new(root: Utf8PathBuf) -> FsSink {
+        FsSink { root }
+    }
+}
+
+impl OutputSink for FsSink {
+    fn exists_with_sha(
+        &self,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<bool, CollationError> {
+        let path = self.root.join(output_path);
+        Ok(path.is_file() && file_sha(&path, hash_algorithm)? == *sha)
+    }
+
+    fn write_resource(&self, output_path: &Utf8Path, content: &[u8], mode: Option<u32>) -> Result<(), CollationError> {
+        let path = self.root.join(output_path);
+        verify_resource_is_in_root(&path, &self.root)?;
+        create_output_directory(path.parent().expect("No output directory!"), None)?;
+
+        let file_name = path.file_name().expect("No output file name!");
+        let temporary = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+        let result = fs::write(&temporary, content)
+            .map_err(|e| CollationError::io(format!("Unable to write resource {}", &path), e))
+            .and_then(|_| match mode {
+                Some(mode) => set_mode(&temporary, mode),
+                None => Ok(()),
+            })
+            .and_then(|_| fs::rename(&temporary, &path)
+                .map_err(|e| CollationError::io(format!("Unable to move {} into place at {}", &temporary, &path), e)));
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        result
+    }
+}
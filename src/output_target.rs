@@ -0,0 +1,30 @@
+#[cfg(any(feature = "zip", feature = "tar"))]
+use cargo_metadata::camino::Utf8PathBuf;
+
+/// Where collated resources are written
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum OutputTarget {
+    /// A directory tree below the resource root
+    #[default]
+    Directory,
+    /// A zip archive at the given path (relative to the crate root, or absolute), holding each
+    /// resource at its output path and the record of resolved resources (needs the zip feature)
+    #[cfg(feature = "zip")]
+    ZipArchive(Utf8PathBuf),
+    /// A tar archive at the given path, laid out as a zip archive is, which is gzipped when the
+    /// path ends in .tar.gz or .tgz (needs the tar feature).
+    ///
+    /// Each entry keeps the mode collation gives the resource (e.g. 0o755 for an executable one):
+    /// ```no_run
+    /// # #[cfg(feature = "tar")]
+    /// # {
+    /// use cargo_metadata::camino::Utf8PathBuf;
+    /// use cargo_resources::{collate_with_options, CollateOptions, OutputTarget};
+    ///
+    /// let options = CollateOptions::default().output_target(OutputTarget::TarArchive("resources.tar.gz".into()));
+    /// collate_with_options(&Utf8PathBuf::from("Cargo.toml"), options).unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "tar")]
+    TarArchive(Utf8PathBuf),
+}
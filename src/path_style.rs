@@ -0,0 +1,12 @@
+/// How output paths are expressed in the record of resolved resources
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PathStyle {
+    /// Relative to the resource root
+    #[default]
+    RelativeToRoot,
+    /// Relative to the consuming crate's root
+    RelativeToCrate,
+    /// Absolute paths
+    Absolute,
+}
@@ -0,0 +1,72 @@
+use std::fmt::{Display, Formatter};
+
+use cargo_metadata::camino::Utf8PathBuf;
+
+use crate::{ResourceName, ResourceSha};
+
+/// The changes executing a collation plan would make to the resource root.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PlannedChanges {
+    /// The resource root the changes apply to
+    pub resource_root: Utf8PathBuf,
+
+    /// The change for each planned resource, in plan order, followed by any recorded resources
+    /// that would be removed (when pruning)
+    pub changes: Vec<PlannedChange>,
+}
+
+/// The change to a single resource in the resource root.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum PlannedChange {
+    /// The resource doesn't exist yet and would be added
+    Add {
+        resource_name: ResourceName,
+        output_path: Utf8PathBuf,
+        new_sha: ResourceSha,
+    },
+    /// The resource exists with different content and would be overwritten
+    Overwrite {
+        resource_name: ResourceName,
+        output_path: Utf8PathBuf,
+        old_sha: ResourceSha,
+        new_sha: ResourceSha,
+    },
+    /// The resource exists with the same content and would be left alone
+    Unchanged {
+        resource_name: ResourceName,
+        output_path: Utf8PathBuf,
+        sha: ResourceSha,
+    },
+    /// The resource was recorded by the last collation but is no longer required, so would be
+    /// removed (when pruning)
+    Remove {
+        resource_name: ResourceName,
+        output_path: Utf8PathBuf,
+        old_sha: ResourceSha,
+    },
+}
+
+impl PlannedChanges {
+    /// Whether executing the plan would change anything
+    pub fn is_empty(&self) -> bool {
+        self.changes.iter().all(|change| matches!(change, PlannedChange::Unchanged { .. }))
+    }
+}
+
+impl Display for PlannedChanges {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            match change {
+                PlannedChange::Add { output_path, new_sha, .. } =>
+                    writeln!(f, "Resource       add: {:50} {}", self.resource_root.join(output_path), new_sha)?,
+                PlannedChange::Overwrite { output_path, old_sha, new_sha, .. } =>
+                    writeln!(f, "Resource overwrite: {:50} {} -> {}", self.resource_root.join(output_path), old_sha, new_sha)?,
+                PlannedChange::Unchanged { output_path, sha, .. } =>
+                    writeln!(f, "Resource unchanged: {:50} {}", self.resource_root.join(output_path), sha)?,
+                PlannedChange::Remove { output_path, old_sha, .. } =>
+                    writeln!(f, "Resource    remove: {:50} {}", self.resource_root.join(output_path), old_sha)?,
+            }
+        }
+        Ok(())
+    }
+}
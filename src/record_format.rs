@@ -0,0 +1,40 @@
+/// How the record of resolved resources is written
+///
+/// The TOML and YAML formats need the toml and yaml features. Records in any enabled format
+/// read back (by [crate::read_record], which goes by the file extension) as they were written:
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::{collate_with_options, read_record, CollateOptions, RecordFormat, DEFAULT_RECORD_FILE_NAME};
+///
+/// let options = CollateOptions::default().record_format(RecordFormat::PrettyJson);
+/// let summary = collate_with_options(&Utf8PathBuf::from("Cargo.toml"), options).unwrap();
+/// let record = read_record(&summary.resource_root.join(DEFAULT_RECORD_FILE_NAME)).unwrap();
+/// ```
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordFormat {
+    /// Compact JSON
+    #[default]
+    Json,
+    /// Indented JSON, which is easier to read and diff
+    PrettyJson,
+    /// TOML, with the resources as an array of tables named resources (needs the toml feature)
+    #[cfg(feature = "toml")]
+    Toml,
+    /// YAML (needs the yaml feature)
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl RecordFormat {
+    /// The extension of a record file in the format (as used for the default record file)
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordFormat::Json | RecordFormat::PrettyJson => "json",
+            #[cfg(feature = "toml")]
+            RecordFormat::Toml => "toml",
+            #[cfg(feature = "yaml")]
+            RecordFormat::Yaml => "yaml",
+        }
+    }
+}
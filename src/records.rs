@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{CollationError, RecordFormat, ResolvedResource};
+
+/// Two records placing different resources at the same output path.
+#[derive(Debug, Clone)]
+pub struct RecordConflict {
+    /// The output path both resources are placed at
+    pub output_path: Utf8PathBuf,
+
+    /// The resource from the earlier record
+    pub existing: ResolvedResource,
+
+    /// The differing resource from the later record
+    pub conflicting: ResolvedResource,
+}
+
+/// A record written as TOML, which needs a table (rather than an array) at the top level
+#[cfg(feature = "toml")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TomlRecord {
+    resources: Vec<ResolvedResource>,
+}
+
+/// Read a record of resolved resources (e.g. a resolved_resources.json written by collation).
+///
+/// The format is given by the file's extension: .toml for TOML and .yaml (or .yml) for YAML
+/// (with the toml and yaml features), otherwise JSON.
+///
+/// # Arguments
+/// * record_file: The path of the record file.
+///
+/// # Returns
+/// The resources in the record, or an error describing the failure.
+pub fn read_record(record_file: &Utf8Path) -> Result<Vec<ResolvedResource>, CollationError> {
+    let contents = fs::read_to_string(record_file)
+        .map_err(|e| CollationError::io(format!("Unable to read record file {}", record_file), e))?;
+    let malformed = |e: Box<dyn std::error::Error + Send + Sync>| {
+        CollationError::parse(format!("Malformed record file {}", record_file), e)
+    };
+    match record_file.extension() {
+        #[cfg(feature = "toml")]
+        Some("toml") => toml::from_str::<TomlRecord>(&contents)
+            .map(|record| record.resources)
+            .map_err(|e| malformed(e.into())),
+        #[cfg(feature = "yaml")]
+        Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(|e| malformed(e.into())),
+        _ => serde_json::from_str(&contents).map_err(|e| malformed(e.into())),
+    }
+}
+
+/// Write a record of resolved resources in the given format
+pub(crate) fn serialize_record(resources: &[ResolvedResource], format: RecordFormat) -> Result<String, CollationError> {
+    let unserializable = |e: Box<dyn std::error::Error + Send + Sync>| {
+        CollationError::serialize("Unable to serialize the set of resolved resources", e)
+    };
+    match format {
+        RecordFormat::Json => serde_json::to_string(resources).map_err(|e| unserializable(e.into())),
+        RecordFormat::PrettyJson => serde_json::to_string_pretty(resources).map_err(|e| unserializable(e.into())),
+        #[cfg(feature = "toml")]
+        RecordFormat::Toml => toml::to_string(&TomlRecord { resources: resources.to_vec() })
+            .map_err(|e| unserializable(e.into())),
+        #[cfg(feature = "yaml")]
+        RecordFormat::Yaml => serde_yaml::to_string(resources).map_err(|e| unserializable(e.into())),
+    }
+}
+
+/// Merge several records of resolved resources into one.
+///
+/// Entries with the same output path are merged when they are the same resource, i.e. the same
+/// resource name declared by the same crate and version. Otherwise they conflict. The records
+/// should use the same path style (relative to the resource root, by default) for the output
+/// paths to be comparable.
+///
+/// # Arguments
+/// * records: The records to merge, e.g. as read by [read_record].
+///
+/// # Returns
+/// The merged record sorted by output path, or every conflict found.
+pub fn merge_records(
+    records: &[Vec<ResolvedResource>],
+) -> Result<Vec<ResolvedResource>, Vec<RecordConflict>> {
+    let mut merged: BTreeMap<&Utf8PathBuf, &ResolvedResource> = BTreeMap::new();
+    let mut conflicts = vec!();
+    for resource in records.iter().flatten() {
+        match merged.get(&resource.specification.output_path) {
+            None => {
+                merged.insert(&resource.specification.output_path, resource);
+            }
+            Some(existing) => {
+                if !is_same_resource(existing, resource) {
+                    conflicts.push(RecordConflict {
+                        output_path: resource.specification.output_path.to_owned(),
+                        existing: (*existing).clone(),
+                        conflicting: resource.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    if !conflicts.is_empty() {
+        return Err(conflicts);
+    }
+    Ok(merged.into_values().cloned().collect())
+}
+
+/// Whether two record entries describe the same declared resource
+fn is_same_resource(a: &ResolvedResource, b: &ResolvedResource) -> bool {
+    a.specification.resource_name == b.specification.resource_name
+        && a.specification.declaring_crate_name == b.specification.declaring_crate_name
+        && a.specification.declaring_crate_version == b.specification.declaring_crate_version
+}
@@ -0,0 +1,607 @@
+use cargo_metadata::camino::Utf8Path;
+use cargo_metadata::semver::Version;
+
+use crate::{CollationError, RequiredSha, ResourceSha, ResourceSpecification};
+
+/// Receives the progress and problems found while collating resources, so they can be reported
+/// in whatever way suits the caller (e.g. the console, or cargo from a build script).
+///
+/// Reporters are shared between threads when resources are copied in parallel (with the rayon
+/// feature), so must be Sync.
+pub trait ReportingTrait: Sync {
+    /// A required resource, with the given specification, has been collated (copied, or left in
+    /// place when already up to date)
+    fn report_resource_collection(
+        &self,
+        res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    );
+
+    /// A required resource, with the given specification, would be collated (copied, or left in
+    /// place when already up to date), for a dry run that doesn't touch the disk
+    fn report_dry_run_collection(
+        &self,
+        res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    );
+
+    /// A copied resource was hashed again and found to have its source's sha
+    fn report_resource_verified(&self, output_path: &Utf8Path, sha: &ResourceSha);
+
+    /// A checksum file was written beside a resource, or would be for a dry run
+    fn report_checksum_file(&self, checksum_path: &Utf8Path, dry_run: bool);
+
+    /// There were no resources to collate
+    fn report_no_resources_found(&self);
+
+    /// A resource from a previous collation that is no longer required has been removed
+    fn report_resource_pruned(&self, output_path: &Utf8Path);
+
+    /// A resource name was declared again, and the later declaration replaces the earlier one
+    fn report_duplicate_resource(&self, replaced: &ResourceSpecification, replacement: &ResourceSpecification);
+
+    /// A crate was excluded from collation, along with the given number of resource declarations
+    fn report_excluded_crate(&self, crate_name: &str, version: &Version, declarations: usize);
+
+    /// A declared resource isn't required by the consuming crate's requires list
+    fn report_unused_resource(&self, res_dec: &ResourceSpecification);
+
+    /// A resource's source doesn't match the sha required by the consuming crate, but collation
+    /// continues as required shas aren't being verified
+    fn report_unverified_sha(&self, resource_name: &str, expected: &RequiredSha, actual: &ResourceSha);
+
+    /// A required resource isn't declared by any crate
+    fn report_missing_resource(&self, resource_name: &str);
+
+    /// A crate's resource declaration couldn't be read
+    fn report_malformed_resource_declaration(&self, crate_name: &str, error: &str);
+
+    /// A declared or required resource has been skipped, for the given reason
+    fn report_skipped_resource(&self, resource_name: &str, reason: &str);
+
+    /// Something is likely wrong, but collation can continue
+    fn report_warning(&self, message: &str);
+
+    /// A step in the resolution of a resource being traced
+    fn report_trace(&self, resource_name: &str, step: &str);
+}
+
+/// Reports to the console, for use from the command line.
+pub struct DefaultReporter;
+
+impl ReportingTrait for DefaultReporter {
+    fn report_resource_collection(
+        &self,
+        _res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    ) {
+        println!("{}", collection_message(already_exists, output_path, sha));
+    }
+
+    fn report_dry_run_collection(
+        &self,
+        _res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    ) {
+        println!("{}", dry_run_collection_message(already_exists, output_path, sha));
+    }
+
+    fn report_resource_verified(&self, output_path: &Utf8Path, sha: &ResourceSha) {
+        println!("Resource verified: {:50} {}", output_path, sha);
+    }
+
+    fn report_checksum_file(&self, checksum_path: &Utf8Path, dry_run: bool) {
+        match dry_run {
+            true => println!("Checksum would write: {}", checksum_path),
+            false => println!("Checksum  written: {}", checksum_path),
+        }
+    }
+
+    fn report_no_resources_found(&self) {
+        println!("No resources were found - finishing early.");
+    }
+
+    fn report_resource_pruned(&self, output_path: &Utf8Path) {
+        println!("Resource  pruned: {}", output_path);
+    }
+
+    fn report_duplicate_resource(&self, replaced: &ResourceSpecification, replacement: &ResourceSpecification) {
+        self.report_warning(&duplicate_message(replaced, replacement));
+    }
+
+    fn report_excluded_crate(&self, crate_name: &str, version: &Version, declarations: usize) {
+        println!("Excluding crate {} {}: {} resource declarations not collated", crate_name, version, declarations);
+    }
+
+    fn report_unused_resource(&self, res_dec: &ResourceSpecification) {
+        self.report_warning(&unused_message(res_dec));
+    }
+
+    fn report_unverified_sha(&self, resource_name: &str, expected: &RequiredSha, actual: &ResourceSha) {
+        self.report_warning(&unverified_message(resource_name, expected, actual));
+    }
+
+    fn report_missing_resource(&self, resource_name: &str) {
+        eprintln!("Error: no resource found matching requirement {}", resource_name);
+    }
+
+    fn report_malformed_resource_declaration(&self, crate_name: &str, error: &str) {
+        eprintln!("Error: malformed resource declaration in {}: {}", crate_name, error);
+    }
+
+    fn report_skipped_resource(&self, resource_name: &str, reason: &str) {
+        println!("Skipping resource {}: {}", resource_name, reason);
+    }
+
+    fn report_warning(&self, message: &str) {
+        println!("Warning: {}", message);
+    }
+
+    fn report_trace(&self, resource_name: &str, step: &str) {
+        println!("Trace {}: {}", resource_name, step);
+    }
+}
+
+/// Reports to cargo, for use from a build script (build.rs).
+///
+/// Warnings and errors are emitted as cargo:warning directives, so cargo shows them in the build
+/// output; progress is printed as plain lines, which cargo only shows in very verbose builds.
+pub struct BuildRsReporter;
+
+impl ReportingTrait for BuildRsReporter {
+    fn report_resource_collection(
+        &self,
+        res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    ) {
+        DefaultReporter.report_resource_collection(res_dec, already_exists, output_path, sha);
+    }
+
+    fn report_dry_run_collection(
+        &self,
+        res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    ) {
+        DefaultReporter.report_dry_run_collection(res_dec, already_exists, output_path, sha);
+    }
+
+    fn report_resource_verified(&self, output_path: &Utf8Path, sha: &ResourceSha) {
+        DefaultReporter.report_resource_verified(output_path, sha);
+    }
+
+    fn report_checksum_file(&self, checksum_path: &Utf8Path, dry_run: bool) {
+        DefaultReporter.report_checksum_file(checksum_path, dry_run);
+    }
+
+    fn report_no_resources_found(&self) {
+        DefaultReporter.report_no_resources_found();
+    }
+
+    fn report_resource_pruned(&self, output_path: &Utf8Path) {
+        DefaultReporter.report_resource_pruned(output_path);
+    }
+
+    fn report_duplicate_resource(&self, replaced: &ResourceSpecification, replacement: &ResourceSpecification) {
+        self.report_warning(&duplicate_message(replaced, replacement));
+    }
+
+    fn report_excluded_crate(&self, crate_name: &str, version: &Version, declarations: usize) {
+        DefaultReporter.report_excluded_crate(crate_name, version, declarations);
+    }
+
+    fn report_unused_resource(&self, res_dec: &ResourceSpecification) {
+        self.report_warning(&unused_message(res_dec));
+    }
+
+    fn report_unverified_sha(&self, resource_name: &str, expected: &RequiredSha, actual: &ResourceSha) {
+        self.report_warning(&unverified_message(resource_name, expected, actual));
+    }
+
+    fn report_missing_resource(&self, resource_name: &str) {
+        println!("cargo:warning=No resource found matching requirement {}", resource_name);
+    }
+
+    fn report_malformed_resource_declaration(&self, crate_name: &str, error: &str) {
+        println!("cargo:warning=Malformed resource declaration in {}: {}", crate_name, error);
+    }
+
+    fn report_skipped_resource(&self, resource_name: &str, reason: &str) {
+        println!("cargo:warning=Skipping resource {}: {}", resource_name, reason);
+    }
+
+    fn report_warning(&self, message: &str) {
+        println!("cargo:warning={}", message);
+    }
+
+    fn report_trace(&self, resource_name: &str, step: &str) {
+        println!("cargo:warning=Trace {}: {}", resource_name, step);
+    }
+}
+
+/// Reports to the console as the [DefaultReporter] does, with each collated resource's source
+/// path and declaring crate, for debugging why a file was collated.
+pub struct VerboseReporter;
+
+impl ReportingTrait for VerboseReporter {
+    fn report_resource_collection(
+        &self,
+        res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    ) {
+        println!("{} {}", collection_message(already_exists, output_path, sha), source_description(res_dec));
+    }
+
+    fn report_dry_run_collection(
+        &self,
+        res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    ) {
+        println!("{} {}", dry_run_collection_message(already_exists, output_path, sha), source_description(res_dec));
+    }
+
+    fn report_resource_verified(&self, output_path: &Utf8Path, sha: &ResourceSha) {
+        DefaultReporter.report_resource_verified(output_path, sha);
+    }
+
+    fn report_checksum_file(&self, checksum_path: &Utf8Path, dry_run: bool) {
+        DefaultReporter.report_checksum_file(checksum_path, dry_run);
+    }
+
+    fn report_no_resources_found(&self) {
+        DefaultReporter.report_no_resources_found();
+    }
+
+    fn report_resource_pruned(&self, output_path: &Utf8Path) {
+        DefaultReporter.report_resource_pruned(output_path);
+    }
+
+    fn report_duplicate_resource(&self, replaced: &ResourceSpecification, replacement: &ResourceSpecification) {
+        DefaultReporter.report_duplicate_resource(replaced, replacement);
+    }
+
+    fn report_excluded_crate(&self, crate_name: &str, version: &Version, declarations: usize) {
+        DefaultReporter.report_excluded_crate(crate_name, version, declarations);
+    }
+
+    fn report_unused_resource(&self, res_dec: &ResourceSpecification) {
+        DefaultReporter.report_unused_resource(res_dec);
+    }
+
+    fn report_unverified_sha(&self, resource_name: &str, expected: &RequiredSha, actual: &ResourceSha) {
+        DefaultReporter.report_unverified_sha(resource_name, expected, actual);
+    }
+
+    fn report_missing_resource(&self, resource_name: &str) {
+        DefaultReporter.report_missing_resource(resource_name);
+    }
+
+    fn report_malformed_resource_declaration(&self, crate_name: &str, error: &str) {
+        DefaultReporter.report_malformed_resource_declaration(crate_name, error);
+    }
+
+    fn report_skipped_resource(&self, resource_name: &str, reason: &str) {
+        DefaultReporter.report_skipped_resource(resource_name, reason);
+    }
+
+    fn report_warning(&self, message: &str) {
+        DefaultReporter.report_warning(message);
+    }
+
+    fn report_trace(&self, resource_name: &str, step: &str) {
+        DefaultReporter.report_trace(resource_name, step);
+    }
+}
+
+/// Describe a collated resource, as the [DefaultReporter] does
+fn collection_message(already_exists: bool, output_path: &Utf8Path, sha: &ResourceSha) -> String {
+    format!(
+        "Resource {} {:50} {}",
+        match already_exists {
+            true => "existed:",
+            false => " copied:"
+        },
+        output_path,
+        sha,
+    )
+}
+
+/// Describe a resource a dry run would collate, as the [DefaultReporter] does
+fn dry_run_collection_message(already_exists: bool, output_path: &Utf8Path, sha: &ResourceSha) -> String {
+    format!(
+        "Resource {} {:50} {}",
+        match already_exists {
+            true => "would skip:",
+            false => "would copy:"
+        },
+        output_path,
+        sha,
+    )
+}
+
+/// Describe where a collated resource came from, e.g. "from /src/provider/resources/hello_world.txt
+/// (provider 0.1.0)"
+fn source_description(res_dec: &ResourceSpecification) -> String {
+    format!(
+        "from {} ({} {})",
+        res_dec.full_crate_path,
+        res_dec.declaring_crate_name,
+        res_dec.declaring_crate_version
+    )
+}
+
+/// Reports only warnings and errors to the console, for running within a larger build where a
+/// line per resource would be noise.
+///
+/// Progress (resources collated, verified or pruned, checksum files written, crates excluded, or
+/// no resources being found) isn't reported; everything else is reported as the [DefaultReporter]
+/// does.
+pub struct QuietReporter;
+
+impl ReportingTrait for QuietReporter {
+    fn report_resource_collection(
+        &self,
+        _res_dec: &ResourceSpecification,
+        _already_exists: bool,
+        _output_path: &Utf8Path,
+        _sha: &ResourceSha,
+    ) {}
+
+    fn report_dry_run_collection(
+        &self,
+        _res_dec: &ResourceSpecification,
+        _already_exists: bool,
+        _output_path: &Utf8Path,
+        _sha: &ResourceSha,
+    ) {}
+
+    fn report_resource_verified(&self, _output_path: &Utf8Path, _sha: &ResourceSha) {}
+
+    fn report_checksum_file(&self, _checksum_path: &Utf8Path, _dry_run: bool) {}
+
+    fn report_no_resources_found(&self) {}
+
+    fn report_resource_pruned(&self, _output_path: &Utf8Path) {}
+
+    fn report_duplicate_resource(&self, replaced: &ResourceSpecification, replacement: &ResourceSpecification) {
+        DefaultReporter.report_duplicate_resource(replaced, replacement);
+    }
+
+    fn report_excluded_crate(&self, _crate_name: &str, _version: &Version, _declarations: usize) {}
+
+    fn report_unused_resource(&self, res_dec: &ResourceSpecification) {
+        DefaultReporter.report_unused_resource(res_dec);
+    }
+
+    fn report_unverified_sha(&self, resource_name: &str, expected: &RequiredSha, actual: &ResourceSha) {
+        DefaultReporter.report_unverified_sha(resource_name, expected, actual);
+    }
+
+    fn report_missing_resource(&self, resource_name: &str) {
+        DefaultReporter.report_missing_resource(resource_name);
+    }
+
+    fn report_malformed_resource_declaration(&self, crate_name: &str, error: &str) {
+        DefaultReporter.report_malformed_resource_declaration(crate_name, error);
+    }
+
+    fn report_skipped_resource(&self, resource_name: &str, reason: &str) {
+        DefaultReporter.report_skipped_resource(resource_name, reason);
+    }
+
+    fn report_warning(&self, message: &str) {
+        DefaultReporter.report_warning(message);
+    }
+
+    fn report_trace(&self, resource_name: &str, step: &str) {
+        DefaultReporter.report_trace(resource_name, step);
+    }
+}
+
+/// Reports to stdout as JSON, one object per line, for tools that drive collation (as cargo's
+/// --message-format json does).
+///
+/// Every object has the same fields: event (e.g. "copied", "unchanged", "duplicate" or "error"),
+/// with resource_name, path, sha and message each null when they don't apply to the event (a
+/// collated resource is given by its output path, relative to the crate root).
+pub struct JsonReporter;
+
+/// A reported event, as written by [JsonReporter]
+#[derive(serde::Serialize)]
+struct JsonEvent<'a> {
+    event: &'a str,
+    resource_name: Option<&'a str>,
+    path: Option<&'a Utf8Path>,
+    sha: Option<&'a ResourceSha>,
+    message: Option<String>,
+}
+
+impl<'a> JsonEvent<'a> {
+    fn new(event: &'a str) -> Self {
+        JsonEvent { event, resource_name: None, path: None, sha: None, message: None }
+    }
+
+    fn print(&self) {
+        // The event only holds strings, so serializing it can't fail
+        println!("{}", serde_json::to_string(self).expect("Unable to serialize the event"));
+    }
+}
+
+impl JsonReporter {
+    /// Report the error that ended collation, as an event of type error
+    pub fn report_error(&self, error: &CollationError) {
+        JsonEvent { message: Some(error.to_string()), ..JsonEvent::new("error") }.print();
+    }
+}
+
+impl ReportingTrait for JsonReporter {
+    fn report_resource_collection(
+        &self,
+        res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    ) {
+        let event = match already_exists {
+            true => "unchanged",
+            false => "copied",
+        };
+        JsonEvent {
+            resource_name: Some(&res_dec.resource_name),
+            path: Some(output_path),
+            sha: Some(sha),
+            ..JsonEvent::new(event)
+        }.print();
+    }
+
+    fn report_dry_run_collection(
+        &self,
+        res_dec: &ResourceSpecification,
+        already_exists: bool,
+        output_path: &Utf8Path,
+        sha: &ResourceSha,
+    ) {
+        let event = match already_exists {
+            true => "would_skip",
+            false => "would_copy",
+        };
+        JsonEvent {
+            resource_name: Some(&res_dec.resource_name),
+            path: Some(output_path),
+            sha: Some(sha),
+            ..JsonEvent::new(event)
+        }.print();
+    }
+
+    fn report_resource_verified(&self, output_path: &Utf8Path, sha: &ResourceSha) {
+        JsonEvent { path: Some(output_path), sha: Some(sha), ..JsonEvent::new("verified") }.print();
+    }
+
+    fn report_checksum_file(&self, checksum_path: &Utf8Path, dry_run: bool) {
+        let event = match dry_run {
+            true => "would_write_checksum",
+            false => "checksum_written",
+        };
+        JsonEvent { path: Some(checksum_path), ..JsonEvent::new(event) }.print();
+    }
+
+    fn report_no_resources_found(&self) {
+        JsonEvent::new("no_resources").print();
+    }
+
+    fn report_resource_pruned(&self, output_path: &Utf8Path) {
+        JsonEvent { path: Some(output_path), ..JsonEvent::new("pruned") }.print();
+    }
+
+    fn report_duplicate_resource(&self, replaced: &ResourceSpecification, replacement: &ResourceSpecification) {
+        JsonEvent {
+            resource_name: Some(&replacement.resource_name),
+            path: Some(&replacement.full_crate_path),
+            message: Some(duplicate_message(replaced, replacement)),
+            ..JsonEvent::new("duplicate")
+        }.print();
+    }
+
+    fn report_excluded_crate(&self, crate_name: &str, version: &Version, declarations: usize) {
+        JsonEvent {
+            message: Some(format!("{} {} excluded, with {} resource declarations", crate_name, version, declarations)),
+            ..JsonEvent::new("excluded_crate")
+        }.print();
+    }
+
+    fn report_unused_resource(&self, res_dec: &ResourceSpecification) {
+        JsonEvent {
+            resource_name: Some(&res_dec.resource_name),
+            path: Some(&res_dec.full_crate_path),
+            message: Some(unused_message(res_dec)),
+            ..JsonEvent::new("unused")
+        }.print();
+    }
+
+    fn report_unverified_sha(&self, resource_name: &str, expected: &RequiredSha, actual: &ResourceSha) {
+        JsonEvent {
+            resource_name: Some(resource_name),
+            sha: Some(actual),
+            message: Some(unverified_message(resource_name, expected, actual)),
+            ..JsonEvent::new("unverified")
+        }.print();
+    }
+
+    fn report_missing_resource(&self, resource_name: &str) {
+        JsonEvent { resource_name: Some(resource_name), ..JsonEvent::new("missing") }.print();
+    }
+
+    fn report_malformed_resource_declaration(&self, crate_name: &str, error: &str) {
+        JsonEvent {
+            message: Some(format!("Malformed resource declaration in {}: {}", crate_name, error)),
+            ..JsonEvent::new("malformed_declaration")
+        }.print();
+    }
+
+    fn report_skipped_resource(&self, resource_name: &str, reason: &str) {
+        JsonEvent {
+            resource_name: Some(resource_name),
+            message: Some(reason.to_owned()),
+            ..JsonEvent::new("skipped")
+        }.print();
+    }
+
+    fn report_warning(&self, message: &str) {
+        JsonEvent { message: Some(message.to_owned()), ..JsonEvent::new("warning") }.print();
+    }
+
+    fn report_trace(&self, resource_name: &str, step: &str) {
+        JsonEvent {
+            resource_name: Some(resource_name),
+            message: Some(step.to_owned()),
+            ..JsonEvent::new("trace")
+        }.print();
+    }
+}
+
+/// Describe a declared resource the consuming crate doesn't require
+fn unused_message(res_dec: &ResourceSpecification) -> String {
+    format!(
+        "Resource {} declared by {} {} is not required by the consuming crate",
+        res_dec.resource_name,
+        res_dec.declaring_crate_name,
+        res_dec.declaring_crate_version
+    )
+}
+
+/// Describe a duplicate resource declaration
+fn duplicate_message(replaced: &ResourceSpecification, replacement: &ResourceSpecification) -> String {
+    format!(
+        "Resource {} declared by {} {} replaces the declaration by {} {}",
+        replacement.resource_name,
+        replacement.declaring_crate_name,
+        replacement.declaring_crate_version,
+        replaced.declaring_crate_name,
+        replaced.declaring_crate_version
+    )
+}
+
+/// The warning given for a resource whose required sha doesn't match, when not verified
+fn unverified_message(resource_name: &str, expected: &RequiredSha, actual: &ResourceSha) -> String {
+    format!(
+        "Resource {} with sha {} does not match required sha {}, which isn't being verified",
+        resource_name,
+        actual,
+        expected
+    )
+}
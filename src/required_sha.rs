@@ -0,0 +1,43 @@
+use std::fmt::{Display, Formatter};
+
+use crate::ResourceSha;
+
+/// The sha (or shas) a consuming crate requires a resource's source to match: a single hex hash,
+/// or a list of them any one of which is accepted (e.g. while upstream is changing the resource).
+///
+/// Either form can be given in a requirement, and is serialized as it was given:
+/// ```
+/// use cargo_resources::RequiredSha;
+///
+/// let one: RequiredSha = serde_json::from_str(r#""sha256:abc""#).unwrap();
+/// assert_eq!(one.shas(), ["sha256:abc"]);
+///
+/// let any_of: RequiredSha = serde_json::from_str(r#"["abc", "def"]"#).unwrap();
+/// assert_eq!(any_of.shas(), ["abc", "def"]);
+/// assert_eq!(any_of.to_string(), "abc or def");
+/// assert_eq!(serde_json::to_string(&any_of).unwrap(), r#"["abc","def"]"#);
+/// ```
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum RequiredSha {
+    /// A single required sha
+    One(ResourceSha),
+    /// Several shas, any of which is accepted
+    AnyOf(Vec<ResourceSha>),
+}
+
+impl RequiredSha {
+    /// The accepted shas, in the order given
+    pub fn shas(&self) -> &[ResourceSha] {
+        match self {
+            RequiredSha::One(sha) => std::slice::from_ref(sha),
+            RequiredSha::AnyOf(shas) => shas,
+        }
+    }
+}
+
+impl Display for RequiredSha {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.shas().join(" or "))
+    }
+}
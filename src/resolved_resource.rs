@@ -0,0 +1,42 @@
+use crate::{HashAlgorithm, ResourceSha, ResourceSpecification};
+
+/// A collated resource, as written to the record of resolved resources.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ResolvedResource {
+    /// The resource's specification, with the output path in the consumer's path style
+    #[serde(flatten)]
+    pub specification: ResourceSpecification,
+
+    /// The hex-encoded hash of the collated resource, i.e. after normalizing any line endings
+    #[serde(alias = "sha")]
+    pub output_sha: ResourceSha,
+
+    /// The hex-encoded hash of the resource's source file, as any required_sha is checked against
+    /// (the output sha, unless its line endings were normalized; empty in a record written before
+    /// it was kept)
+    #[serde(default)]
+    pub source_sha: ResourceSha,
+
+    /// The algorithm used to hash the resource
+    pub hash_algorithm: HashAlgorithm,
+
+    /// The size of the collated resource, in bytes
+    pub size: u64,
+
+    /// The unix mode of the collated resource, when collation set it (by preserve_mode, file_mode
+    /// or executable)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+
+    /// When the collated resource was last modified, in nanoseconds since the unix epoch, so a
+    /// later collation can take it to be up to date without hashing it while it is unchanged (none
+    /// for a resource collated to an archive or sink)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+
+    /// When the resource's source was last modified, in nanoseconds since the unix epoch, as when
+    /// it was planned, so a later collation can take the recorded sha to be the source's without
+    /// reading it again while it is unchanged (none for a resource collated to an archive or sink)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_modified: Option<u64>,
+}
@@ -1,5 +1,9 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use cargo_metadata::camino::Utf8PathBuf;
+use cargo_resources::{
+    CollateOptions, CollationError, DependencyScope, DuplicateStrategy, FeatureSelection, JsonReporter, OutputTarget, QuietReporter,
+    RecordFormat, SriAlgorithm, VerboseReporter,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -10,4 +14,317 @@ pub struct ResourceArgs {
     /// The optional package to operate on
     #[arg(short, long, value_name = "FILE")]
     pub package: Option<Utf8PathBuf>,
+
+    /// The path of the package's Cargo.toml, as for other cargo commands (instead of --package)
+    #[arg(long, value_name = "PATH", conflicts_with = "package")]
+    pub manifest_path: Option<Utf8PathBuf>,
+
+    /// The resource root to collate into (relative to the package), instead of the package's (with
+    /// a directory for each member of a virtual workspace)
+    #[arg(long, value_name = "DIR")]
+    pub resource_root: Option<Utf8PathBuf>,
+
+    /// The target triple substituted for {triple} in the resource root (overrides TARGET)
+    #[arg(long, value_name = "TRIPLE")]
+    pub target: Option<String>,
+
+    /// How progress and problems are reported: human readable text, or one JSON object per line
+    #[arg(long, value_name = "FMT", default_value = "human", value_parser = ["human", "json"])]
+    pub message_format: String,
+
+    /// Only report warnings and errors, not each resource collated (with the human message format)
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Also report each collated resource's source path and declaring crate (with the human message
+    /// format)
+    #[arg(short, long, conflicts_with = "quiet")]
+    pub verbose: bool,
+
+    /// Report what collation would copy or skip, without touching the disk
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Remove the previously collated resources that are no longer required
+    #[arg(long)]
+    pub prune: bool,
+
+    /// Always hash sources and existing resources, rather than trusting the size and modified time
+    /// recorded for them
+    #[arg(long)]
+    pub always_hash: bool,
+
+    /// Hash each resource again once copied, failing if it doesn't have its source's sha
+    #[arg(long)]
+    pub post_verify: bool,
+
+    /// Write a checksum file (e.g. logo.png.sha256) beside each collated resource
+    #[arg(long)]
+    pub emit_checksums: bool,
+
+    /// Only enable the given (comma separated) features, with the default ones, when resolving
+    /// dependencies
+    #[arg(long, value_name = "FEATURES", value_delimiter = ',', conflicts_with_all = ["no_default_features", "all_features"])]
+    pub features: Option<Vec<String>>,
+
+    /// Enable no features, not even the default ones, when resolving dependencies
+    #[arg(long, conflicts_with = "all_features")]
+    pub no_default_features: bool,
+
+    /// Enable all features when resolving dependencies (the default)
+    #[arg(long)]
+    pub all_features: bool,
+
+    /// Which declaration to use when a resource name is declared more than once
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        value_parser = ["last_wins", "first_wins", "error", "highest_version"]
+    )]
+    pub duplicate_strategy: Option<String>,
+
+    /// Resolve dependencies without accessing the network
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Require Cargo.lock, cargo-resources.lock and the dependency cache to be up to date
+    #[arg(long)]
+    pub frozen: bool,
+
+    /// Require Cargo.lock and cargo-resources.lock to be up to date, failing rather than updating
+    /// them
+    #[arg(long)]
+    pub locked: bool,
+
+    /// Fail when a dependency declares a resource the package's requires list doesn't include
+    #[arg(long)]
+    pub deny_unused: bool,
+
+    /// Collate resources that don't match their required_sha, with a warning, rather than failing
+    /// (for development only, never in CI)
+    #[arg(long)]
+    pub no_verify: bool,
+
+    /// Only collate resources from the package's direct dependencies
+    #[arg(long)]
+    pub direct_only: bool,
+
+    /// Don't collate resources from the given (comma separated) crates, each a name optionally
+    /// qualified by a version, e.g. examples@1.2.0
+    #[arg(long, value_name = "CRATES", value_delimiter = ',')]
+    pub exclude: Vec<String>,
+
+    /// Where to write the record of resolved resources (relative to the package), instead of the
+    /// resource root
+    #[arg(long, value_name = "FILE")]
+    pub record_path: Option<Utf8PathBuf>,
+
+    /// How to write the record of resolved resources
+    #[arg(long, value_name = "FORMAT", value_parser = ["json", "pretty_json", "toml", "yaml"])]
+    pub record_format: Option<String>,
+
+    /// Don't write the record of resolved resources
+    #[arg(long)]
+    pub no_record: bool,
+
+    /// Generate a Rust module mapping resource names to their output paths, at the given path
+    /// (relative to the package)
+    #[arg(long, value_name = "FILE")]
+    pub generate_rust_module: Option<Utf8PathBuf>,
+
+    /// Write an integrity manifest, mapping output paths to Subresource Integrity strings, at the
+    /// given path (relative to the package)
+    #[arg(long, value_name = "FILE")]
+    pub integrity_manifest: Option<Utf8PathBuf>,
+
+    /// Write a names manifest, mapping resource names to output paths, at the given path (relative
+    /// to the package): JSON, or TOML or YAML by the file's extension
+    #[arg(long, value_name = "FILE")]
+    pub names_manifest: Option<Utf8PathBuf>,
+
+    /// The hash used for the integrity manifest
+    #[arg(long, value_name = "ALGORITHM", default_value = "sha384", value_parser = ["sha384", "sha512"])]
+    pub integrity_algorithm: String,
+
+    /// Collate the resources into a zip archive at the given path (relative to the package),
+    /// instead of the resource root
+    #[arg(long, value_name = "FILE")]
+    pub zip: Option<Utf8PathBuf>,
+
+    /// Collate the resources into a tar archive at the given path (relative to the package),
+    /// gzipped when it ends in .tar.gz or .tgz, instead of the resource root
+    #[arg(long, value_name = "FILE", conflicts_with = "zip")]
+    pub tar: Option<Utf8PathBuf>,
+
+    /// The action to perform, collating resources when not given
+    #[command(subcommand)]
+    pub command: Option<ResourceCommand>,
+}
+
+#[derive(Subcommand)]
+pub enum ResourceCommand {
+    /// Print the resources declared by the package itself as JSON
+    Export,
+
+    /// Print the resources declared across the dependency tree, whether required or not
+    List {
+        /// Print the resources as JSON, for tooling
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print the changes collation would make to the resource root, without making them
+    Plan,
+
+    /// Check the resource root is up to date without changing it, failing when it isn't
+    Verify,
+
+    /// Print the chain of dependencies through which each declaration of a resource was found
+    Why {
+        /// The name of the resource
+        resource_name: String,
+    },
+
+    /// Rewrite cargo-resources.lock with the sha of every resource collation would copy
+    UpdateLock,
+
+    /// Remove the resources recorded by the last collation that are no longer required
+    Clean {
+        /// List the resources that would be removed, without removing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+impl ResourceArgs {
+    /// The features selected for resolving dependencies, all features when none are given
+    pub fn feature_selection(&self) -> FeatureSelection {
+        match (&self.features, self.no_default_features) {
+            (Some(features), _) => FeatureSelection::Features(features.to_owned()),
+            (None, true) => FeatureSelection::NoDefaultFeatures,
+            (None, false) => FeatureSelection::AllFeatures,
+        }
+    }
+
+    /// The duplicate strategy asked for, the last declaration scanned winning when none is given
+    pub fn duplicate_strategy(&self) -> DuplicateStrategy {
+        match self.duplicate_strategy.as_deref() {
+            Some("first_wins") => DuplicateStrategy::FirstWins,
+            Some("error") => DuplicateStrategy::Error,
+            Some("highest_version") => DuplicateStrategy::HighestVersion,
+            _ => DuplicateStrategy::LastWins,
+        }
+    }
+
+    /// The record format asked for, compact JSON when none is given, or an error when the format's
+    /// feature isn't enabled
+    pub fn record_format(&self) -> Result<RecordFormat, CollationError> {
+        match self.record_format.as_deref() {
+            Some("pretty_json") => Ok(RecordFormat::PrettyJson),
+            #[cfg(feature = "toml")]
+            Some("toml") => Ok(RecordFormat::Toml),
+            #[cfg(feature = "yaml")]
+            Some("yaml") => Ok(RecordFormat::Yaml),
+            #[cfg(not(feature = "toml"))]
+            Some("toml") => Err(CollationError::FeatureNotEnabled {
+                feature: "toml".to_string(),
+                needed_for: "The toml record format".to_string(),
+            }),
+            #[cfg(not(feature = "yaml"))]
+            Some("yaml") => Err(CollationError::FeatureNotEnabled {
+                feature: "yaml".to_string(),
+                needed_for: "The yaml record format".to_string(),
+            }),
+            _ => Ok(RecordFormat::Json),
+        }
+    }
+
+    /// Where the resources are written: the resource root, or any archive asked for (an error when
+    /// the archive's feature isn't enabled)
+    pub fn output_target(&self) -> Result<OutputTarget, CollationError> {
+        match (&self.zip, &self.tar) {
+            #[cfg(feature = "zip")]
+            (Some(archive), _) => Ok(OutputTarget::ZipArchive(archive.to_owned())),
+            #[cfg(not(feature = "zip"))]
+            (Some(_), _) => Err(CollationError::FeatureNotEnabled {
+                feature: "zip".to_string(),
+                needed_for: "Collating into a zip archive".to_string(),
+            }),
+            #[cfg(feature = "tar")]
+            (None, Some(archive)) => Ok(OutputTarget::TarArchive(archive.to_owned())),
+            #[cfg(not(feature = "tar"))]
+            (None, Some(_)) => Err(CollationError::FeatureNotEnabled {
+                feature: "tar".to_string(),
+                needed_for: "Collating into a tar archive".to_string(),
+            }),
+            (None, None) => Ok(OutputTarget::Directory),
+        }
+    }
+
+    /// Whether progress and problems are reported as JSON
+    pub fn json_messages(&self) -> bool {
+        self.message_format == "json"
+    }
+
+    /// The collation options given by the arguments
+    pub fn collate_options(&self) -> Result<CollateOptions, CollationError> {
+        let options = match (self.json_messages(), self.quiet, self.verbose) {
+            (true, _, _) => CollateOptions::default().reporter(Box::new(JsonReporter)),
+            (false, true, _) => CollateOptions::default().reporter(Box::new(QuietReporter)),
+            (false, false, true) => CollateOptions::default().reporter(Box::new(VerboseReporter)),
+            (false, false, false) => CollateOptions::default(),
+        };
+        let options = options
+            .dry_run(self.dry_run)
+            .prune(self.prune)
+            .always_hash(self.always_hash)
+            .post_verify(self.post_verify)
+            .verify_shas(!self.no_verify)
+            .emit_checksums(self.emit_checksums)
+            .features(self.feature_selection())
+            .offline(self.offline)
+            .frozen(self.frozen)
+            .locked(self.locked)
+            .duplicate_strategy(self.duplicate_strategy())
+            .dependency_scope(match self.direct_only {
+                true => DependencyScope::DirectOnly,
+                false => DependencyScope::All,
+            })
+            .exclude_crates(self.exclude.to_owned())
+            .record_format(self.record_format()?)
+            .write_record(!self.no_record)
+            .output_target(self.output_target()?);
+        let options = match self.deny_unused {
+            true => options.deny_unused(true),
+            false => options,
+        };
+        let options = match &self.resource_root {
+            Some(resource_root) => options.resource_root_override(resource_root.to_owned()),
+            None => options,
+        };
+        let options = match &self.target {
+            Some(target) => options.target(target.to_owned()),
+            None => options,
+        };
+        let options = match &self.record_path {
+            Some(record_path) => options.record_path(record_path.to_owned()),
+            None => options,
+        };
+        let options = match &self.generate_rust_module {
+            Some(module_path) => options.generate_rust_module(module_path.to_owned()),
+            None => options,
+        };
+        let options = match &self.names_manifest {
+            Some(manifest_path) => options.names_manifest(manifest_path.to_owned()),
+            None => options,
+        };
+        let options = options.integrity_algorithm(match self.integrity_algorithm.as_str() {
+            "sha512" => SriAlgorithm::Sha512,
+            _ => SriAlgorithm::Sha384,
+        });
+        Ok(match &self.integrity_manifest {
+            Some(manifest_path) => options.integrity_manifest(manifest_path.to_owned()),
+            None => options,
+        })
+    }
 }
@@ -0,0 +1,88 @@
+use std::fmt::{Display, Formatter};
+
+use cargo_metadata::camino::Utf8PathBuf;
+
+use crate::{RequiredSha, ResourceName, ResourceSha};
+
+/// A way a required resource differs from what collation would put in the resource root.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum ResourceDrift {
+    /// The resource isn't in the resource root
+    Missing {
+        resource_name: ResourceName,
+        output_path: Utf8PathBuf,
+    },
+    /// The resource in the resource root differs from its source
+    OutOfDate {
+        resource_name: ResourceName,
+        output_path: Utf8PathBuf,
+        expected: ResourceSha,
+        actual: ResourceSha,
+    },
+    /// The resource's source doesn't match the sha (or any of the shas) required by the consuming
+    /// crate
+    ShaMismatch {
+        resource_name: ResourceName,
+        expected: RequiredSha,
+        actual: ResourceSha,
+    },
+    /// The resource in the resource root doesn't have the (unix) mode collation gives it
+    ModeMismatch {
+        resource_name: ResourceName,
+        output_path: Utf8PathBuf,
+        expected: u32,
+        actual: u32,
+    },
+    /// The resource isn't in the crate's cargo-resources.lock
+    Unlocked {
+        resource_name: ResourceName,
+        actual: ResourceSha,
+    },
+    /// The resource's sha doesn't match the one in the crate's cargo-resources.lock
+    LockMismatch {
+        resource_name: ResourceName,
+        expected: ResourceSha,
+        actual: ResourceSha,
+    },
+    /// The resource is in the crate's cargo-resources.lock, but is no longer collated
+    NotCollated {
+        resource_name: ResourceName,
+        expected: ResourceSha,
+    },
+}
+
+impl ResourceDrift {
+    /// The name of the resource that has drifted
+    pub fn resource_name(&self) -> &ResourceName {
+        match self {
+            ResourceDrift::Missing { resource_name, .. }
+            | ResourceDrift::OutOfDate { resource_name, .. }
+            | ResourceDrift::ShaMismatch { resource_name, .. }
+            | ResourceDrift::ModeMismatch { resource_name, .. }
+            | ResourceDrift::Unlocked { resource_name, .. }
+            | ResourceDrift::LockMismatch { resource_name, .. }
+            | ResourceDrift::NotCollated { resource_name, .. } => resource_name,
+        }
+    }
+}
+
+impl Display for ResourceDrift {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceDrift::Missing { resource_name, output_path } =>
+                write!(f, "{} is missing from {}", resource_name, output_path),
+            ResourceDrift::OutOfDate { resource_name, output_path, expected, actual } =>
+                write!(f, "{} at {} has sha {} rather than {}", resource_name, output_path, actual, expected),
+            ResourceDrift::ShaMismatch { resource_name, expected, actual } =>
+                write!(f, "{} with sha {} does not match required sha {}", resource_name, actual, expected),
+            ResourceDrift::ModeMismatch { resource_name, output_path, expected, actual } =>
+                write!(f, "{} at {} has mode {:o} rather than {:o}", resource_name, output_path, actual, expected),
+            ResourceDrift::Unlocked { resource_name, actual } =>
+                write!(f, "{} with sha {} is not in the lock file", resource_name, actual),
+            ResourceDrift::LockMismatch { resource_name, expected, actual } =>
+                write!(f, "{} with sha {} does not match locked sha {}", resource_name, actual, expected),
+            ResourceDrift::NotCollated { resource_name, expected } =>
+                write!(f, "{} is locked with sha {} but is no longer collated", resource_name, expected),
+        }
+    }
+}
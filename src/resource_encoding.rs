@@ -1,6 +1,57 @@
+use std::fs::File;
+use std::io::Read;
+
+use cargo_metadata::camino::Utf8Path;
+
 /// What sort of file encoding the resource is using (i.e. text or binary)
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum ResourceEncoding {
     Txt,
     Bin,
+    /// Detected from the file's extension or, failing that, its content (the default)
+    Auto,
+}
+
+/// The extensions of files known to be binary
+const BINARY_EXTENSIONS: &[&str] = &[
+    "7z", "avif", "bin", "bmp", "bz2", "dat", "db", "dll", "dylib", "eot", "exe", "flac", "gif", "gz", "ico", "jpeg",
+    "jpg", "mo", "mov", "mp3", "mp4", "ogg", "otf", "pdf", "png", "so", "sqlite", "tar", "tif", "tiff", "ttf", "wasm",
+    "wav", "webm", "webp", "woff", "woff2", "xz", "zip", "zst",
+];
+
+/// The extensions of files known to be text
+const TEXT_EXTENSIONS: &[&str] = &[
+    "cfg", "conf", "css", "csv", "frag", "ftl", "glsl", "hlsl", "htm", "html", "ini", "js", "json", "md", "mjs", "po",
+    "properties", "rs", "scss", "sh", "sql", "svg", "toml", "ts", "txt", "vert", "wgsl", "xml", "yaml", "yml",
+];
+
+/// How much of a file's content is checked for NUL bytes, when its extension is unknown
+const SNIFF_SIZE: u64 = 8192;
+
+impl ResourceEncoding {
+    /// Resolve the encoding of the file at the given path: Txt and Bin are kept as they are, while
+    /// Auto is detected by the file's extension or, when that isn't known, as binary if the start
+    /// of its content has a NUL byte (text if it can't be read).
+    pub(crate) fn resolve(self, path: &Utf8Path) -> ResourceEncoding {
+        match self {
+            ResourceEncoding::Auto => detect_encoding(path),
+            encoding => encoding,
+        }
+    }
+}
+
+/// Detect whether the file is text or binary, by its extension or content
+fn detect_encoding(path: &Utf8Path) -> ResourceEncoding {
+    let extension = path.extension().map(|extension| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some(extension) if BINARY_EXTENSIONS.contains(&extension) => return ResourceEncoding::Bin,
+        Some(extension) if TEXT_EXTENSIONS.contains(&extension) => return ResourceEncoding::Txt,
+        _ => {}
+    }
+    let mut start = vec!();
+    let read = File::open(path).and_then(|file| file.take(SNIFF_SIZE).read_to_end(&mut start));
+    match read {
+        Ok(_) if start.contains(&0) => ResourceEncoding::Bin,
+        _ => ResourceEncoding::Txt,
+    }
 }
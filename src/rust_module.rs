@@ -0,0 +1,35 @@
+use cargo_metadata::camino::Utf8Path;
+
+use crate::ResourceName;
+
+/// Generate the source of a Rust module mapping each resource name to its output path (relative
+/// to the resource root), e.g. to include from a build script.
+///
+/// The resources are listed in name order, and names and paths written as escaped string literals,
+/// so the same resources always generate the same, valid, source.
+pub(crate) fn generate_rust_module(resources: &[(&ResourceName, &Utf8Path)]) -> String {
+    let mut resources = resources.to_vec();
+    resources.sort();
+
+    let mut source = String::from(
+        "// @generated by cargo-resources, do not edit.\n\
+        \n\
+        /// The collated resources, as (resource name, output path relative to the resource root), in name order\n\
+        pub const RESOURCES: &[(&str, &str)] = &[\n"
+    );
+    for (name, output_path) in &resources {
+        source.push_str(&format!("    ({:?}, {:?}),\n", name, output_path.as_str()));
+    }
+    source.push_str(
+        "];\n\
+        \n\
+        /// The output path (relative to the resource root) of the named resource\n\
+        pub fn resource_path(name: &str) -> Option<&'static str> {\n    \
+            match name {\n"
+    );
+    for (name, output_path) in &resources {
+        source.push_str(&format!("        {:?} => Some({:?}),\n", name, output_path.as_str()));
+    }
+    source.push_str("        _ => None,\n    }\n}\n");
+    source
+}
@@ -1,10 +1,16 @@
-use cargo_metadata::semver::Version;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use cargo_metadata::semver::{Version, VersionReq};
 use cargo_metadata::camino::Utf8PathBuf;
+use crate::hash_algorithm::HashAlgorithm;
+use crate::line_endings::LineEndings;
+use crate::path_style::PathStyle;
 use crate::resource_encoding::ResourceEncoding;
-use crate::{ResourceName, ResourceSha};
+use crate::{CollationError, RequiredSha, ResourceName};
 
 /// The fully populated resource specification (derived from a crate's resource declaration).
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct ResourceSpecification {
     /// The crate identifier
     pub declaring_crate_name: String,
@@ -22,24 +28,271 @@ pub struct ResourceSpecification {
 
     /// The unique name for the resource
     pub resource_name: String,
+
+    /// Whether the collated resource is made executable (on unix)
+    #[serde(default)]
+    pub executable: bool,
+
+    /// The file name the consuming crate has the resource written as, when renamed (the resource
+    /// name being unchanged)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_to: Option<String>,
+
+    /// The output path before the resource's sha was put in its file name, when file names are
+    /// hashed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unhashed_output_path: Option<Utf8PathBuf>,
+
+    /// The named group of the resource, which the consumer may collate into a root of its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// The chain of crates (each as name@version) through which the declaring crate was found,
+    /// from the consuming crate to the declaring crate, for finding out why a resource is included
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependency_chain: Vec<String>,
+}
+
+/// A concise description of the resource, for logging: its name, declaring crate and version, and
+/// output path:
+/// ```
+/// use cargo_metadata::semver::Version;
+/// use cargo_resources::{ResourceEncoding, ResourceSpecification};
+///
+/// let res_dec = ResourceSpecification {
+///     declaring_crate_name: "framework".to_string(),
+///     declaring_crate_version: Version::new(1, 2, 0),
+///     encoding: ResourceEncoding::Txt,
+///     full_crate_path: "/src/framework/framework.css".into(),
+///     output_path: "css/framework.css".into(),
+///     resource_name: "framework.css".to_string(),
+///     executable: false,
+///     renamed_to: None,
+///     unhashed_output_path: None,
+///     group: None,
+///     dependency_chain: vec!(),
+/// };
+/// assert_eq!(res_dec.to_string(), "framework.css (framework v1.2.0) -> css/framework.css");
+/// ```
+impl Display for ResourceSpecification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} v{}) -> {}",
+            self.resource_name,
+            self.declaring_crate_name,
+            self.declaring_crate_version,
+            self.output_path
+        )
+    }
 }
 
 /// The fully populated specification of the consuming package.
-#[derive(serde::Deserialize, Debug)]
+///
+/// The resolved specification (e.g. a [crate::CollationPlan]'s consumer) can be serialized, for
+/// logging or caching, with the defaults filled in and every available resource required when
+/// the consuming crate doesn't list any:
+/// ```no_run
+/// use cargo_metadata::camino::Utf8PathBuf;
+/// use cargo_resources::plan_collation;
+///
+/// let plan = plan_collation(&Utf8PathBuf::from("Cargo.toml")).unwrap();
+/// println!("{}", serde_json::to_string_pretty(&plan.consumer).unwrap());
+/// ```
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ResourceConsumerSpecification {
     /// The relative path of the resource root from the crate root
     pub resource_root: Utf8PathBuf,
 
+    /// The root of each named group of resources, used instead of the resource root for the
+    /// group's resources
+    #[serde(default)]
+    pub roots: BTreeMap<String, Utf8PathBuf>,
+
     /// The required resources
-    pub required_resources: Vec<ResourceRequirement>
+    pub required_resources: Vec<ResourceRequirement>,
+
+    /// Whether copied resources keep the modified time of their source
+    pub preserve_mtime: bool,
+
+    /// Whether copied resources keep the permissions (mode) of their source, on unix
+    #[serde(default)]
+    pub preserve_mode: bool,
+
+    /// The names of resources whose resolution should be traced
+    pub trace: Vec<ResourceName>,
+
+    /// How output paths are expressed in the record of resolved resources
+    pub path_style: PathStyle,
+
+    /// Whether resources whose source can't be read (permission denied) are skipped with a warning
+    pub skip_unreadable: bool,
+
+    /// The optional unix mode (e.g. 0o755) for directories created in the resource root
+    pub directory_mode: Option<u32>,
+
+    /// The optional unix mode (e.g. 0o644) for files copied into the resource root
+    pub file_mode: Option<u32>,
+
+    /// The algorithm used to hash resources (and to check any required sha)
+    pub hash_algorithm: HashAlgorithm,
+
+    /// The line endings text resources are normalized to when copied, when set
+    #[serde(default)]
+    pub line_endings: Option<LineEndings>,
+
+    /// Whether each resource's file name includes the start of its sha, for cache busting
+    #[serde(default)]
+    pub hashed_filenames: bool,
+
+    /// The limit, in bytes, on the combined size of the collated resources, when set
+    #[serde(default)]
+    pub max_total_bytes: Option<u64>,
+
+    /// The limit, in bytes, on the size of any one collated resource, when set
+    #[serde(default)]
+    pub max_file_bytes: Option<u64>,
+}
+
+impl ResourceConsumerSpecification {
+    /// The root the resource is collated into: its group's root, when the consumer gives one, or
+    /// else the resource root:
+    /// ```no_run
+    /// use cargo_metadata::camino::Utf8PathBuf;
+    /// use cargo_resources::plan_collation;
+    ///
+    /// let plan = plan_collation(&Utf8PathBuf::from("Cargo.toml")).unwrap();
+    /// for planned in &plan.resources {
+    ///     println!("{} -> {}", planned.specification.resource_name, plan.consumer.root_for(&planned.specification));
+    /// }
+    /// ```
+    pub fn root_for(&self, res_dec: &ResourceSpecification) -> &Utf8PathBuf {
+        res_dec.group.as_ref()
+            .and_then(|group| self.roots.get(group))
+            .unwrap_or(&self.resource_root)
+    }
 }
 
 /// The fully populated specification for a resource usage.
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct ResourceRequirement {
     /// The unique name of the required resource
     pub resource_name: ResourceName,
 
-    /// The optional hex-encoded SHA256 value of the required resource
-    pub required_sha: Option<ResourceSha>
+    /// The optional hex-encoded hash (or hashes, any of which is accepted) of the required resource
+    /// (compared against the source file), prefixed by the algorithm when not the consumer's
+    /// hash_algorithm, e.g. sha512:...
+    pub required_sha: Option<RequiredSha>,
+
+    /// The optional encoding to use for this consumer, overriding the providing crate's declaration
+    pub encoding: Option<ResourceEncoding>,
+
+    /// The optional name of the crate the resource must be declared by, when more than one declares it
+    pub from_crate: Option<String>,
+
+    /// The optional versions of the declaring crate the resource must come from, e.g. ">=2.0"
+    pub version_req: Option<VersionReq>,
+
+    /// Whether the resource is skipped, rather than failing collation, when no crate declares it
+    #[serde(default)]
+    pub optional: bool,
+
+    /// The optional output path to use instead of the declaring crate's
+    #[serde(default)]
+    pub output_path_override: Option<Utf8PathBuf>,
+
+    /// The optional file name to write the resource as, instead of its output path's
+    #[serde(default)]
+    pub rename_to: Option<String>,
+
+    /// The optional group to collate the resource with, instead of the declaring crate's
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+impl ResourceRequirement {
+    /// The algorithm and hex digests of the required shas, if any: the algorithm named by their
+    /// prefix (sha256:, sha512: or blake3:), or the consumer's hash_algorithm for a bare hex hash.
+    ///
+    /// An unknown prefix, a digest of the wrong length for the algorithm named, an empty list or
+    /// shas of more than one algorithm is an error:
+    /// ```
+    /// use cargo_resources::{HashAlgorithm, RequiredSha, ResourceRequirement};
+    ///
+    /// let sha512 = "0".repeat(128);
+    /// let mut res_req = ResourceRequirement {
+    ///     resource_name: "framework.css".to_string(),
+    ///     required_sha: Some(RequiredSha::One(format!("sha512:{}", sha512))),
+    ///     encoding: None,
+    ///     from_crate: None,
+    ///     version_req: None,
+    ///     optional: false,
+    ///     output_path_override: None,
+    ///     rename_to: None,
+    ///     group: None,
+    /// };
+    /// let digests = res_req.required_digests(HashAlgorithm::Sha256).unwrap();
+    /// assert_eq!(digests, Some((HashAlgorithm::Sha512, vec!(sha512.as_str()))));
+    ///
+    /// let old_sha = "62368a1a29259b30bac235c0e75dc700c9b3bacf1513ad5708e4fe4a6c0d6560";
+    /// let new_sha = "0".repeat(64);
+    /// res_req.required_sha = Some(RequiredSha::AnyOf(vec!(old_sha.to_string(), new_sha.to_owned())));
+    /// let (hash_algorithm, digests) = res_req.required_digests(HashAlgorithm::Sha256).unwrap().unwrap();
+    /// assert_eq!(hash_algorithm, HashAlgorithm::Sha256);
+    /// assert_eq!(digests, [old_sha, new_sha.as_str()]);
+    ///
+    /// res_req.required_sha = Some(RequiredSha::One(format!("md5:{}", "0".repeat(32))));
+    /// assert!(res_req.required_digests(HashAlgorithm::Sha256).is_err());
+    /// res_req.required_sha = Some(RequiredSha::One(format!("blake3:{}", sha512)));
+    /// assert!(res_req.required_digests(HashAlgorithm::Sha256).is_err());
+    /// res_req.required_sha = Some(RequiredSha::AnyOf(vec!(old_sha.to_string(), format!("sha512:{}", sha512))));
+    /// assert!(res_req.required_digests(HashAlgorithm::Sha256).is_err());
+    /// ```
+    pub fn required_digests(&self, hash_algorithm: HashAlgorithm) -> Result<Option<(HashAlgorithm, Vec<&str>)>, CollationError> {
+        let Some(required_sha) = &self.required_sha else { return Ok(None) };
+        let digests = required_sha.shas().iter()
+            .map(|sha| self.parse_required_sha(sha, hash_algorithm))
+            .collect::<Result<Vec<(HashAlgorithm, &str)>, CollationError>>()?;
+
+        let Some((digest_algorithm, _)) = digests.first().copied() else {
+            Err(CollationError::InvalidRequiredSha {
+                resource: self.resource_name.to_owned(),
+                required_sha: required_sha.to_string(),
+                reason: "lists no shas".to_string(),
+            })?
+        };
+        if digests.iter().any(|(algorithm, _)| *algorithm != digest_algorithm) {
+            Err(CollationError::InvalidRequiredSha {
+                resource: self.resource_name.to_owned(),
+                required_sha: required_sha.to_string(),
+                reason: "must all use the same hash algorithm".to_string(),
+            })?
+        }
+        Ok(Some((digest_algorithm, digests.into_iter().map(|(_, digest)| digest).collect())))
+    }
+
+    /// Split a required sha into the algorithm named by its prefix (or the given one when bare)
+    /// and its hex digest
+    fn parse_required_sha<'a>(&self, required_sha: &'a str, hash_algorithm: HashAlgorithm) -> Result<(HashAlgorithm, &'a str), CollationError> {
+        let Some((prefix, digest)) = required_sha.split_once(':') else {
+            return Ok((hash_algorithm, required_sha));
+        };
+
+        let named_algorithm = [HashAlgorithm::Sha256, HashAlgorithm::Sha512, HashAlgorithm::Blake3]
+            .into_iter()
+            .find(|algorithm| algorithm.name() == prefix)
+            .ok_or_else(|| CollationError::InvalidRequiredSha {
+                resource: self.resource_name.to_owned(),
+                required_sha: required_sha.to_string(),
+                reason: format!("names an unknown hash algorithm {} (expected sha256, sha512 or blake3)", prefix),
+            })?;
+        if digest.len() != named_algorithm.hex_digits() || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            Err(CollationError::InvalidRequiredSha {
+                resource: self.resource_name.to_owned(),
+                required_sha: required_sha.to_string(),
+                reason: format!("should be {} hex digits for {}", named_algorithm.hex_digits(), prefix),
+            })?
+        }
+        Ok((named_algorithm, digest))
+    }
 }
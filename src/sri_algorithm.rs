@@ -0,0 +1,28 @@
+/// The hash used for Subresource Integrity (SRI) strings, as browsers accept in integrity attributes
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SriAlgorithm {
+    /// SHA-384, as recommended for SRI
+    #[default]
+    Sha384,
+    /// SHA-512
+    Sha512,
+}
+
+impl SriAlgorithm {
+    /// The prefix of an SRI string using the algorithm, e.g. "sha384"
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            SriAlgorithm::Sha384 => "sha384",
+            SriAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// The algorithm's implementation
+    pub(crate) fn digest_algorithm(&self) -> &'static ring::digest::Algorithm {
+        match self {
+            SriAlgorithm::Sha384 => &ring::digest::SHA384,
+            SriAlgorithm::Sha512 => &ring::digest::SHA512,
+        }
+    }
+}
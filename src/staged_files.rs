@@ -0,0 +1,96 @@
+use std::fs;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{create_output_directory, temporary_path, CollationError};
+
+/// The files collation generates beside the resources (the record, lock, checksum files and
+/// manifests), written as a group so they are always consistent with each other.
+///
+/// Each file is first staged, written to a temporary file beside it, and only once every file is
+/// staged are they moved into place together. Files still staged when the group is dropped (e.g.
+/// as a later step failed) are removed, leaving the existing files untouched.
+#[derive(Default)]
+pub(crate) struct StagedFiles {
+    /// The temporary file staged for each file, with the file's path, in staging order
+    staged: Vec<(Utf8PathBuf, Utf8PathBuf)>,
+}
+
+impl StagedFiles {
+    /// Stage the file with the given contents, unless it already has them (so a file that is up to
+    /// date isn't touched, e.g. for code that includes it), creating its directory
+    pub(crate) fn stage(&mut self, path: &Utf8Path, contents: impl AsRef<[u8]>) -> Result<(), CollationError> {
+        let contents = contents.as_ref();
+        if fs::read(path).is_ok_and(|existing| existing == contents) {
+            return Ok(());
+        }
+
+        if let Some(directory) = path.parent() {
+            create_output_directory(directory, None)?;
+        }
+        let temporary = temporary_path(path, "tmp");
+        if let Err(e) = fs::write(&temporary, contents) {
+            let _ = fs::remove_file(&temporary);
+            Err(CollationError::io(format!("Unable to write {}", path), e))?
+        }
+        self.staged.push((temporary, path.to_owned()));
+        Ok(())
+    }
+
+    /// Move every staged file into place.
+    ///
+    /// Each existing file is set aside first, so that when a file can't be moved into place the
+    /// files moved before it are restored (or removed, when they are new): either every file is
+    /// replaced or none is.
+    pub(crate) fn commit(mut self) -> Result<(), CollationError> {
+        let mut moved: Vec<(Utf8PathBuf, Option<Utf8PathBuf>)> = vec!();
+        let mut staged = std::mem::take(&mut self.staged).into_iter();
+        while let Some((temporary, path)) = staged.next() {
+            if let Err(error) = replace_file(&temporary, &path).map(|set_aside| moved.push((path, set_aside))) {
+                let _ = fs::remove_file(&temporary);
+                staged.for_each(|(temporary, _)| { let _ = fs::remove_file(temporary); });
+                for (path, set_aside) in moved.into_iter().rev() {
+                    let _ = match set_aside {
+                        Some(set_aside) => fs::rename(set_aside, &path),
+                        None => fs::remove_file(&path),
+                    };
+                }
+                return Err(error);
+            }
+        }
+
+        for set_aside in moved.into_iter().filter_map(|(_, set_aside)| set_aside) {
+            let _ = fs::remove_file(set_aside);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StagedFiles {
+    fn drop(&mut self) {
+        for (temporary, _) in &self.staged {
+            let _ = fs::remove_file(temporary);
+        }
+    }
+}
+
+/// Move a temporary file into place at the path, setting aside any existing file (but not a
+/// directory, which can't be replaced) first, giving where it was set aside
+fn replace_file(temporary: &Utf8Path, path: &Utf8Path) -> Result<Option<Utf8PathBuf>, CollationError> {
+    let set_aside = match path.symlink_metadata() {
+        Ok(metadata) if !metadata.is_dir() => {
+            let set_aside = temporary_path(path, "old");
+            fs::rename(path, &set_aside)
+                .map_err(|e| CollationError::io(format!("Unable to set aside {}", path), e))?;
+            Some(set_aside)
+        }
+        _ => None,
+    };
+    if let Err(e) = fs::rename(temporary, path) {
+        if let Some(set_aside) = &set_aside {
+            let _ = fs::rename(set_aside, path);
+        }
+        Err(CollationError::io(format!("Unable to move {} into place at {}", temporary, path), e))?
+    }
+    Ok(set_aside)
+}
@@ -0,0 +1,527 @@
+mod common;
+
+use std::fs;
+
+use cargo_resources::{
+    collate_resources, collate_to_memory, collate_with_options, file_sha, list_available_resources, plan_collation,
+    update_lock, CollateOptions, CollationError, HashAlgorithm, LineEndings, ResourceConsumerSpecification,
+    DEFAULT_RECORD_FILE_NAME,
+};
+
+use common::Fixture;
+
+#[test]
+fn record_is_identical_when_collated_again() {
+    let fixture = Fixture::new("record_order")
+        .file("c.txt", "c")
+        .file("a.txt", "a")
+        .file("b.txt", "b")
+        .package(r#"provides = [{ crate_path = "c.txt" }, { crate_path = "a.txt" }, { crate_path = "b.txt" }]"#);
+    let summary = collate_resources(&fixture.manifest_file()).unwrap();
+    let record_file = summary.resource_root.join(DEFAULT_RECORD_FILE_NAME);
+    let first_record = fs::read(&record_file).unwrap();
+
+    collate_resources(&fixture.manifest_file()).unwrap();
+    assert_eq!(first_record, fs::read(&record_file).unwrap());
+}
+
+#[test]
+fn resources_nest_below_an_unnormalized_root() {
+    let fixture = Fixture::new("nested_root")
+        .file("app.js", "run()")
+        .package(r#"provides = [{ crate_path = "app.js", output_path = "static/js/v1/app.js" }]"#);
+    let resource_root = fixture.path("target/../target/./resources");
+    let options = CollateOptions::default().resource_root_override(resource_root.clone());
+
+    let summary = collate_with_options(&fixture.manifest_file(), options).unwrap();
+    assert_eq!(summary.copied.len(), 1);
+    assert!(resource_root.join("static/js/v1/app.js").is_file());
+}
+
+#[test]
+fn escaping_output_path_is_rejected_before_anything_is_created() {
+    let fixture = Fixture::new("escaping_output_path")
+        .file("escape.txt", "out")
+        .package(r#"provides = [{ crate_path = "escape.txt", output_path = "nested/../../../escaped/escape.txt" }]"#);
+    assert!(collate_with_options(&fixture.manifest_file(), CollateOptions::default().dry_run(true)).is_err());
+
+    assert!(collate_with_options(&fixture.manifest_file(), CollateOptions::default()).is_err());
+    assert!(!fixture.path("target/resources/nested").exists());
+    assert!(!fixture.path("escaped").exists());
+}
+
+#[test]
+fn traversing_declarations_name_the_declaring_crate() {
+    let fixture = Fixture::new("traversal")
+        .file("passwd", "")
+        .package(r#"provides = [{ crate_path = "passwd", output_path = "../../etc/passwd" }]"#);
+    let error = collate_resources(&fixture.manifest_file()).unwrap_err();
+    assert!(error.to_string().contains("traversal declares an output path ../../etc/passwd"));
+    assert!(matches!(error, CollationError::ResourceOutsideRoot { root: None, .. }));
+
+    let fixture = fixture.package(r#"provides = [{ crate_path = "passwd", resource_name = "../passwd" }]"#);
+    let error = collate_resources(&fixture.manifest_file()).unwrap_err();
+    assert!(error.to_string().contains("traversal declares a resource name ../passwd"));
+    assert!(matches!(error, CollationError::PathSeparatorInName { .. }));
+}
+
+#[test]
+fn invalid_declarations_have_typed_errors() {
+    let fixture = Fixture::new("invalid_declarations").file("app.css", "body {}");
+
+    let fixture = fixture.package(r#"provides = [{ crate_path = "/etc/passwd" }]"#);
+    let error = collate_resources(&fixture.manifest_file()).unwrap_err();
+    assert!(matches!(error, CollationError::AbsoluteResourcePath { .. }));
+
+    let fixture = fixture.package(r#"provides = [{ crate_path = "*.js" }]"#);
+    let error = collate_resources(&fixture.manifest_file()).unwrap_err();
+    assert!(matches!(error, CollationError::UnmatchedGlob { .. }));
+
+    let fixture = fixture.package(r#"provides = [{ crate_path = "${CARGO_RESOURCES_TEST_UNSET}/app.css" }]"#);
+    match collate_resources(&fixture.manifest_file()).unwrap_err() {
+        CollationError::UnsetVariable { crate_name, variable, .. } => {
+            assert_eq!(crate_name, "invalid_declarations");
+            assert_eq!(variable, "CARGO_RESOURCES_TEST_UNSET");
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+
+    let fixture = fixture.package(r#"provides = { crate_path = "app.css" }"#);
+    let error = collate_resources(&fixture.manifest_file()).unwrap_err();
+    assert!(matches!(error, CollationError::MalformedMetadata { .. }));
+}
+
+#[test]
+fn environment_variables_are_expanded_in_declared_paths() {
+    std::env::set_var("CARGO_RESOURCES_TEST_STYLES", "styles");
+    std::env::set_var("CARGO_RESOURCES_TEST_SITE", "site");
+    let fixture = Fixture::new("expanded_paths")
+        .file("styles/app.css", "body {}")
+        .file("styles/print.css", "@media print {}")
+        .package(r#"provides = [
+    { crate_path = "${CARGO_RESOURCES_TEST_STYLES}/app.css", output_path = "$CARGO_RESOURCES_TEST_SITE/app.css" },
+    { crate_path = "$CARGO_RESOURCES_TEST_STYLES/print.css" },
+]"#);
+    collate_resources(&fixture.manifest_file()).unwrap();
+
+    assert_eq!(fixture.read("target/resources/site/app.css"), "body {}");
+    assert_eq!(fixture.read("target/resources/styles/print.css"), "@media print {}");
+}
+
+#[test]
+fn generated_sources_are_output_below_their_variable() {
+    let out_dir = Fixture::new("generated_out_dir")
+        .file("css/generated.css", "body {}")
+        .file("icons/logo.svg", "<svg/>");
+    std::env::set_var("CARGO_RESOURCES_TEST_OUT_DIR", out_dir.path(""));
+    let fixture = Fixture::new("generated_sources")
+        .package(r#"provides = [
+    { crate_path = "${CARGO_RESOURCES_TEST_OUT_DIR}/css/generated.css" },
+    { crate_path = "$CARGO_RESOURCES_TEST_OUT_DIR/icons/*.svg" },
+]"#);
+    let summary = collate_resources(&fixture.manifest_file()).unwrap();
+
+    assert_eq!(summary.copied.len(), 2);
+    assert_eq!(fixture.read("target/resources/css/generated.css"), "body {}");
+    assert_eq!(fixture.read("target/resources/icons/logo.svg"), "<svg/>");
+}
+
+#[test]
+fn malformed_variables_are_errors() {
+    std::env::set_var("CARGO_RESOURCES_TEST_ROOT", "/");
+    let fixture = Fixture::new("malformed_variables").file("app.css", "body {}");
+
+    let fixture = fixture.package(r#"provides = [{ crate_path = "${CARGO_RESOURCES_TEST_STYLES/app.css" }]"#);
+    let error = collate_resources(&fixture.manifest_file()).unwrap_err();
+    assert!(matches!(error, CollationError::UnclosedVariable { .. }));
+
+    let fixture = fixture.package(r#"provides = [{ crate_path = "$CARGO_RESOURCES_TEST_UNSET_TOO/app.css" }]"#);
+    match collate_resources(&fixture.manifest_file()).unwrap_err() {
+        CollationError::UnsetVariable { variable, .. } => assert_eq!(variable, "CARGO_RESOURCES_TEST_UNSET_TOO"),
+        error => panic!("Unexpected error: {}", error),
+    }
+
+    let fixture = fixture.package(
+        r#"provides = [{ crate_path = "app.css", output_path = "${CARGO_RESOURCES_TEST_ROOT}app.css" }]"#
+    );
+    let error = collate_resources(&fixture.manifest_file()).unwrap_err();
+    assert!(matches!(error, CollationError::AbsoluteOutputPath { .. }));
+}
+
+#[test]
+fn every_invalid_declaration_is_reported_at_once() {
+    let fixture = Fixture::new("invalid_declarations_at_once")
+        .file("app.css", "body {}")
+        .package(r#"
+provides = [
+    { crate_path = "app.css", output_path = "../app.css" },
+    { crate_path = "app.css", resource_name = "css/app.css" },
+    { crate_path = "*.js" },
+    { crate_path = "app.css" },
+]
+"#);
+    match collate_resources(&fixture.manifest_file()).unwrap_err() {
+        CollationError::MultipleDeclarationErrors(errors) => {
+            assert_eq!(errors.len(), 3);
+            assert!(matches!(errors[0], CollationError::ResourceOutsideRoot { .. }));
+            assert!(matches!(errors[1], CollationError::PathSeparatorInName { .. }));
+            assert!(matches!(errors[2], CollationError::UnmatchedGlob { .. }));
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+    assert!(!fixture.path("target/resources").exists());
+}
+
+#[test]
+fn misconfigured_consumer_is_an_error_rather_than_a_panic() {
+    let fixture = Fixture::new("misconfigured_consumer")
+        .file("src/lib.rs", "")
+        .file("Cargo.toml", r#"[package]
+name = "misconfigured_consumer"
+version = "0.1.0"
+
+[package.metadata]
+cargo_resources = "target/resources"
+
+[workspace]
+"#);
+    match collate_resources(&fixture.manifest_file()).unwrap_err() {
+        CollationError::MalformedMetadata { crate_name, key } => {
+            assert_eq!(crate_name, "misconfigured_consumer");
+            assert_eq!(key, "cargo_resources");
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+}
+
+#[test]
+fn patched_providers_resources_come_from_the_patch() {
+    let fixture = Fixture::new("patched_provider")
+        .file("Cargo.toml", r#"
+[workspace]
+members = ["app"]
+exclude = ["provider"]
+
+[patch.crates-io]
+provider = { path = "provider" }
+"#)
+        .member("app", "0.1.0", r#"provider = "0.1""#, "")
+        .member("provider", "0.1.0", "", r#"provides = [{ crate_path = "greeting.txt" }]"#)
+        .file("provider/greeting.txt", "Hello from the patch");
+    let options = CollateOptions::default().offline(true);
+    let summary = collate_with_options(&fixture.path("app/Cargo.toml"), options).unwrap();
+
+    assert_eq!(summary.copied[0].specification.full_crate_path, fixture.path("provider/greeting.txt"));
+    assert_eq!(fixture.read("app/target/resources/greeting.txt"), "Hello from the patch");
+}
+
+#[test]
+fn dependencies_with_traversing_declarations_are_named() {
+    let fixture = Fixture::new("dependency_traversal")
+        .workspace(&["app", "crafted"])
+        .member("app", "0.1.0", r#"crafted = { path = "../crafted" }"#, "")
+        .member("crafted", "0.1.0", "", r#"provides = [{ crate_path = "payload", output_path = "assets/../../../payload" }]"#)
+        .file("crafted/payload", "");
+    match collate_resources(&fixture.path("app/Cargo.toml")).unwrap_err() {
+        CollationError::ResourceOutsideRoot { path, root, crate_name } => {
+            assert_eq!(path, "assets/../../../payload");
+            assert_eq!(root, None);
+            assert_eq!(crate_name.as_deref(), Some("crafted"));
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+    assert!(!fixture.path("app/target/resources").exists());
+
+    let fixture = fixture
+        .member("crafted", "0.1.0", "", r#"provides = [{ crate_path = "payload", resource_name = 'assets\payload' }]"#);
+    match collate_resources(&fixture.path("app/Cargo.toml")).unwrap_err() {
+        CollationError::PathSeparatorInName { crate_name, resource_name } => {
+            assert_eq!(crate_name, "crafted");
+            assert_eq!(resource_name, "assets\\payload");
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn links_out_of_the_resource_root_are_rejected_before_anything_is_created() {
+    let fixture = Fixture::new("linked_out_of_root")
+        .file("app.css", "body {}")
+        .file("outside/.keep", "")
+        .package(r#"provides = [{ crate_path = "app.css", output_path = "linked/css/app.css" }]"#);
+    fs::create_dir_all(fixture.path("target/resources")).unwrap();
+    std::os::unix::fs::symlink(fixture.path("outside"), fixture.path("target/resources/linked")).unwrap();
+
+    match collate_resources(&fixture.manifest_file()).unwrap_err() {
+        CollationError::ResourceOutsideRoot { path, root, crate_name: None } => {
+            assert_eq!(path, fixture.root.canonicalize_utf8().unwrap().join("outside/css/app.css"));
+            assert_eq!(root, Some(fixture.path("target/resources").canonicalize_utf8().unwrap()));
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+    assert!(!fixture.path("outside/css").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn a_linked_resource_root_is_resolved_before_checking_paths_are_in_it() {
+    let fixture = Fixture::new("linked_root")
+        .file("app.css", "body {}")
+        .file("site/.keep", "")
+        .package(r#"provides = [{ crate_path = "app.css", output_path = "css/app.css" }]"#);
+    std::os::unix::fs::symlink(fixture.path("site"), fixture.path("linked_site")).unwrap();
+    let options = CollateOptions::default().resource_root_override(fixture.path("linked_site/./resources/../"));
+
+    collate_with_options(&fixture.manifest_file(), options).unwrap();
+    assert_eq!(fixture.read("site/css/app.css"), "body {}");
+}
+
+#[test]
+fn collate_to_memory_normalizes_without_writing() {
+    let fixture = Fixture::new("memory")
+        .file("greeting.txt", "hello\r\nworld\r\n")
+        .package(r#"
+line_endings = "lf"
+provides = [{ crate_path = "greeting.txt" }]
+"#);
+    let resources = collate_to_memory(&fixture.manifest_file()).unwrap();
+
+    assert_eq!(resources["greeting.txt"], b"hello\nworld\n");
+    assert!(!fixture.path("target/resources").exists());
+}
+
+#[test]
+fn mixed_line_endings_are_normalized_leaving_lone_carriage_returns() {
+    let fixture = Fixture::new("mixed_line_endings")
+        .file("mixed.txt", "one\r\ntwo\nthree\rstill three\r\n\nfive")
+        .file("mixed.bin", "one\r\ntwo\nthree\r")
+        .package(r#"provides = [{ crate_path = "mixed.txt" }, { crate_path = "mixed.bin", encoding = "Bin" }]"#);
+
+    let options = CollateOptions::default().line_endings(LineEndings::Lf);
+    collate_with_options(&fixture.manifest_file(), options).unwrap();
+    assert_eq!(fixture.read("target/resources/mixed.txt"), "one\ntwo\nthree\rstill three\n\nfive");
+    assert_eq!(fixture.read("target/resources/mixed.bin"), "one\r\ntwo\nthree\r");
+
+    let options = CollateOptions::default().line_endings(LineEndings::Crlf);
+    let summary = collate_with_options(&fixture.manifest_file(), options).unwrap();
+    assert_eq!(summary.copied.len(), 1);
+    assert_eq!(fixture.read("target/resources/mixed.txt"), "one\r\ntwo\r\nthree\rstill three\r\n\r\nfive");
+    assert_eq!(fixture.read("target/resources/mixed.bin"), "one\r\ntwo\nthree\r");
+
+    // The normalized copy is up to date, though it differs from the source
+    let options = CollateOptions::default().line_endings(LineEndings::Crlf);
+    let summary = collate_with_options(&fixture.manifest_file(), options).unwrap();
+    assert_eq!(summary.skipped.len(), 2);
+}
+
+#[test]
+fn lone_carriage_returns_are_not_line_endings() {
+    let fixture = Fixture::new("lone_carriage_returns")
+        .file("progress.txt", "\r10%\r50%\r100%\r")
+        .package(r#"
+line_endings = "crlf"
+provides = [{ crate_path = "progress.txt" }]
+"#);
+    let resources = collate_to_memory(&fixture.manifest_file()).unwrap();
+
+    assert_eq!(resources["progress.txt"], b"\r10%\r50%\r100%\r");
+}
+
+#[test]
+fn available_resources_are_listed_without_copying() {
+    let fixture = Fixture::new("list_available")
+        .file("framework.css", "body {}")
+        .package(r#"provides = [{ crate_path = "framework.css", output_path = "css/framework.css" }]"#);
+    let available = list_available_resources(&fixture.manifest_file()).unwrap();
+
+    assert_eq!(available.len(), 1);
+    assert_eq!(available[0].resource_name, "framework.css");
+    assert_eq!(available[0].declaring_crate_name, "list_available");
+    assert_eq!(available[0].output_path, "css/framework.css");
+    assert_eq!(available[0].dependency_chain, ["list_available@0.1.0"]);
+    assert!(!fixture.path("target/resources").exists());
+}
+
+#[test]
+fn consumer_specification_round_trips_with_defaults() {
+    let fixture = Fixture::new("consumer_specification")
+        .file("framework.css", "body {}")
+        .package(r#"provides = [{ crate_path = "framework.css", output_path = "css/framework.css" }]"#);
+    let plan = plan_collation(&fixture.manifest_file()).unwrap();
+    let json = serde_json::to_string(&plan.consumer).unwrap();
+
+    let consumer: ResourceConsumerSpecification = serde_json::from_str(&json).unwrap();
+    assert_eq!(consumer.resource_root, fixture.path("target/resources"));
+    assert_eq!(consumer.required_resources[0].resource_name, "framework.css");
+    assert_eq!(serde_json::to_string(&consumer).unwrap(), json);
+}
+
+#[test]
+fn grouped_resources_collate_into_their_group_root() {
+    let fixture = Fixture::new("group_roots")
+        .file("app.css", "body {}")
+        .file("words.txt", "words")
+        .package(r#"
+roots = { static = "target/static" }
+provides = [{ crate_path = "app.css", group = "static" }, { crate_path = "words.txt" }]
+"#);
+    let plan = plan_collation(&fixture.manifest_file()).unwrap();
+    let roots: Vec<_> = plan.resources.iter()
+        .map(|planned| plan.consumer.root_for(&planned.specification).to_owned())
+        .collect();
+    assert_eq!(roots, [fixture.path("target/static"), fixture.path("target/resources")]);
+
+    collate_resources(&fixture.manifest_file()).unwrap();
+    assert!(fixture.path("target/static/app.css").is_file());
+    assert!(fixture.path("target/resources/words.txt").is_file());
+}
+
+#[test]
+fn malformed_manifest_is_a_metadata_error() {
+    let fixture = Fixture::new("malformed_manifest").file("Cargo.toml", "[package\nname = ");
+
+    let error = collate_resources(&fixture.manifest_file()).unwrap_err();
+    assert!(matches!(error, CollationError::Metadata(_)));
+}
+
+#[test]
+fn size_budgets_fail_collation() {
+    let fixture = Fixture::new("size_budget")
+        .file("small.txt", "small")
+        .file("large.txt", "much larger");
+    let provides = r#"provides = [{ crate_path = "small.txt" }, { crate_path = "large.txt" }]"#;
+
+    let fixture = fixture.package(provides);
+    assert_eq!(collate_resources(&fixture.manifest_file()).unwrap().total_bytes, 16);
+    fs::remove_dir_all(fixture.path("target/resources")).unwrap();
+
+    // Nothing is written when the resources are over budget
+    let fixture = fixture.package(&format!("max_total_bytes = 12\n{}", provides));
+    match collate_resources(&fixture.manifest_file()).unwrap_err() {
+        CollationError::SizeBudgetExceeded { total_bytes, largest, .. } => {
+            assert_eq!(total_bytes, 16);
+            assert_eq!(largest[0], ("large.txt".to_string(), 11));
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+
+    let fixture = fixture.package(&format!("max_file_bytes = 8\n{}", provides));
+    let error = collate_resources(&fixture.manifest_file()).unwrap_err();
+    assert!(matches!(error, CollationError::ResourceTooLarge { size: 11, .. }));
+    assert!(!fixture.path("target/resources").exists());
+}
+
+#[test]
+fn file_sha_matches_the_collated_sha() {
+    let fixture = Fixture::new("file_sha")
+        .file("framework.css", "body {}")
+        .package(r#"provides = [{ crate_path = "framework.css" }]"#);
+
+    // The sha256 of "body {}"
+    let sha = file_sha(&fixture.path("framework.css"), HashAlgorithm::Sha256).unwrap();
+    assert_eq!(sha, "62368a1a29259b30bac235c0e75dc700c9b3bacf1513ad5708e4fe4a6c0d6560");
+    assert_eq!(collate_resources(&fixture.manifest_file()).unwrap().copied[0].output_sha, sha);
+}
+
+#[test]
+fn update_lock_relocks_changed_resources() {
+    let fixture = Fixture::new("update_lock")
+        .file("app.css", "body {}")
+        .package(r#"provides = [{ crate_path = "app.css" }]"#);
+    let lock_file = update_lock(&fixture.manifest_file(), CollateOptions::default()).unwrap();
+
+    let lock = fs::read_to_string(&lock_file).unwrap();
+    assert!(lock.contains(r#""app.css": "62368a1a29259b30bac235c0e75dc700c9b3bacf1513ad5708e4fe4a6c0d6560""#));
+
+    let fixture = fixture.file("app.css", "body { margin: 0 }");
+    assert!(collate_with_options(&fixture.manifest_file(), CollateOptions::default().locked(true)).is_err());
+    update_lock(&fixture.manifest_file(), CollateOptions::default()).unwrap();
+    collate_with_options(&fixture.manifest_file(), CollateOptions::default().locked(true)).unwrap();
+}
+
+#[test]
+fn resources_with_the_same_output_path_are_rejected_before_copying() {
+    let fixture = Fixture::new("duplicate_output_path")
+        .file("framework.css", "body {}")
+        .file("print.css", "@media print {}")
+        .package(r#"
+roots = { static = "target/static" }
+provides = [
+    { crate_path = "framework.css", output_path = "site.css" },
+    { crate_path = "print.css", output_path = "site.css", resource_name = "print" },
+]
+"#);
+    match collate_resources(&fixture.manifest_file()).unwrap_err() {
+        CollationError::DuplicateOutputPath { output_path, resources } => {
+            assert_eq!(output_path, fixture.path("target/resources/site.css"));
+            assert_eq!(resources, ["framework.css", "print"]);
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+    assert!(!fixture.path("target/resources/site.css").exists());
+
+    // The same output path in another root is another file
+    let fixture = fixture.package(r#"
+roots = { static = "target/static" }
+provides = [
+    { crate_path = "framework.css", output_path = "site.css" },
+    { crate_path = "print.css", output_path = "site.css", resource_name = "print", group = "static" },
+]
+"#);
+    assert_eq!(collate_resources(&fixture.manifest_file()).unwrap().copied.len(), 2);
+}
+
+#[test]
+fn sources_changed_without_a_newer_modified_time_are_copied_again() {
+    let fixture = Fixture::new("unchanged_by_time")
+        .file("greeting.txt", "hello")
+        .package(r#"provides = [{ crate_path = "greeting.txt" }]"#);
+    let summary = collate_resources(&fixture.manifest_file()).unwrap();
+    assert_eq!(summary.copied.len(), 1);
+    let modified = fs::metadata(fixture.path("target/resources/greeting.txt")).unwrap().modified().unwrap();
+
+    // Unchanged, the copy is up to date
+    let summary = collate_resources(&fixture.manifest_file()).unwrap();
+    assert_eq!(summary.skipped.len(), 1);
+
+    // Edited to the same size, and restored to a time before the copy was made
+    fs::write(fixture.path("greeting.txt"), "howdy").unwrap();
+    let source = fs::File::options().write(true).open(fixture.path("greeting.txt")).unwrap();
+    source.set_modified(modified - std::time::Duration::from_secs(60)).unwrap();
+    let summary = collate_resources(&fixture.manifest_file()).unwrap();
+    assert_eq!(summary.copied.len(), 1);
+    assert_eq!(fixture.read("target/resources/greeting.txt"), "howdy");
+
+    // Edited to the same size, with the same modified time as the copy
+    fs::write(fixture.path("greeting.txt"), "hiya!").unwrap();
+    let copied = fs::metadata(fixture.path("target/resources/greeting.txt")).unwrap().modified().unwrap();
+    let source = fs::File::options().write(true).open(fixture.path("greeting.txt")).unwrap();
+    source.set_modified(copied).unwrap();
+    let summary = collate_resources(&fixture.manifest_file()).unwrap();
+    assert_eq!(summary.copied.len(), 1);
+    assert_eq!(fixture.read("target/resources/greeting.txt"), "hiya!");
+}
+
+#[test]
+fn sources_unchanged_since_recorded_are_not_read_again() {
+    let fixture = Fixture::new("unchanged_not_read")
+        .file("greeting.txt", "hello")
+        .package(r#"provides = [{ crate_path = "greeting.txt" }]"#);
+    collate_resources(&fixture.manifest_file()).unwrap();
+    let modified = fs::metadata(fixture.path("greeting.txt")).unwrap().modified().unwrap();
+
+    // Rewritten keeping its size and modified time, the source is taken to be unchanged unread, so
+    // the new content isn't seen
+    fs::write(fixture.path("greeting.txt"), "howdy").unwrap();
+    let source = fs::File::options().write(true).open(fixture.path("greeting.txt")).unwrap();
+    source.set_modified(modified).unwrap();
+    let summary = collate_resources(&fixture.manifest_file()).unwrap();
+    assert_eq!(summary.skipped.len(), 1);
+    assert_eq!(fixture.read("target/resources/greeting.txt"), "hello");
+
+    // Unless every source is hashed
+    let summary = collate_with_options(&fixture.manifest_file(), CollateOptions::default().always_hash(true)).unwrap();
+    assert_eq!(summary.copied.len(), 1);
+    assert_eq!(fixture.read("target/resources/greeting.txt"), "howdy");
+}
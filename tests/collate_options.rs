@@ -0,0 +1,406 @@
+mod common;
+
+use std::fs;
+
+use cargo_resources::{
+    check_unique_names, check_unique_names_with, collate_resources, collate_resources_with_reporter, collate_with_options,
+    collate_workspace, explain_resource, explain_resource_with, file_sha, list_available_resources,
+    list_available_resources_with, plan_changes, plan_changes_with, plan_collation_with, read_record, record_file_path,
+    record_file_path_with, verify_resources, verify_resources_with, BuildRsReporter, CollateOptions, CollationError,
+    DuplicateStrategy, HashAlgorithm, LineEndings, PlannedChange, RecordFormat, ResourceDrift, DEFAULT_RECORD_FILE_NAME,
+    HASHED_FILENAMES_MANIFEST, LOCK_FILE_NAME,
+};
+
+use common::Fixture;
+
+#[test]
+fn line_endings_normalize_only_text_resources() {
+    let fixture = Fixture::new("line_endings")
+        .file("notes.txt", "one\r\ntwo\nthree\r\n")
+        .file("logo.bin", b"\x89PNG\r\n\x1a\n")
+        .file("mesh.raw", b"\0\x01\r\n")
+        .package(r#"
+provides = [
+    { crate_path = "notes.txt" },
+    { crate_path = "logo.bin", encoding = "Bin" },
+    { crate_path = "mesh.raw" },
+]
+"#);
+    let lf = || CollateOptions::default().line_endings(LineEndings::Lf);
+
+    let summary = collate_with_options(&fixture.manifest_file(), lf()).unwrap();
+    assert_eq!(fs::read(fixture.path("target/resources/notes.txt")).unwrap(), b"one\ntwo\nthree\n");
+
+    // Both the source's sha (for required_sha) and the normalized copy's sha are recorded
+    let notes = summary.copied.iter().find(|resource| resource.specification.resource_name == "notes.txt").unwrap();
+    assert_eq!(notes.source_sha, file_sha(&fixture.path("notes.txt"), HashAlgorithm::Sha256).unwrap());
+    assert_eq!(notes.output_sha, file_sha(&fixture.path("target/resources/notes.txt"), HashAlgorithm::Sha256).unwrap());
+    assert_ne!(notes.source_sha, notes.output_sha);
+    assert_eq!(fs::read(fixture.path("target/resources/logo.bin")).unwrap(), b"\x89PNG\r\n\x1a\n");
+    assert_eq!(fs::read(fixture.path("target/resources/mesh.raw")).unwrap(), b"\0\x01\r\n");
+
+    // The normalized copy is up to date, so isn't copied again
+    let summary = collate_with_options(&fixture.manifest_file(), lf()).unwrap();
+    assert_eq!(summary.skipped.len(), 3);
+
+    collate_with_options(&fixture.manifest_file(), CollateOptions::default().line_endings(LineEndings::Crlf)).unwrap();
+    assert_eq!(fs::read(fixture.path("target/resources/notes.txt")).unwrap(), b"one\r\ntwo\r\nthree\r\n");
+    assert_eq!(fs::read(fixture.path("target/resources/logo.bin")).unwrap(), b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn hashed_filenames_are_mapped_from_the_unhashed_paths() {
+    let fixture = Fixture::new("hashed_filenames")
+        .file("app.css", "body {}")
+        .package(r#"provides = [{ crate_path = "app.css", output_path = "css/app.css" }]"#);
+    let options = CollateOptions::default().hashed_filenames(true);
+    let summary = collate_with_options(&fixture.manifest_file(), options).unwrap();
+
+    // The sha256 of "body {}" starts 62368a1a
+    assert!(fixture.path("target/resources/css/app.62368a1a.css").is_file());
+    let manifest = fs::read_to_string(summary.resource_root.join(HASHED_FILENAMES_MANIFEST)).unwrap();
+    assert!(manifest.contains(r#""css/app.css": "css/app.62368a1a.css""#));
+}
+
+#[test]
+fn deny_unused_fails_for_resources_not_required() {
+    let fixture = Fixture::new("deny_unused")
+        .file("used.txt", "used")
+        .file("unused.txt", "unused")
+        .package(r#"
+provides = [{ crate_path = "used.txt" }, { crate_path = "unused.txt" }]
+requires = [{ resource_name = "used.txt" }]
+"#);
+    let summary = collate_with_options(&fixture.manifest_file(), CollateOptions::default()).unwrap();
+    assert_eq!(summary.copied.len(), 1);
+
+    match collate_with_options(&fixture.manifest_file(), CollateOptions::default().deny_unused(true)).unwrap_err() {
+        CollationError::UnusedResources(unused) => assert_eq!(unused[0].resource_name, "unused.txt"),
+        error => panic!("Unexpected error: {}", error),
+    }
+}
+
+#[test]
+fn unverified_shas_collate_the_actual_resource() {
+    let fixture = Fixture::new("no_verify")
+        .file("framework.css", "body { color: red }")
+        .package(r#"
+provides = [{ crate_path = "framework.css" }]
+requires = [{ resource_name = "framework.css", required_sha = "62368a1a29259b30bac235c0e75dc700c9b3bacf1513ad5708e4fe4a6c0d6560" }]
+"#);
+    match collate_with_options(&fixture.manifest_file(), CollateOptions::default()).unwrap_err() {
+        CollationError::ShaMismatch { resource, .. } => assert_eq!(resource, "framework.css"),
+        error => panic!("Unexpected error: {}", error),
+    }
+
+    let summary = collate_with_options(&fixture.manifest_file(), CollateOptions::default().verify_shas(false)).unwrap();
+    assert_ne!(summary.copied[0].output_sha, "62368a1a29259b30bac235c0e75dc700c9b3bacf1513ad5708e4fe4a6c0d6560");
+}
+
+#[test]
+fn hash_buffer_size_doesnt_change_the_hashes() {
+    let content: Vec<u8> = (0..4_000_000u32).map(|i| (i % 251) as u8).collect();
+    let fixture = Fixture::new("hash_buffer_size")
+        .file("large.bin", content)
+        .package(r#"provides = [{ crate_path = "large.bin", encoding = "Bin" }]"#);
+    let default_summary = collate_with_options(&fixture.manifest_file(), CollateOptions::default()).unwrap();
+
+    let options = CollateOptions::default().hash_buffer_size(1024 * 1024).always_hash(true);
+    let large_block_summary = collate_with_options(&fixture.manifest_file(), options).unwrap();
+    assert_eq!(default_summary.copied[0].output_sha, large_block_summary.skipped[0].output_sha);
+}
+
+#[test]
+fn duplicate_strategy_chooses_the_declaration() {
+    let provides = r#"provides = [{ crate_path = "greeting.txt" }]"#;
+    let fixture = Fixture::new("duplicate_strategy")
+        .workspace(&["app", "older"])
+        .member("app", "0.2.0", r#"older = { path = "../older" }"#, provides)
+        .file("app/greeting.txt", "Hello from app")
+        .member("older", "0.1.0", "", provides)
+        .file("older/greeting.txt", "Hello from older");
+    let manifest_file = fixture.path("app/Cargo.toml");
+
+    let options = CollateOptions::default().duplicate_strategy(DuplicateStrategy::HighestVersion);
+    let summary = collate_with_options(&manifest_file, options).unwrap();
+    assert_eq!(summary.copied[0].specification.declaring_crate_name, "app");
+
+    let options = CollateOptions::default().duplicate_strategy(DuplicateStrategy::Error);
+    assert!(collate_with_options(&manifest_file, options).is_err());
+}
+
+#[test]
+fn locked_collation_fails_for_changed_resources() {
+    let fixture = Fixture::new("locked")
+        .file("logo.svg", "<svg/>")
+        .package(r#"provides = [{ crate_path = "logo.svg" }]"#);
+
+    // Collating writes Cargo.lock and cargo-resources.lock, which are then up to date
+    collate_with_options(&fixture.manifest_file(), CollateOptions::default().offline(true)).unwrap();
+    assert!(fixture.path("Cargo.lock").is_file());
+    assert!(fixture.path(LOCK_FILE_NAME).is_file());
+    let locked = || CollateOptions::default().locked(true).offline(true);
+    collate_with_options(&fixture.manifest_file(), locked()).unwrap();
+
+    // A changed resource no longer matches its locked sha
+    let fixture = fixture.file("logo.svg", "<svg></svg>");
+    let error = collate_with_options(&fixture.manifest_file(), locked()).unwrap_err();
+    assert!(matches!(error, CollationError::LockOutOfDate(_)));
+}
+
+#[test]
+fn record_formats_read_back_as_written() {
+    let fixture = Fixture::new("record_format")
+        .file("a.txt", "a")
+        .file("b.bin", [0u8, 1, 2])
+        .package(r#"provides = [{ crate_path = "a.txt" }, { crate_path = "b.bin", output_path = "bin/b.bin", encoding = "Bin" }]"#);
+    #[allow(unused_mut)]
+    let mut formats = vec!(RecordFormat::Json, RecordFormat::PrettyJson);
+    #[cfg(feature = "toml")]
+    formats.push(RecordFormat::Toml);
+    #[cfg(feature = "yaml")]
+    formats.push(RecordFormat::Yaml);
+    for format in formats {
+        let options = CollateOptions::default().record_format(format);
+        let summary = collate_with_options(&fixture.manifest_file(), options).unwrap();
+        let record_file = summary.resource_root.join(DEFAULT_RECORD_FILE_NAME).with_extension(format.extension());
+        let record = read_record(&record_file).unwrap();
+
+        let collated = summary.copied.iter().chain(summary.skipped.iter());
+        assert_eq!(
+            serde_json::to_value(&record).unwrap(),
+            serde_json::to_value(collated.collect::<Vec<_>>()).unwrap(),
+        );
+    }
+}
+
+#[test]
+fn rust_module_maps_names_to_output_paths() {
+    let fixture = Fixture::new("rust_module")
+        .file("framework.css", "body {}")
+        .package(r#"provides = [{ crate_path = "framework.css", output_path = "css/framework.css" }]"#);
+    let options = CollateOptions::default().generate_rust_module(fixture.path("generated/resources.rs"));
+    collate_with_options(&fixture.manifest_file(), options).unwrap();
+
+    let module = fixture.read("generated/resources.rs");
+    assert!(module.contains(r#"("framework.css", "css/framework.css"),"#));
+    assert!(module.contains(r#""framework.css" => Some("css/framework.css"),"#));
+}
+
+#[test]
+fn integrity_manifest_maps_output_paths_to_sri_strings() {
+    let fixture = Fixture::new("integrity_manifest")
+        .file("framework.css", "body {}")
+        .package(r#"provides = [{ crate_path = "framework.css", output_path = "css/framework.css" }]"#);
+    let options = CollateOptions::default().integrity_manifest(fixture.path("integrity.json"));
+    collate_with_options(&fixture.manifest_file(), options).unwrap();
+
+    assert!(fixture.read("integrity.json").contains(
+        r#""css/framework.css": "sha384-JvbluEOKMBmUtNHx346xlZFWqKqtOmexOupPSHRCR0NbwTey4wjq9itKKoSWuGsH""#
+    ));
+}
+
+#[test]
+fn names_manifest_lists_resources_in_name_order() {
+    let fixture = Fixture::new("names_manifest")
+        .file("framework.css", "body {}")
+        .file("app.js", "main()")
+        .package(r#"
+provides = [
+    { crate_path = "framework.css", output_path = "css/framework.css" },
+    { crate_path = "app.js", output_path = "js/app.js" },
+]
+"#);
+    let options = CollateOptions::default().names_manifest(fixture.path("names.json"));
+    collate_with_options(&fixture.manifest_file(), options).unwrap();
+
+    assert_eq!(fixture.read("names.json"), r#"{
+  "app.js": "js/app.js",
+  "framework.css": "css/framework.css"
+}
+"#);
+}
+
+#[test]
+fn verify_checks_the_resources_as_collated_with_the_options() {
+    let fixture = Fixture::new("verify_with_options")
+        .file("app.css", "body {}")
+        .package(r#"provides = [{ crate_path = "app.css" }]"#);
+    let options = || CollateOptions::default().resource_root_override(fixture.path("target/site"));
+    collate_with_options(&fixture.manifest_file(), options()).unwrap();
+
+    verify_resources_with(&fixture.manifest_file(), &options()).unwrap();
+    match verify_resources(&fixture.manifest_file()).unwrap_err() {
+        CollationError::ResourcesOutOfDate(drift) => assert!(matches!(drift[0], ResourceDrift::Missing { .. })),
+        error => panic!("Unexpected error: {}", error),
+    }
+}
+
+#[test]
+fn plan_checks_the_resources_as_collated_with_the_options() {
+    let fixture = Fixture::new("plan_with_options")
+        .file("app.css", "body {}")
+        .package(r#"provides = [{ crate_path = "app.css" }]"#);
+    let options = || CollateOptions::default().resource_root_override(fixture.path("target/site"));
+    collate_with_options(&fixture.manifest_file(), options()).unwrap();
+
+    let changes = plan_changes(&plan_collation_with(&fixture.manifest_file(), &options()).unwrap()).unwrap();
+    assert!(matches!(changes.changes[0], PlannedChange::Unchanged { .. }));
+    let changes = plan_changes(&plan_collation_with(&fixture.manifest_file(), &CollateOptions::default()).unwrap()).unwrap();
+    assert!(matches!(changes.changes[0], PlannedChange::Add { .. }));
+}
+
+#[test]
+fn list_and_why_search_the_crates_collation_would() {
+    let fixture = Fixture::new("list_with_options")
+        .workspace(&["app", "framework"])
+        .member("app", "0.1.0", r#"framework = { path = "../framework" }"#, r#"provides = [{ crate_path = "app.js" }]"#)
+        .file("app/app.js", "main()")
+        .member("framework", "0.1.0", "", r#"provides = [{ crate_path = "framework.css" }]"#)
+        .file("framework/framework.css", "body {}");
+    let manifest_file = fixture.path("app/Cargo.toml");
+    let options = CollateOptions::default().exclude_crates(vec!("framework".to_string()));
+
+    assert_eq!(list_available_resources(&manifest_file).unwrap().len(), 2);
+    let available = list_available_resources_with(&manifest_file, &options).unwrap();
+    assert_eq!(available.len(), 1);
+    assert_eq!(available[0].resource_name, "app.js");
+
+    assert!(explain_resource(&manifest_file, "framework.css").is_ok());
+    assert!(explain_resource_with(&manifest_file, "framework.css", &options).is_err());
+}
+
+#[test]
+fn planned_changes_list_the_resources_pruning_removes() {
+    let fixture = Fixture::new("plan_prune")
+        .file("app.css", "body {}")
+        .file("old.css", "p {}")
+        .package(r#"provides = [{ crate_path = "app.css" }, { crate_path = "old.css", output_path = "css/old.css" }]"#);
+    collate_with_options(&fixture.manifest_file(), CollateOptions::default()).unwrap();
+
+    let fixture = fixture.package(r#"provides = [{ crate_path = "app.css" }]"#);
+    let plan = plan_collation_with(&fixture.manifest_file(), &CollateOptions::default()).unwrap();
+    assert_eq!(plan_changes(&plan).unwrap().changes.len(), 1);
+
+    let changes = plan_changes_with(&plan, &CollateOptions::default().prune(true)).unwrap();
+    match &changes.changes[1] {
+        PlannedChange::Remove { resource_name, output_path, .. } => {
+            assert_eq!(resource_name, "old.css");
+            assert_eq!(output_path, "css/old.css");
+        }
+        change => panic!("Unexpected change: {:?}", change),
+    }
+    assert!(!changes.is_empty());
+    assert!(changes.to_string().contains("remove"));
+    assert!(fixture.path("target/resources/css/old.css").is_file());
+}
+
+#[test]
+fn record_file_path_follows_the_record_options() {
+    let fixture = Fixture::new("record_file_path")
+        .file("app.css", "body {}")
+        .file("old.css", "p {}")
+        .package(r#"provides = [{ crate_path = "app.css" }, { crate_path = "old.css" }]"#);
+    let options = || CollateOptions::default().record_path("records/resources.json".into()).prune(true);
+    assert_eq!(record_file_path(&fixture.manifest_file()).unwrap(), fixture.path("target/resources/resolved_resources.json"));
+    let record_file = record_file_path_with(&fixture.manifest_file(), &options()).unwrap();
+    assert_eq!(record_file, fixture.path("records/resources.json"));
+    let pretty = CollateOptions::default().record_format(RecordFormat::PrettyJson).resource_root_override(fixture.path("site"));
+    assert_eq!(record_file_path_with(&fixture.manifest_file(), &pretty).unwrap(), fixture.path("site/resolved_resources.json"));
+
+    collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    assert_eq!(read_record(&record_file).unwrap().len(), 2);
+
+    // Pruning reads the record from the same place
+    let fixture = fixture.package(r#"provides = [{ crate_path = "app.css" }]"#);
+    collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    assert!(!fixture.path("target/resources/old.css").exists());
+    assert_eq!(read_record(&record_file).unwrap().len(), 1);
+}
+
+#[test]
+fn target_is_substituted_into_the_resource_root() {
+    let fixture = Fixture::new("target_triple")
+        .file("greeting.txt", "hello")
+        .package(r#"
+resource_root = "target/{triple}/resources"
+provides = [{ crate_path = "greeting.txt" }]
+"#);
+    let options = || CollateOptions::default().target("wasm32-unknown-unknown".to_string());
+
+    collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    assert_eq!(fixture.read("target/wasm32-unknown-unknown/resources/greeting.txt"), "hello");
+    assert_eq!(
+        record_file_path_with(&fixture.manifest_file(), &options()).unwrap(),
+        fixture.path("target/wasm32-unknown-unknown/resources").join(DEFAULT_RECORD_FILE_NAME),
+    );
+}
+
+#[test]
+fn workspace_members_have_their_own_directory_in_a_shared_resource_root() {
+    let fixture = Fixture::new("workspace_root_override")
+        .workspace(&["one", "two"])
+        .member("one", "0.1.0", "", r#"provides = [{ crate_path = "greeting.txt" }]"#)
+        .member("two", "0.1.0", "", r#"provides = [{ crate_path = "greeting.txt" }]"#)
+        .file("one/greeting.txt", "hello")
+        .file("two/greeting.txt", "howdy");
+    let options = CollateOptions::default().resource_root_override(fixture.path("dist"));
+
+    let summaries = collate_workspace(&fixture.manifest_file(), options).unwrap();
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(fixture.read("dist/one/greeting.txt"), "hello");
+    assert_eq!(fixture.read("dist/two/greeting.txt"), "howdy");
+    assert!(fixture.path("dist/one").join(DEFAULT_RECORD_FILE_NAME).is_file());
+    assert!(fixture.path("dist/two").join(DEFAULT_RECORD_FILE_NAME).is_file());
+}
+
+#[test]
+fn collating_with_a_reporter_locks_the_resources_as_collating_does() {
+    let fixture = Fixture::new("reporter_lock")
+        .file("logo.svg", "<svg/>")
+        .package(r#"provides = [{ crate_path = "logo.svg" }]"#);
+
+    collate_resources_with_reporter(&fixture.manifest_file(), &BuildRsReporter).unwrap();
+    let lock = fixture.read(LOCK_FILE_NAME);
+    fs::remove_file(fixture.path(LOCK_FILE_NAME)).unwrap();
+    fs::remove_dir_all(fixture.path("target/resources")).unwrap();
+    collate_resources(&fixture.manifest_file()).unwrap();
+    assert_eq!(fixture.read(LOCK_FILE_NAME), lock);
+}
+
+#[test]
+fn workspace_members_are_collated_into_their_own_resource_roots() {
+    let fixture = Fixture::new("virtual_workspace")
+        .workspace(&["one", "two"])
+        .member("one", "0.1.0", "", r#"provides = [{ crate_path = "greeting.txt" }]"#)
+        .member("two", "0.1.0", "", r#"provides = [{ crate_path = "greeting.txt" }]"#)
+        .file("one/greeting.txt", "hello")
+        .file("two/greeting.txt", "howdy");
+
+    let summaries = collate_workspace(&fixture.manifest_file(), CollateOptions::default()).unwrap();
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(fixture.read("one/target/resources/greeting.txt"), "hello");
+    assert_eq!(fixture.read("two/target/resources/greeting.txt"), "howdy");
+}
+
+#[test]
+fn every_name_conflict_is_found_with_the_collation_options() {
+    let provides = r#"provides = [{ crate_path = "app.css" }, { crate_path = "logo.svg" }]"#;
+    let fixture = Fixture::new("name_conflicts")
+        .workspace(&["app", "theme"])
+        .member("app", "0.2.0", r#"theme = { path = "../theme" }"#, provides)
+        .file("app/app.css", "body {}")
+        .file("app/logo.svg", "<svg/>")
+        .member("theme", "0.1.0", "", provides)
+        .file("theme/app.css", "body { color: red }")
+        .file("theme/logo.svg", "<svg></svg>");
+    let manifest_file = fixture.path("app/Cargo.toml");
+
+    let conflicts = check_unique_names(&manifest_file).unwrap();
+    let names: Vec<&str> = conflicts.iter().map(|conflict| conflict.resource_name.as_str()).collect();
+    assert_eq!(names, ["app.css", "logo.svg"]);
+    assert!(conflicts.iter().all(|conflict| conflict.declarations.len() == 2));
+
+    let options = CollateOptions::default().exclude_crates(vec!("theme".to_string()));
+    assert!(check_unique_names_with(&manifest_file, &options).unwrap().is_empty());
+}
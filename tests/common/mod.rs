@@ -0,0 +1,87 @@
+//! The fixture shared by the integration tests: crates written below a temporary directory.
+#![allow(dead_code)]
+
+use std::fs;
+
+use cargo_metadata::camino::Utf8PathBuf;
+
+/// A crate (or a workspace of crates) written below its own temporary directory, which is emptied
+/// when the fixture is created.
+///
+/// Each test names its own fixture, so the tests can run in parallel.
+pub struct Fixture {
+    /// The fixture's directory, the root of the crate or workspace
+    pub root: Utf8PathBuf,
+    name: String,
+}
+
+impl Fixture {
+    /// An empty fixture, whose crate (if written with [Fixture::package]) has the given name
+    pub fn new(name: &str) -> Fixture {
+        let root = Utf8PathBuf::from_path_buf(std::env::temp_dir()).unwrap()
+            .join("cargo_resources_tests")
+            .join(name);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Fixture { root, name: name.to_owned() }
+    }
+
+    /// Write a file (relative to the fixture root), creating its directory
+    pub fn file(self, path: &str, contents: impl AsRef<[u8]>) -> Fixture {
+        let path = self.root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        self
+    }
+
+    /// Write the crate at the fixture root, as a workspace of its own, with the given
+    /// [package.metadata.cargo_resources] content (replacing any crate written before)
+    pub fn package(self, metadata: &str) -> Fixture {
+        let manifest = format!("{}\n[workspace]\n", manifest(&self.name, "0.1.0", "", metadata));
+        self.file("src/lib.rs", "").file("Cargo.toml", manifest)
+    }
+
+    /// Write a workspace of the given member directories at the fixture root
+    pub fn workspace(self, members: &[&str]) -> Fixture {
+        let members: Vec<String> = members.iter().map(|member| format!("{:?}", member)).collect();
+        self.file("Cargo.toml", format!("[workspace]\nmembers = [{}]\n", members.join(", ")))
+    }
+
+    /// Write a workspace member crate, named for its directory, with the given [dependencies] and
+    /// [package.metadata.cargo_resources] content
+    pub fn member(self, directory: &str, version: &str, dependencies: &str, metadata: &str) -> Fixture {
+        self.file(&format!("{}/src/lib.rs", directory), "")
+            .file(&format!("{}/Cargo.toml", directory), manifest(directory, version, dependencies, metadata))
+    }
+
+    /// The path of a file relative to the fixture root
+    pub fn path(&self, path: &str) -> Utf8PathBuf {
+        self.root.join(path)
+    }
+
+    /// The cargo manifest of the crate at the fixture root
+    pub fn manifest_file(&self) -> Utf8PathBuf {
+        self.path("Cargo.toml")
+    }
+
+    /// Read a file (relative to the fixture root) as text
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.path(path)).unwrap()
+    }
+}
+
+/// A crate's cargo manifest
+fn manifest(name: &str, version: &str, dependencies: &str, metadata: &str) -> String {
+    format!(r#"
+[package]
+name = "{name}"
+version = "{version}"
+edition = "2021"
+
+[dependencies]
+{dependencies}
+
+[package.metadata.cargo_resources]
+{metadata}
+"#)
+}
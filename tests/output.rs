@@ -0,0 +1,114 @@
+mod common;
+
+use std::sync::Mutex;
+
+use cargo_metadata::camino::Utf8Path;
+use cargo_resources::{collate_with_options, CollateOptions, CollationError, FsSink, HashAlgorithm, OutputSink};
+
+use common::Fixture;
+
+/// The output paths written to the [Recording] sink
+static WRITTEN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// A sink keeping the paths written, in memory
+struct Recording;
+
+impl OutputSink for Recording {
+    fn exists_with_sha(&self, _: &Utf8Path, _: &String, _: HashAlgorithm) -> Result<bool, CollationError> {
+        Ok(false)
+    }
+
+    fn write_resource(&self, output_path: &Utf8Path, _: &[u8], _: Option<u32>) -> Result<(), CollationError> {
+        WRITTEN.lock().unwrap().push(output_path.to_string());
+        Ok(())
+    }
+}
+
+#[test]
+fn sink_is_given_resources_and_record() {
+    let fixture = Fixture::new("sink")
+        .file("framework.css", "body {}")
+        .package(r#"provides = [{ crate_path = "framework.css", output_path = "css/framework.css" }]"#);
+    collate_with_options(&fixture.manifest_file(), CollateOptions::default().sink(Box::new(Recording))).unwrap();
+
+    assert_eq!(*WRITTEN.lock().unwrap(), vec!("css/framework.css", "resolved_resources.json"));
+    assert!(!fixture.path("target/resources").exists());
+}
+
+#[test]
+fn fs_sink_writes_below_its_directory() {
+    let fixture = Fixture::new("fs_sink")
+        .file("framework.css", "body {}")
+        .package(r#"provides = [{ crate_path = "framework.css", output_path = "css/framework.css" }]"#);
+    let options = || CollateOptions::default().sink(Box::new(FsSink::new(fixture.path("site"))));
+
+    collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    assert_eq!(fixture.read("site/css/framework.css"), "body {}");
+    assert!(fixture.path("site/resolved_resources.json").is_file());
+
+    let summary = collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    assert_eq!(summary.skipped.len(), 1);
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zip_archive_is_reproducible() {
+    use std::fs;
+
+    use cargo_resources::OutputTarget;
+
+    let fixture = Fixture::new("zip_archive")
+        .file("framework.css", "body {}")
+        .file("print.css", "@media print {}")
+        .package(r#"provides = [{ crate_path = "framework.css", output_path = "css/framework.css" }]"#);
+    let options = || CollateOptions::default().output_target(OutputTarget::ZipArchive("resources.zip".into()));
+
+    let summary = collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    assert_eq!(summary.copied.len(), 1);
+    let archive = fs::read(fixture.path("resources.zip")).unwrap();
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(&archive)).unwrap();
+    assert_eq!(zip.file_names().count(), 2);
+    assert!(zip.by_name("css/framework.css").is_ok());
+    assert!(zip.by_name("resolved_resources.json").is_ok());
+
+    // Collating again leaves the identical archive in place
+    fs::remove_file(fixture.path("resources.zip")).unwrap();
+    collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    assert_eq!(fs::read(fixture.path("resources.zip")).unwrap(), archive);
+    let summary = collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    assert_eq!(summary.skipped.len(), 1);
+
+    // Two resources can't share an entry
+    let fixture = fixture.package(r#"provides = [
+    { crate_path = "framework.css", output_path = "site.css" },
+    { crate_path = "print.css", output_path = "site.css", resource_name = "print" },
+]"#);
+    let error = collate_with_options(&fixture.manifest_file(), options()).unwrap_err();
+    assert!(matches!(error, CollationError::DuplicateOutputPath { .. }));
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn tar_archive_keeps_modes() {
+    use cargo_resources::OutputTarget;
+
+    let fixture = Fixture::new("tar_archive")
+        .file("start.sh", "#!/bin/sh\n")
+        .package(r#"
+file_mode = 0o644
+provides = [{ crate_path = "start.sh", output_path = "bin/start.sh", executable = true }]
+"#);
+    let options = CollateOptions::default().output_target(OutputTarget::TarArchive("resources.tar".into()));
+    collate_with_options(&fixture.manifest_file(), options).unwrap();
+
+    let archive = std::fs::File::open(fixture.path("resources.tar")).unwrap();
+    let entries: Vec<(String, u32)> = tar::Archive::new(archive).entries().unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            (entry.path().unwrap().display().to_string(), entry.header().mode().unwrap())
+        })
+        .collect();
+    #[cfg(unix)]
+    assert_eq!(entries[0], ("bin/start.sh".to_string(), 0o755));
+    assert_eq!(entries[1].0, "resolved_resources.json");
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::fs;
+
+use cargo_resources::{collate_with_options, CollateOptions, LOCK_FILE_NAME};
+
+use common::Fixture;
+
+/// The files in a directory (recursively), relative to it, sorted
+fn files_below(fixture: &Fixture, directory: &str) -> Vec<String> {
+    let mut pending = vec!(fixture.path(directory));
+    let mut files = vec!();
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(&directory).unwrap() {
+            let path = entry.unwrap().path();
+            match path.is_dir() {
+                true => pending.push(path.try_into().unwrap()),
+                false => files.push(path.strip_prefix(&fixture.root).unwrap().display().to_string()),
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[test]
+fn written_files_are_all_replaced_or_none_are() {
+    let fixture = Fixture::new("staged_files")
+        .file("app.css", "body {}")
+        .file("app.js", "main()")
+        .package(r#"provides = [{ crate_path = "app.css" }]"#);
+    let options = || CollateOptions::default()
+        .emit_checksums(true)
+        .generate_rust_module("target/generated/resources.rs".into())
+        .names_manifest("target/resources/names".into());
+    collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    let written = ["target/generated/resources.rs", "target/resources/resolved_resources.json", LOCK_FILE_NAME];
+    let before: Vec<String> = written.iter().map(|path| fixture.read(path)).collect();
+
+    // The names manifest can't replace a directory, so none of the files collation writes beside
+    // the resources change (though the new resource is copied)
+    fs::remove_file(fixture.path("target/resources/names")).unwrap();
+    let fixture = fixture
+        .file("target/resources/names/occupied", "")
+        .package(r#"provides = [{ crate_path = "app.css" }, { crate_path = "app.js" }]"#);
+    assert!(collate_with_options(&fixture.manifest_file(), options()).is_err());
+    let after: Vec<String> = written.iter().map(|path| fixture.read(path)).collect();
+    assert_eq!(before, after);
+    assert!(!fixture.path("target/resources/app.js.sha256").exists());
+
+    // Nor are any temporary files left behind
+    assert_eq!(files_below(&fixture, "target/generated"), ["target/generated/resources.rs"]);
+    assert_eq!(files_below(&fixture, "target/resources"), [
+        "target/resources/app.css",
+        "target/resources/app.css.sha256",
+        "target/resources/app.js",
+        "target/resources/names/occupied",
+        "target/resources/resolved_resources.json",
+    ]);
+    assert!(!fixture.root.read_dir().unwrap().any(|entry| entry.unwrap().file_name().to_string_lossy().starts_with('.')));
+
+    // Once the names manifest can be written, every file is
+    fs::remove_dir_all(fixture.path("target/resources/names")).unwrap();
+    collate_with_options(&fixture.manifest_file(), options()).unwrap();
+    assert!(fixture.read(LOCK_FILE_NAME).contains("app.js"));
+    assert!(fixture.read("target/resources/resolved_resources.json").contains("app.js"));
+    assert!(fixture.path("target/resources/app.js.sha256").is_file());
+}